@@ -0,0 +1,167 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::*;
+use framework::RunLevel;
+
+/// An app registered with the applications service
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisteredApp {
+    /// The UUID of the app
+    pub uuid: String,
+    /// The name of the app
+    pub name: String,
+    /// The version of the app
+    pub version: String,
+    /// The author of the app
+    pub author: String,
+    /// The pid of the app's process, or 0 if it isn't running
+    pub pid: i32,
+    /// Whether the app is currently running
+    pub running: bool,
+    /// The path to the app's executable
+    pub path: String,
+    /// How the app is restarted when it exits
+    pub restart_policy: String,
+}
+
+/// A version of an app in the applications service's registry
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppEntry {
+    /// The app
+    pub app: RegisteredApp,
+    /// Whether this is the active version of the app
+    pub active: bool,
+    /// The version which is rolled back to if this one keeps crashing
+    pub previous_version: Option<String>,
+}
+
+/// Client for the applications service
+#[derive(Clone, Debug)]
+pub struct AppServiceClient {
+    client: ServiceClient,
+}
+
+impl AppServiceClient {
+    /// Create an applications service client
+    pub fn new(client: ServiceClient) -> Self {
+        AppServiceClient { client }
+    }
+
+    /// Fetch every registered version of every app
+    pub fn apps(&self) -> ClientResult<Vec<AppEntry>> {
+        self.client.query(
+            "{ apps { app { uuid, name, version, author, pid, running, path, restartPolicy }, active, previousVersion } }",
+            json!({}),
+            "apps",
+        )
+    }
+
+    /// Fetch the active version of an app
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid` - The UUID of the app
+    pub fn active_app(&self, uuid: &str) -> ClientResult<Option<AppEntry>> {
+        let request = r#"query App($uuid: String) {
+            apps(uuid: $uuid, active: true) {
+                app { uuid, name, version, author, pid, running, path, restartPolicy },
+                active,
+                previousVersion
+            }
+        }"#;
+
+        let mut entries: Vec<AppEntry> =
+            self.client
+                .query(request, json!({ "uuid": uuid }), "apps")?;
+        Ok(entries.pop())
+    }
+
+    /// Start the active version of an app. Returns the pid of the new process.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid` - The UUID of the app
+    /// * `run_level` - The run level to start the app with
+    /// * `args` - Additional arguments to pass to the app
+    pub fn start_app(&self, uuid: &str, run_level: &RunLevel, args: &[&str]) -> ClientResult<i32> {
+        let request = r#"mutation StartApp($uuid: String!, $runLevel: String!, $args: [String!]) {
+            startApp(uuid: $uuid, runLevel: $runLevel, args: $args)
+        }"#;
+
+        self.client.mutate(
+            request,
+            json!({
+                "uuid": uuid,
+                "runLevel": run_level.to_string(),
+                "args": args,
+            }),
+            "startApp",
+        )
+    }
+
+    /// Stop a running app, sending it `SIGTERM`
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid` - The UUID of the app
+    pub fn stop_app(&self, uuid: &str) -> ClientResult<()> {
+        let request = r#"mutation StopApp($uuid: String!) {
+            stopApp(uuid: $uuid)
+        }"#;
+
+        self.client
+            .mutate::<bool>(request, json!({ "uuid": uuid }), "stopApp")
+            .map(|_| ())
+    }
+
+    /// Send a message to a running app
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid` - The UUID of the app
+    /// * `message` - The message to send. It must not contain any newlines
+    pub fn send_message(&self, uuid: &str, message: &str) -> ClientResult<()> {
+        let request = r#"mutation SendMessage($uuid: String!, $message: String!) {
+            sendMessage(uuid: $uuid, message: $message)
+        }"#;
+
+        self.client
+            .mutate::<bool>(
+                request,
+                json!({ "uuid": uuid, "message": message }),
+                "sendMessage",
+            )
+            .map(|_| ())
+    }
+
+    /// Fetch the most recent lines an app wrote to stdout
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid` - The UUID of the app
+    /// * `lines` - The maximum number of lines to return
+    pub fn app_logs(&self, uuid: &str, lines: i32) -> ClientResult<Vec<String>> {
+        let request = r#"query AppLogs($uuid: String!, $lines: Int) {
+            appLogs(uuid: $uuid, lines: $lines)
+        }"#;
+
+        self.client
+            .query(request, json!({ "uuid": uuid, "lines": lines }), "appLogs")
+    }
+}
@@ -0,0 +1,124 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::*;
+
+/// Overall deployment status of the antennas
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DeploymentStatus {
+    /// All antennas have been deployed
+    Deployed,
+    /// At least one antenna is being deployed
+    InProgress,
+    /// Some, but not all, antennas have been deployed
+    Partial,
+    /// All antennas are stowed
+    Stowed,
+    /// At least one antenna failed to deploy
+    Error,
+}
+
+/// Which antennas to deploy
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum DeployTarget {
+    /// Deploy every antenna, one after another
+    #[serde(rename = "ALL")]
+    All,
+    /// Deploy antenna 1
+    #[serde(rename = "ANTENNA1")]
+    Antenna1,
+    /// Deploy antenna 2
+    #[serde(rename = "ANTENNA2")]
+    Antenna2,
+    /// Deploy antenna 3
+    #[serde(rename = "ANTENNA3")]
+    Antenna3,
+    /// Deploy antenna 4
+    #[serde(rename = "ANTENNA4")]
+    Antenna4,
+}
+
+/// Client for the ISIS antenna system service
+#[derive(Clone, Debug)]
+pub struct IsisAntsClient {
+    client: ServiceClient,
+}
+
+impl IsisAntsClient {
+    /// Create an ISIS antenna system service client
+    pub fn new(client: ServiceClient) -> Self {
+        IsisAntsClient { client }
+    }
+
+    /// Get the current deployment status of the antennas
+    pub fn deployment_status(&self) -> ClientResult<DeploymentStatus> {
+        #[derive(Deserialize)]
+        struct Response {
+            status: DeploymentStatus,
+        }
+
+        let response: Response = self.client.query(
+            "{ deploymentStatus { status } }",
+            json!({}),
+            "deploymentStatus",
+        )?;
+        Ok(response.status)
+    }
+
+    /// Arm or disarm the antenna system. It must be armed before the antennas can be deployed.
+    ///
+    /// # Arguments
+    ///
+    /// * `armed` - Whether the system should be armed
+    pub fn arm(&self, armed: bool) -> ClientResult<()> {
+        let request = r#"mutation Arm($state: ArmState!) {
+            arm(state: $state) { success, errors }
+        }"#;
+
+        let state = if armed { "ARM" } else { "DISARM" };
+        let response: MutationResponse =
+            self.client
+                .mutate(request, json!({ "state": state }), "arm")?;
+        response.check()
+    }
+
+    /// Deploy antennas
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - Which antennas to deploy
+    /// * `force` - Whether to deploy antennas which are already deployed
+    /// * `time` - How many seconds to spend deploying each antenna
+    pub fn deploy(&self, target: DeployTarget, force: bool, time: i32) -> ClientResult<()> {
+        let request = r#"mutation Deploy($ant: DeployType, $force: Boolean, $time: Int!) {
+            deploy(ant: $ant, force: $force, time: $time) { success, errors }
+        }"#;
+
+        let response: MutationResponse = self.client.mutate(
+            request,
+            json!({ "ant": target, "force": force, "time": time }),
+            "deploy",
+        )?;
+        response.check()
+    }
+}
+
+impl HardwareClient for IsisAntsClient {
+    fn client(&self) -> &ServiceClient {
+        &self.client
+    }
+}
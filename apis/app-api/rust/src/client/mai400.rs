@@ -0,0 +1,92 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::*;
+
+/// Rotation rates reported by the MAI-400, in degrees per second
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Spin {
+    /// X-axis rotation rate
+    pub x: f64,
+    /// Y-axis rotation rate
+    pub y: f64,
+    /// Z-axis rotation rate
+    pub z: f64,
+}
+
+/// Client for the MAI-400 ADACS service
+#[derive(Clone, Debug)]
+pub struct Mai400Client {
+    client: ServiceClient,
+}
+
+impl Mai400Client {
+    /// Create a MAI-400 service client
+    pub fn new(client: ServiceClient) -> Self {
+        Mai400Client { client }
+    }
+
+    /// Get the current ACS mode, such as `"RATE_NULLING"`
+    pub fn mode(&self) -> ClientResult<String> {
+        self.client.query("{ mode }", json!({}), "mode")
+    }
+
+    /// Get the most recently reported rotation rates
+    pub fn spin(&self) -> ClientResult<Spin> {
+        self.client.query("{ spin { x, y, z } }", json!({}), "spin")
+    }
+
+    /// Set the ACS mode
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The new mode, such as `"NADIR_POINTING"`
+    /// * `qbi_cmd` - The mode's four command arguments
+    pub fn set_mode(&self, mode: &str, qbi_cmd: [i32; 4]) -> ClientResult<()> {
+        let request = r#"mutation SetMode($mode: Mode!, $qbiCmd: [Int!]) {
+            setMode(mode: $mode, qbiCmd: $qbiCmd) { success, errors }
+        }"#;
+
+        let response: MutationResponse = self.client.mutate(
+            request,
+            json!({ "mode": mode, "qbiCmd": qbi_cmd }),
+            "setMode",
+        )?;
+        response.check()
+    }
+
+    /// Update the ADACS clock
+    ///
+    /// # Arguments
+    ///
+    /// * `gps_time` - The current GPS time, in seconds
+    pub fn update_time(&self, gps_time: i32) -> ClientResult<()> {
+        let request = r#"mutation Update($gpsTime: Int) {
+            update(gpsTime: $gpsTime) { success, errors }
+        }"#;
+
+        let response: MutationResponse =
+            self.client
+                .mutate(request, json!({ "gpsTime": gps_time }), "update")?;
+        response.check()
+    }
+}
+
+impl HardwareClient for Mai400Client {
+    fn client(&self) -> &ServiceClient {
+        &self.client
+    }
+}
@@ -0,0 +1,281 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Typed clients for the KubOS services
+//!
+//! Each client wraps a `ServiceClient`, which sends the GraphQL requests with `query_with_options`
+//! and converts the responses into Rust types.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate kubos_app;
+//! use kubos_app::client::*;
+//! use std::time::Duration;
+//!
+//! # fn func() -> Result<(), ClientError> {
+//! let telemetry = TelemetryClient::new(
+//!     ServiceClient::new("telemetry-service").timeout(Duration::from_millis(500)),
+//! );
+//!
+//! telemetry.insert("eps", "voltage", "3.3")?;
+//! # Ok(())
+//! # }
+//! # fn main() {}
+//! ```
+
+mod app_service;
+mod isis_ants;
+mod mai400;
+mod monitor;
+mod novatel;
+mod telemetry;
+
+pub use self::app_service::*;
+pub use self::isis_ants::*;
+pub use self::mai400::*;
+pub use self::monitor::*;
+pub use self::novatel::*;
+pub use self::telemetry::*;
+
+use kubos_system::Config as ServiceConfig;
+use query::{query_with_options, QueryError, QueryOptions};
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+use std::io;
+use std::time::Duration;
+
+/// Errors returned by the service clients
+#[derive(Debug, Fail, PartialEq)]
+pub enum ClientError {
+    /// The service couldn't be reached, or didn't respond in time
+    #[fail(display = "Failed to communicate with service: {}", _0)]
+    Communication(String),
+    /// The service rejected the request
+    #[fail(display = "Service returned an error: {}", _0)]
+    Service(QueryError),
+    /// The response from the service didn't have the expected format
+    #[fail(display = "Unexpected response from service: {}", _0)]
+    Response(String),
+    /// The service carried out the request, but reported that it failed
+    #[fail(display = "Request failed: {}", _0)]
+    Failed(String),
+}
+
+/// The result type used by the service clients
+pub type ClientResult<T> = Result<T, ClientError>;
+
+/// How many times a query is resent if no response arrives, by default
+const DEFAULT_QUERY_RETRIES: u32 = 2;
+
+/// Sends requests to a single service
+///
+/// Queries which get no response are resent, up to the configured number of retries
+/// (two, by default). Mutations are only sent once, since they may not be safe to repeat.
+#[derive(Clone, Debug)]
+pub struct ServiceClient {
+    config: ServiceConfig,
+    options: QueryOptions,
+}
+
+impl ServiceClient {
+    /// Create a client for a service, using the default config file
+    ///
+    /// # Arguments
+    ///
+    /// * `service` - The name of the service, as used in the config file
+    pub fn new(service: &str) -> Self {
+        ServiceClient::from_config(ServiceConfig::new(service))
+    }
+
+    /// Create a client for a service from its configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration of the service
+    pub fn from_config(config: ServiceConfig) -> Self {
+        ServiceClient {
+            config,
+            options: QueryOptions {
+                retries: DEFAULT_QUERY_RETRIES,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Set how long to wait for each response from the service
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeout = Some(timeout);
+        self
+    }
+
+    /// Set how many times a query is resent if the service doesn't respond
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.options.retries = retries;
+        self
+    }
+
+    /// Set how long to wait before resending a query for the first time.
+    /// The delay doubles with each retry.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.options.backoff = backoff;
+        self
+    }
+
+    /// Send a query and convert the requested field of the result
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The GraphQL query, declaring the variables it uses
+    /// * `variables` - A JSON object mapping each variable name to its value
+    /// * `field` - The field of the result to return
+    pub fn query<T: DeserializeOwned>(
+        &self,
+        request: &str,
+        variables: Value,
+        field: &str,
+    ) -> ClientResult<T> {
+        self.send(request, variables, field, &self.options)
+    }
+
+    /// Send a mutation and convert the requested field of the result
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The GraphQL mutation, declaring the variables it uses
+    /// * `variables` - A JSON object mapping each variable name to its value
+    /// * `field` - The field of the result to return
+    pub fn mutate<T: DeserializeOwned>(
+        &self,
+        request: &str,
+        variables: Value,
+        field: &str,
+    ) -> ClientResult<T> {
+        let options = QueryOptions {
+            retries: 0,
+            ..self.options.clone()
+        };
+        self.send(request, variables, field, &options)
+    }
+
+    fn send<T: DeserializeOwned>(
+        &self,
+        request: &str,
+        variables: Value,
+        field: &str,
+        options: &QueryOptions,
+    ) -> ClientResult<T> {
+        let result = query_with_options(self.config.clone(), request, Some(variables), options)
+            .map_err(|err| {
+                let err = match err.downcast::<QueryError>() {
+                    Ok(query_err) => return ClientError::Service(query_err),
+                    Err(err) => err,
+                };
+                match err.downcast_ref::<io::Error>() {
+                    Some(io_err) => ClientError::Communication(io_err.to_string()),
+                    None => ClientError::Response(err.to_string()),
+                }
+            })?;
+
+        let value = match result.get(field) {
+            Some(value) => value.clone(),
+            None => {
+                return Err(ClientError::Response(format!(
+                    "No '{}' field in {}",
+                    field, result
+                )))
+            }
+        };
+
+        serde_json::from_value(value)
+            .map_err(|err| ClientError::Response(format!("Invalid '{}' field: {}", field, err)))
+    }
+}
+
+/// Response fields of the mutations which report whether they succeeded
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct MutationResponse {
+    success: bool,
+    errors: String,
+}
+
+impl MutationResponse {
+    fn check(self) -> ClientResult<()> {
+        if self.success {
+            Ok(())
+        } else {
+            Err(ClientError::Failed(self.errors))
+        }
+    }
+}
+
+/// Power state reported by a hardware service
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PowerState {
+    /// The device is powered on
+    On,
+    /// The device is powered off
+    Off,
+    /// The device is resetting
+    Reset,
+}
+
+/// Current power status of a hardware device
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct PowerStatus {
+    /// Power state of the device
+    pub state: PowerState,
+    /// Seconds since the device was powered on
+    pub uptime: i32,
+}
+
+/// Requests common to all of the hardware services
+pub trait HardwareClient {
+    /// The client used to send requests to the service
+    fn client(&self) -> &ServiceClient;
+
+    /// Check that the service is running
+    fn ping(&self) -> ClientResult<()> {
+        let pong: String = self.client().query("{ ping }", json!({}), "ping")?;
+        match pong.as_str() {
+            "pong" => Ok(()),
+            _ => Err(ClientError::Response(format!(
+                "Unexpected ping reply: {}",
+                pong
+            ))),
+        }
+    }
+
+    /// Get the current power status of the device
+    fn power(&self) -> ClientResult<PowerStatus> {
+        self.client()
+            .query("{ power { state, uptime } }", json!({}), "power")
+    }
+
+    /// Get the errors which have been encountered since they were last fetched
+    fn errors(&self) -> ClientResult<Vec<String>> {
+        self.client().query("{ errors }", json!({}), "errors")
+    }
+
+    /// Check that the device is responding
+    fn noop(&self) -> ClientResult<()> {
+        let response: MutationResponse =
+            self.client()
+                .mutate("mutation { noop { success, errors } }", json!({}), "noop")?;
+        response.check()
+    }
+}
@@ -0,0 +1,93 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::*;
+
+/// System memory information, in kB
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MemInfo {
+    /// Total usable memory
+    pub total: Option<i32>,
+    /// Memory which isn't being used at all
+    pub free: Option<i32>,
+    /// Memory available for starting new applications
+    pub available: Option<i32>,
+    /// Free memory in the low memory region
+    pub low_free: Option<i32>,
+}
+
+/// Information about a running process
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ProcessInfo {
+    /// Process ID
+    pub pid: i32,
+    /// User ID of the process owner
+    pub uid: Option<i32>,
+    /// Group ID of the process owner
+    pub gid: Option<i32>,
+    /// Name of the process owner
+    pub usr: Option<String>,
+    /// Group name of the process owner
+    pub grp: Option<String>,
+    /// Process state
+    pub state: Option<String>,
+    /// Parent process ID
+    pub ppid: Option<i32>,
+    /// Virtual memory size, in bytes
+    pub mem: Option<i32>,
+    /// Resident set size, in pages
+    pub rss: Option<i32>,
+    /// Number of threads
+    pub threads: Option<i32>,
+    /// Command line of the process
+    pub cmd: Option<String>,
+}
+
+/// Client for the monitor service
+#[derive(Clone, Debug)]
+pub struct MonitorClient {
+    client: ServiceClient,
+}
+
+impl MonitorClient {
+    /// Create a monitor service client
+    pub fn new(client: ServiceClient) -> Self {
+        MonitorClient { client }
+    }
+
+    /// Get the system's memory usage
+    pub fn mem_info(&self) -> ClientResult<MemInfo> {
+        self.client.query(
+            "{ memInfo { total, free, available, lowFree } }",
+            json!({}),
+            "memInfo",
+        )
+    }
+
+    /// Get information about running processes
+    ///
+    /// # Arguments
+    ///
+    /// * `pids` - The processes to report on. If `None`, all running processes are reported
+    pub fn ps(&self, pids: Option<&[i32]>) -> ClientResult<Vec<ProcessInfo>> {
+        let request = r#"query PS($pids: [Int!]) {
+            ps(pids: $pids) { pid, uid, gid, usr, grp, state, ppid, mem, rss, threads, cmd }
+        }"#;
+
+        self.client.query(request, json!({ "pids": pids }), "ps")
+    }
+}
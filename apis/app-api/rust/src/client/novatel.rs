@@ -0,0 +1,92 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::*;
+
+/// GPS time, as reported by the NovAtel OEM6
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct OemTime {
+    /// GPS week number
+    pub week: i32,
+    /// Milliseconds into the week
+    pub ms: i32,
+}
+
+/// Lock status of the NovAtel OEM6
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LockStatus {
+    /// Validity of the time, such as `"FINE_STEERING"`
+    pub time_status: String,
+    /// When the status was last updated
+    pub time: OemTime,
+    /// Status of the position solution, such as `"SOL_COMPUTED"`
+    pub position_status: String,
+    /// Type of the position solution
+    pub position_type: String,
+    /// Status of the velocity solution
+    pub velocity_status: String,
+    /// Type of the velocity solution
+    pub velocity_type: String,
+}
+
+/// The last known good position and velocity of the NovAtel OEM6
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct LockInfo {
+    /// When the position and velocity were last updated
+    pub time: OemTime,
+    /// ECEF X, Y and Z position, in meters
+    pub position: Vec<f64>,
+    /// ECEF X, Y and Z velocity, in meters per second
+    pub velocity: Vec<f64>,
+}
+
+/// Client for the NovAtel OEM6 GPS service
+#[derive(Clone, Debug)]
+pub struct NovatelClient {
+    client: ServiceClient,
+}
+
+impl NovatelClient {
+    /// Create a NovAtel OEM6 service client
+    pub fn new(client: ServiceClient) -> Self {
+        NovatelClient { client }
+    }
+
+    /// Get the current lock status of the receiver
+    pub fn lock_status(&self) -> ClientResult<LockStatus> {
+        self.client.query(
+            "{ lockStatus { timeStatus, time { week, ms }, positionStatus, positionType, velocityStatus, velocityType } }",
+            json!({}),
+            "lockStatus",
+        )
+    }
+
+    /// Get the last known good position and velocity of the receiver
+    pub fn lock_info(&self) -> ClientResult<LockInfo> {
+        self.client.query(
+            "{ lockInfo { time { week, ms }, position, velocity } }",
+            json!({}),
+            "lockInfo",
+        )
+    }
+}
+
+impl HardwareClient for NovatelClient {
+    fn client(&self) -> &ServiceClient {
+        &self.client
+    }
+}
@@ -0,0 +1,134 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::*;
+
+/// A telemetry entry stored by the telemetry database service
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct TelemetryEntry {
+    /// When the entry was recorded, in seconds since the epoch
+    pub timestamp: i32,
+    /// Subsystem name
+    pub subsystem: String,
+    /// Telemetry parameter
+    pub parameter: String,
+    /// Telemetry value
+    pub value: String,
+}
+
+/// Filters for telemetry queries. Unset fields match all entries.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TelemetryFilter {
+    /// Only return entries recorded at or after this time
+    pub timestamp_ge: Option<i32>,
+    /// Only return entries recorded at or before this time
+    pub timestamp_le: Option<i32>,
+    /// Only return entries for this subsystem
+    pub subsystem: Option<String>,
+    /// Only return entries for this parameter
+    pub parameter: Option<String>,
+    /// Maximum number of entries to return
+    pub limit: Option<i32>,
+}
+
+/// Client for the telemetry database service
+#[derive(Clone, Debug)]
+pub struct TelemetryClient {
+    client: ServiceClient,
+}
+
+impl TelemetryClient {
+    /// Create a telemetry database service client
+    pub fn new(client: ServiceClient) -> Self {
+        TelemetryClient { client }
+    }
+
+    /// Store a telemetry value, timestamped with the current system time
+    ///
+    /// # Arguments
+    ///
+    /// * `subsystem` - Subsystem name
+    /// * `parameter` - Telemetry parameter
+    /// * `value` - Telemetry value
+    pub fn insert(&self, subsystem: &str, parameter: &str, value: &str) -> ClientResult<()> {
+        self.send_insert(None, subsystem, parameter, value)
+    }
+
+    /// Store a telemetry value with the given timestamp
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - When the value was recorded, in seconds since the epoch
+    /// * `subsystem` - Subsystem name
+    /// * `parameter` - Telemetry parameter
+    /// * `value` - Telemetry value
+    pub fn insert_at(
+        &self,
+        timestamp: i32,
+        subsystem: &str,
+        parameter: &str,
+        value: &str,
+    ) -> ClientResult<()> {
+        self.send_insert(Some(timestamp), subsystem, parameter, value)
+    }
+
+    /// Fetch the telemetry entries matching the filter, most recent first
+    pub fn query(&self, filter: &TelemetryFilter) -> ClientResult<Vec<TelemetryEntry>> {
+        let request = r#"query Telemetry($timestampGe: Int, $timestampLe: Int, $subsystem: String, $parameter: String, $limit: Int) {
+            telemetry(timestampGe: $timestampGe, timestampLe: $timestampLe, subsystem: $subsystem, parameter: $parameter, limit: $limit) {
+                timestamp, subsystem, parameter, value
+            }
+        }"#;
+
+        self.client.query(
+            request,
+            json!({
+                "timestampGe": filter.timestamp_ge,
+                "timestampLe": filter.timestamp_le,
+                "subsystem": filter.subsystem,
+                "parameter": filter.parameter,
+                "limit": filter.limit,
+            }),
+            "telemetry",
+        )
+    }
+
+    fn send_insert(
+        &self,
+        timestamp: Option<i32>,
+        subsystem: &str,
+        parameter: &str,
+        value: &str,
+    ) -> ClientResult<()> {
+        let request = r#"mutation Insert($timestamp: Int, $subsystem: String!, $parameter: String!, $value: String!) {
+            insert(timestamp: $timestamp, subsystem: $subsystem, parameter: $parameter, value: $value) {
+                success, errors
+            }
+        }"#;
+
+        let response: MutationResponse = self.client.mutate(
+            request,
+            json!({
+                "timestamp": timestamp,
+                "subsystem": subsystem,
+                "parameter": parameter,
+                "value": value,
+            }),
+            "insert",
+        )?;
+        response.check()
+    }
+}
@@ -20,8 +20,21 @@
 #![deny(warnings)]
 
 use getopts::Options;
+use libc;
+use std::cmp;
 use std::env;
 use std::fmt;
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the framework checks whether the app has been asked to shut down
+const SHUTDOWN_POLL_MS: u64 = 100;
+
+// Set when the app receives SIGTERM or SIGINT, or calls `request_shutdown`
+pub(crate) static SHUTDOWN: AtomicBool = AtomicBool::new(false);
 
 /// The different ways an application can be started
 #[derive(Clone, Debug, PartialEq)]
@@ -42,12 +55,155 @@ impl fmt::Display for RunLevel {
 }
 
 /// Common trait which is used to ensure handlers for all required run levels are defined
+///
+/// The remaining hooks are optional. They're only used by apps which keep running after
+/// their run level handler returns (see `keep_alive`), and are all called from the app's
+/// main thread.
+///
+/// # Examples
+///
+/// ```
+/// use kubos_app::AppHandler;
+/// use std::time::Duration;
+///
+/// struct MyApp;
+///
+/// impl AppHandler for MyApp {
+///   fn on_boot(&self, _args: Vec<String>) {
+///     println!("OnBoot logic");
+///   }
+///   fn on_command(&self, _args: Vec<String>) {
+///     println!("OnCommand logic");
+///   }
+///   fn tick_interval(&self) -> Option<Duration> {
+///     Some(Duration::from_secs(10))
+///   }
+///   fn on_tick(&self) {
+///     println!("Checking on the payload");
+///   }
+///   fn on_message(&self, message: String) {
+///     println!("Received {}", message);
+///   }
+///   fn on_shutdown(&self) {
+///     println!("Cleaning up");
+///   }
+/// }
+/// ```
 pub trait AppHandler {
     /// Called when the application is started at system boot time
     fn on_boot(&self, args: Vec<String>);
 
     /// Called when the application is started on-demand through the `start_app` GraphQL mutation
     fn on_command(&self, args: Vec<String>);
+
+    /// Whether the application should keep running after its run level handler returns,
+    /// handling ticks and messages until it is shut down.
+    ///
+    /// Defaults to `true` if the application has a tick interval
+    fn keep_alive(&self) -> bool {
+        self.tick_interval().is_some()
+    }
+
+    /// How often `on_tick` should be called. Defaults to `None` (never)
+    fn tick_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Called periodically, according to `tick_interval`
+    fn on_tick(&self) {}
+
+    /// Called with each message sent to the application through the applications
+    /// service's `sendMessage` GraphQL mutation
+    fn on_message(&self, _message: String) {}
+
+    /// Called when the application is asked to shut down (ex. by the `stopApp` GraphQL
+    /// mutation sending SIGTERM). The application exits once this returns
+    fn on_shutdown(&self) {}
+}
+
+/// Whether the application has been asked to shut down.
+///
+/// Run level handlers which don't return until the application is done should
+/// check this periodically, so that `on_shutdown` can be called.
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::SeqCst)
+}
+
+/// Ask the framework to shut the application down, as if it had received SIGTERM
+pub fn request_shutdown() {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn handle_shutdown_signal(_signal: libc::c_int) {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+// Record shutdown requests instead of letting SIGTERM and SIGINT kill the app
+pub(crate) fn install_signal_handlers() {
+    let handler = handle_shutdown_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGINT, handler);
+    }
+}
+
+// Read messages from the applications service, which sends them one per line on stdin
+fn read_messages() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            match line {
+                Ok(line) => {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    receiver
+}
+
+// Deliver ticks and messages to the app until it's asked to shut down
+pub(crate) fn run_events(handler: &AppHandler, messages: &Receiver<String>) {
+    let interval = handler.tick_interval();
+    let mut next_tick = interval.map(|interval| Instant::now() + interval);
+    let mut connected = true;
+
+    loop {
+        if shutdown_requested() {
+            handler.on_shutdown();
+            return;
+        }
+
+        let now = Instant::now();
+        let mut wait = Duration::from_millis(SHUTDOWN_POLL_MS);
+
+        if let (Some(due), Some(interval)) = (next_tick, interval) {
+            if due <= now {
+                handler.on_tick();
+                // Skip any ticks which were missed while the handler was busy
+                next_tick = Some(cmp::max(due + interval, Instant::now()));
+                continue;
+            }
+            wait = cmp::min(wait, due - now);
+        }
+
+        if !connected {
+            thread::sleep(wait);
+            continue;
+        }
+
+        match messages.recv_timeout(wait) {
+            Ok(message) => handler.on_message(message),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => connected = false,
+        }
+    }
 }
 
 /// A helper macro which detects the requested run level and calls the appropriate handler function
@@ -116,6 +272,16 @@ pub fn app_start(_pid: u32, handler: &AppHandler) {
     let _uuid = env::var_os("KUBOS_APP_UUID");
     let run_level = matches.opt_str("r").unwrap_or("OnCommand".to_owned());
 
+    // Apps which keep running handle shutdown requests themselves, and start
+    // queueing messages right away so none are lost while the run level handler runs
+    let keep_alive = handler.keep_alive();
+    let messages = if keep_alive {
+        install_signal_handlers();
+        Some(read_messages())
+    } else {
+        None
+    };
+
     match run_level.as_ref() {
         "OnBoot" => {
             handler.on_boot(args);
@@ -130,4 +296,8 @@ pub fn app_start(_pid: u32, handler: &AppHandler) {
             return;
         }
     }
+
+    if let Some(messages) = messages {
+        run_events(handler, &messages);
+    }
 }
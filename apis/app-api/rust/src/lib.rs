@@ -17,6 +17,9 @@
 //! A simple API to make standalone Rust applications with high-level hooks
 //! for mission life-cycle management
 //!
+//! Services can be queried directly with `query`, or through the typed clients
+//! in the `client` module.
+//!
 //! # Examples
 //!
 //! ```
@@ -82,19 +85,24 @@ extern crate juniper;
 #[cfg(test)]
 extern crate kubos_service;
 extern crate kubos_system;
-#[cfg(not(test))]
-extern crate serde_json;
-#[cfg(test)]
+extern crate libc;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 #[macro_use]
 extern crate serde_json;
 #[cfg(test)]
 extern crate tempfile;
 
+pub mod client;
 mod framework;
 mod query;
 #[cfg(test)]
 mod tests;
 
 pub use framework::*;
-pub use query::query;
+pub use query::{
+    query, query_with_options, query_with_variables, ErrorLocation, QueryError, QueryOptions,
+    ServiceError,
+};
 pub use kubos_system::Config as ServiceConfig;
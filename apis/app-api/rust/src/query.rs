@@ -15,17 +15,152 @@
  */
 
 use failure;
-use kubos_system::Config as ServiceConfig;
-use serde_json;
+use kubos_system::{recv_response, Config as ServiceConfig};
+use serde_json::{self, Value};
+use std::fmt;
 use std::net::UdpSocket;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 use std::time::Duration;
 
 /// The result type used by `query`
 type AppResult<T> = Result<T, failure::Error>;
 
+/// How long to wait for each response, by default
+const DEFAULT_TIMEOUT_SECS: u64 = 1;
+/// The delay before the first retry, by default
+const DEFAULT_BACKOFF_MS: u64 = 100;
+
+/// Used to give each request sent by this process a unique ID
+static REQUEST_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Settings which control how a request is sent to a service
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryOptions {
+    /// How long to wait for each response. The request blocks until a response arrives
+    /// when `None` is provided here
+    pub timeout: Option<Duration>,
+    /// How many times the request is resent if no response arrives.
+    /// Only set this for requests which are safe to repeat: a mutation whose response was
+    /// lost would be carried out again
+    pub retries: u32,
+    /// How long to wait before the first retry. The delay doubles with each retry
+    pub backoff: Duration,
+}
+
+/// By default, requests are sent once and each response is waited on for one second
+impl Default for QueryOptions {
+    fn default() -> Self {
+        QueryOptions {
+            timeout: Some(Duration::from_secs(DEFAULT_TIMEOUT_SECS)),
+            retries: 0,
+            backoff: Duration::from_millis(DEFAULT_BACKOFF_MS),
+        }
+    }
+}
+
+impl QueryOptions {
+    // Send a request once, waiting for the given time for a response
+    fn once(timeout: Option<Duration>) -> Self {
+        QueryOptions {
+            timeout,
+            retries: 0,
+            backoff: Duration::from_millis(0),
+        }
+    }
+}
+
+/// The location in a query of the cause of a service error
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorLocation {
+    /// The line of the query
+    pub line: u64,
+    /// The column of the line
+    pub column: u64,
+}
+
+/// An error returned by a service
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServiceError {
+    /// A description of the error
+    pub message: String,
+    /// Where in the query the error occurred
+    pub locations: Vec<ErrorLocation>,
+    /// The path of the result field which produced the error
+    pub path: Vec<String>,
+}
+
+impl ServiceError {
+    fn from_value(value: &Value) -> ServiceError {
+        let message = match value.get("message").and_then(|message| message.as_str()) {
+            Some(message) => message.to_owned(),
+            None => match value.as_str() {
+                Some(message) => message.to_owned(),
+                None => value.to_string(),
+            },
+        };
+
+        let locations = value
+            .get("locations")
+            .and_then(|locations| locations.as_array())
+            .map(|locations| {
+                locations
+                    .iter()
+                    .map(|location| ErrorLocation {
+                        line: location["line"].as_u64().unwrap_or(0),
+                        column: location["column"].as_u64().unwrap_or(0),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let path = value
+            .get("path")
+            .and_then(|path| path.as_array())
+            .map(|path| {
+                path.iter()
+                    .map(|item| match item.as_str() {
+                        Some(name) => name.to_owned(),
+                        None => item.to_string(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        ServiceError {
+            message,
+            locations,
+            path,
+        }
+    }
+}
+
+/// The error returned when a service reports that a request failed
+#[derive(Clone, Debug, Fail, PartialEq)]
+pub struct QueryError {
+    /// The errors reported by the service
+    pub errors: Vec<ServiceError>,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let messages: Vec<&str> = self
+            .errors
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
 /// Execute a GraphQL query against a running KubOS Service using UDP.
 ///
-/// Returns the parsed JSON result as a serde_json::Value on success
+/// Returns the parsed JSON result as a serde_json::Value on success.
+/// If the service reports any errors, a `QueryError` listing them is returned instead
+///
+/// Responses which the service split into multiple fragments (see the `chunk_size` service
+/// config option) are reassembled before being parsed
 ///
 /// # Arguments
 ///
@@ -34,8 +169,8 @@ type AppResult<T> = Result<T, failure::Error>;
 ///                   of the service to query. If `None` is specified, the default config location will be
 ///                   used
 /// * `query` - The raw GraphQL query as a string
-/// * `timeout` - The timeout provided to the UDP socket. Note: This function will block when `None`
-///               is provided here
+/// * `timeout` - The timeout provided to the UDP socket. This applies to each datagram of the
+///               response. Note: This function will block when `None` is provided here
 ///
 /// # Examples
 ///
@@ -86,44 +221,217 @@ pub fn query(
     query: &str,
     timeout: Option<Duration>,
 ) -> AppResult<serde_json::Value> {
+    send_request(config, query, None, &QueryOptions::once(timeout))
+}
+
+/// Execute a GraphQL query with variables against a running KubOS Service using UDP.
+///
+/// The query and variables are sent to the service together as a JSON object, so values
+/// never need to be interpolated into the query text.
+///
+/// Returns the parsed JSON result as a serde_json::Value on success
+///
+/// # Arguments
+///
+/// * `config` - The configuration of the service to send the query to
+/// * `query` - The raw GraphQL query as a string, declaring the variables it uses
+/// * `variables` - A JSON object mapping each variable name to its value
+/// * `timeout` - The timeout provided to the UDP socket. Note: This function will block when `None`
+///               is provided here
+///
+/// # Examples
+///
+/// ```
+/// # extern crate failure;
+/// # extern crate kubos_app;
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// use kubos_app::*;
+/// use std::time::Duration;
+///
+/// # fn func() -> Result<(), failure::Error> {
+/// let request = r#"mutation Insert($subsystem: String!, $param: String!, $value: String!) {
+///         insert(subsystem: $subsystem, parameter: $param, value: $value) {
+///             success
+///         }
+///     }"#;
+///
+/// let variables = json!({
+///     "subsystem": "eps",
+///     "param": "voltage",
+///     "value": "3.3"
+/// });
+///
+/// let result = query_with_variables(
+///     ServiceConfig::new("telemetry-service"),
+///     request,
+///     variables,
+///     Some(Duration::from_secs(1)),
+/// )?;
+/// # Ok(())
+/// # }
+/// # fn main() {}
+/// ```
+///
+pub fn query_with_variables(
+    config: ServiceConfig,
+    query: &str,
+    variables: serde_json::Value,
+    timeout: Option<Duration>,
+) -> AppResult<serde_json::Value> {
+    send_request(config, query, Some(variables), &QueryOptions::once(timeout))
+}
+
+/// Execute a GraphQL query against a running KubOS Service using UDP, resending it if
+/// no response arrives in time.
+///
+/// Requests are only resent if `options.retries` is set, which should never be done for
+/// mutations, since a mutation whose response was lost would be carried out again.
+/// `ServiceClient::mutate` always sends mutations once, whatever its configured retries.
+///
+/// Requests with variables or retries are sent with a unique ID, which the service echoes
+/// in its response, so a late response to an earlier request is never mistaken for the
+/// response to this one. Other queries are sent as plain query strings.
+///
+/// Returns the parsed JSON result as a serde_json::Value on success.
+/// If the service reports any errors, a `QueryError` listing them is returned instead
+///
+/// # Arguments
+///
+/// * `config` - The configuration of the service to send the query to
+/// * `query` - The raw GraphQL query as a string
+/// * `variables` - An optional JSON object mapping each variable used by the query to its value
+/// * `options` - How long to wait for responses and how often to retry
+///
+/// # Examples
+///
+/// ```
+/// # extern crate failure;
+/// # extern crate kubos_app;
+/// use kubos_app::*;
+/// use std::time::Duration;
+///
+/// # fn func() -> Result<(), failure::Error> {
+/// // Queries are safe to resend
+/// let options = QueryOptions {
+///     retries: 5,
+///     ..Default::default()
+/// };
+///
+/// let result = query_with_options(
+///     ServiceConfig::new("radio-service"),
+///     "{ power }",
+///     None,
+///     &options,
+/// )?;
+/// # Ok(())
+/// # }
+/// # fn main() {}
+/// ```
+///
+pub fn query_with_options(
+    config: ServiceConfig,
+    query: &str,
+    variables: Option<serde_json::Value>,
+    options: &QueryOptions,
+) -> AppResult<serde_json::Value> {
+    send_request(config, query, variables, options)
+}
+
+// Send a request to the service, retrying as needed, and decode its response.
+//
+// Queries without variables which are only sent once go out as plain query strings,
+// which every service understands. Otherwise the query is wrapped in a JSON envelope
+// along with its variables and a unique ID
+fn send_request(
+    config: ServiceConfig,
+    query: &str,
+    variables: Option<Value>,
+    options: &QueryOptions,
+) -> AppResult<Value> {
+    let (request, id) = match variables {
+        None if options.retries == 0 => (query.to_owned(), None),
+        variables => {
+            let id = format!(
+                "{}-{}",
+                process::id(),
+                REQUEST_COUNT.fetch_add(1, Ordering::SeqCst)
+            );
+
+            let mut request = json!({
+                "query": query,
+                "id": id,
+            });
+            if let Some(variables) = variables {
+                request["variables"] = variables;
+            }
+            (request.to_string(), Some(id))
+        }
+    };
+
+    let mut attempt = 0;
+    let mut delay = options.backoff;
+    let response = loop {
+        match exchange(&config, request.as_bytes(), id.as_ref(), options.timeout) {
+            Ok(response) => break response,
+            Err(_) if attempt < options.retries => {
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    };
+
+    decode_response(response)
+}
+
+// Send a request and wait for the matching response.
+//
+// Each attempt uses a new socket, so fragments of a late response to an earlier attempt
+// can never be mixed into this one. Responses which carry a different request ID are
+// skipped. Responses without an ID come from services which don't echo request IDs,
+// so are accepted.
+fn exchange(
+    config: &ServiceConfig,
+    request: &[u8],
+    id: Option<&String>,
+    timeout: Option<Duration>,
+) -> AppResult<Value> {
     let socket = UdpSocket::bind("0.0.0.0:0")?;
     socket.connect(config.hosturl())?;
-    socket.send(query.as_bytes())?;
 
     // Allow the caller to set a read timeout on the socket
-    socket.set_read_timeout(timeout).unwrap();
+    socket.set_read_timeout(timeout)?;
 
-    let mut buf = [0; 4096];
-    let (amt, _) = socket.recv_from(&mut buf)?;
+    socket.send(request)?;
 
-    let v: serde_json::Value = serde_json::from_slice(&buf[0..(amt)])?;
+    loop {
+        let response = recv_response(&socket)?;
+        let v: Value = serde_json::from_slice(&response)?;
 
-    if let Some(errs) = v.get("errs") {
-        if errs.is_string() {
-            let errs_str = errs.as_str().unwrap();
-            if errs_str.len() > 0 {
-                return Err(format_err!("{}", errs_str.to_string()));
-            }
-        } else if !errs.is_null() {
-            match errs.get("message") {
-                Some(message) => {
-                    return Err(format_err!("{}", message.as_str().unwrap().to_string()));
-                }
-                None => {
-                    return Err(format_err!("{}", serde_json::to_string(errs).unwrap()));
-                }
-            }
+        match (v.get("id"), id) {
+            (Some(reply_id), Some(id)) if reply_id.as_str() != Some(id) => continue,
+            _ => return Ok(v),
+        }
+    }
+}
+
+// Get the result from a service's response, or the errors it reported
+fn decode_response(v: Value) -> AppResult<Value> {
+    // Errors which stopped the query from being run are returned as a list
+    if let Some(errs) = v.as_array() {
+        return Err(QueryError {
+            errors: errs.iter().map(ServiceError::from_value).collect(),
         }
+        .into());
     }
 
-    match v.get(0) {
-        Some(err) if err.get("message").is_some() => {
-            return Err(format_err!(
-                "{}",
-                err["message"].as_str().unwrap().to_string(),
-            ));
+    if let Some(errs) = v.get("errs") {
+        let errors = parse_errors(errs);
+        if !errors.is_empty() {
+            return Err(QueryError { errors }.into());
         }
-        _ => {}
     }
 
     match v.get("msg") {
@@ -134,3 +442,23 @@ pub fn query(
         )),
     }
 }
+
+// Parse the `errs` field of a service's response. Services concatenate the JSON
+// of each error into a single string
+fn parse_errors(errs: &Value) -> Vec<ServiceError> {
+    match errs {
+        Value::Null => vec![],
+        Value::String(errs) if errs.is_empty() => vec![],
+        Value::String(errs) => {
+            let parsed: Result<Vec<Value>, _> = serde_json::Deserializer::from_str(errs)
+                .into_iter::<Value>()
+                .collect();
+            match parsed {
+                Ok(values) => values.iter().map(ServiceError::from_value).collect(),
+                Err(_) => vec![ServiceError::from_value(&Value::String(errs.clone()))],
+            }
+        }
+        Value::Array(errs) => errs.iter().map(ServiceError::from_value).collect(),
+        other => vec![ServiceError::from_value(other)],
+    }
+}
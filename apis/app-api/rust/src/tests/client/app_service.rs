@@ -0,0 +1,189 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::mock_client;
+use client::*;
+use framework::RunLevel;
+use juniper::{FieldError, FieldResult, Value};
+use kubos_service::{self, Service};
+use kubos_system::Config as ServiceConfig;
+use tempfile::TempDir;
+
+// A mock applications service, with the same argument types as the real one.
+// It knows of two versions of a single app, the second of which is active.
+struct Subsystem;
+type Context = kubos_service::Context<Subsystem>;
+
+const UUID: &str = "a1b2c3";
+
+#[derive(GraphQLObject)]
+struct KApp {
+    uuid: String,
+    name: String,
+    version: String,
+    author: String,
+    pid: i32,
+    running: bool,
+    path: String,
+    restart_policy: String,
+}
+
+#[derive(GraphQLObject)]
+struct KAppRegistryEntry {
+    app: KApp,
+    active: bool,
+    previous_version: Option<String>,
+}
+
+fn entries() -> Vec<KAppRegistryEntry> {
+    ["1.0", "2.0"]
+        .iter()
+        .map(|version| KAppRegistryEntry {
+            app: KApp {
+                uuid: UUID.to_owned(),
+                name: "mission-app".to_owned(),
+                version: version.to_string(),
+                author: "user".to_owned(),
+                pid: 0,
+                running: false,
+                path: format!("/apps/{}/{}/mission-app", UUID, version),
+                restart_policy: "Never".to_owned(),
+            },
+            active: *version == "2.0",
+            previous_version: match *version {
+                "2.0" => Some("1.0".to_owned()),
+                _ => None,
+            },
+        })
+        .collect()
+}
+
+fn check_uuid(uuid: &str) -> FieldResult<()> {
+    match uuid {
+        UUID => Ok(()),
+        _ => Err(FieldError::new(
+            format!("App {} not found", uuid),
+            Value::null(),
+        )),
+    }
+}
+
+struct QueryRoot;
+
+graphql_object!(QueryRoot: Context as "Query" |&self| {
+    field ping() -> FieldResult<String> {
+        Ok(String::from("pong"))
+    }
+
+    field apps(&executor,
+               uuid: Option<String>,
+               name: Option<String>,
+               version: Option<String>,
+               active: Option<bool>)
+        -> FieldResult<Vec<KAppRegistryEntry>>
+    {
+        Ok(entries()
+            .into_iter()
+            .filter(|entry| uuid.as_ref().map_or(true, |uuid| *uuid == entry.app.uuid))
+            .filter(|entry| name.as_ref().map_or(true, |name| *name == entry.app.name))
+            .filter(|entry| version.as_ref().map_or(true, |version| *version == entry.app.version))
+            .filter(|entry| active.map_or(true, |active| active == entry.active))
+            .collect())
+    }
+
+    field app_logs(&executor, uuid: String, stream: Option<String>, lines: Option<i32>)
+        -> FieldResult<Vec<String>>
+    {
+        check_uuid(&uuid)?;
+        let output = vec!["first".to_owned(), "second".to_owned(), "third".to_owned()];
+        let lines = (lines.unwrap_or(50).max(0) as usize).min(output.len());
+        Ok(output[output.len() - lines..].to_vec())
+    }
+});
+
+struct MutationRoot;
+
+graphql_object!(MutationRoot: Context as "Mutation" |&self| {
+    // The pid of the new process counts the arguments the app was started with
+    field start_app(&executor, uuid: String, run_level: String, args: Option<Vec<String>>)
+        -> FieldResult<i32>
+    {
+        check_uuid(&uuid)?;
+        match run_level.as_ref() {
+            "OnCommand" => Ok(100 + args.map_or(0, |args| args.len() as i32)),
+            _ => Err(FieldError::new(
+                format!("Unexpected run level: {}", run_level),
+                Value::null(),
+            )),
+        }
+    }
+
+    field stop_app(&executor, uuid: String, signal: Option<String>, grace_period: Option<i32>)
+        -> FieldResult<bool>
+    {
+        check_uuid(&uuid)?;
+        Ok(true)
+    }
+
+    field send_message(&executor, uuid: String, message: String) -> FieldResult<bool> {
+        check_uuid(&uuid)?;
+        Ok(true)
+    }
+});
+
+#[test]
+fn app_service_queries() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8741);
+
+    let apps = AppServiceClient::new(mock_client(&config_file));
+
+    assert_eq!(apps.apps().unwrap().len(), 2);
+
+    let active = apps.active_app(UUID).unwrap().unwrap();
+    assert_eq!(active.app.version, "2.0");
+    assert_eq!(active.app.restart_policy, "Never");
+    assert_eq!(active.previous_version, Some("1.0".to_owned()));
+    assert_eq!(apps.active_app("missing"), Ok(None));
+
+    assert_eq!(
+        apps.app_logs(UUID, 2),
+        Ok(vec!["second".to_owned(), "third".to_owned()])
+    );
+}
+
+#[test]
+fn app_service_mutations() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8740);
+
+    let apps = AppServiceClient::new(mock_client(&config_file));
+
+    assert_eq!(
+        apps.start_app(UUID, &RunLevel::OnCommand, &["-v", "--count", "3"]),
+        Ok(103)
+    );
+    assert_eq!(apps.start_app(UUID, &RunLevel::OnCommand, &[]), Ok(100));
+    assert_eq!(apps.stop_app(UUID), Ok(()));
+    assert_eq!(apps.send_message(UUID, "status"), Ok(()));
+
+    match apps.stop_app("missing") {
+        Err(ClientError::Service(_)) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
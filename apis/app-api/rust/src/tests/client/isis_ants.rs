@@ -0,0 +1,135 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::mock_client;
+use client::*;
+use juniper::FieldResult;
+use kubos_service::{self, Service};
+use kubos_system::Config as ServiceConfig;
+use tempfile::TempDir;
+
+// A mock ISIS antenna system service, with the same argument types as the real one
+struct Subsystem;
+type Context = kubos_service::Context<Subsystem>;
+
+#[derive(GraphQLEnum)]
+enum ArmState {
+    Arm,
+    Disarm,
+}
+
+#[derive(GraphQLEnum, Debug)]
+enum DeployType {
+    All,
+    Antenna1,
+    Antenna2,
+    Antenna3,
+    Antenna4,
+}
+
+#[derive(GraphQLEnum)]
+enum DeploymentStatus {
+    Deployed,
+    InProgress,
+    Partial,
+    Stowed,
+    Error,
+}
+
+#[derive(GraphQLObject)]
+struct GetDeployResponse {
+    status: DeploymentStatus,
+}
+
+#[derive(GraphQLObject)]
+struct GenericResponse {
+    errors: String,
+    success: bool,
+}
+
+struct QueryRoot;
+
+graphql_object!(QueryRoot: Context as "Query" |&self| {
+    field ping() -> FieldResult<String> {
+        Ok(String::from("pong"))
+    }
+
+    field deployment_status(&executor) -> FieldResult<GetDeployResponse> {
+        Ok(GetDeployResponse { status: DeploymentStatus::Partial })
+    }
+});
+
+struct MutationRoot;
+
+graphql_object!(MutationRoot: Context as "Mutation" |&self| {
+    // Disarming is rejected, so that the tests can tell which state was received
+    field arm(&executor, state: ArmState) -> FieldResult<GenericResponse> {
+        Ok(match state {
+            ArmState::Arm => GenericResponse { errors: String::new(), success: true },
+            ArmState::Disarm => GenericResponse {
+                errors: "Disarm rejected".to_owned(),
+                success: false,
+            },
+        })
+    }
+
+    // Reports the arguments which were received, unless every antenna is deployed normally
+    field deploy(&executor, ant = (DeployType::All): DeployType, force = false: bool, time: i32)
+        -> FieldResult<GenericResponse>
+    {
+        Ok(match (&ant, force) {
+            (DeployType::All, false) => GenericResponse { errors: String::new(), success: true },
+            _ => GenericResponse {
+                errors: format!("{:?} {} {}", ant, force, time),
+                success: false,
+            },
+        })
+    }
+});
+
+#[test]
+fn isis_ants_status() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8743);
+
+    let ants = IsisAntsClient::new(mock_client(&config_file));
+
+    assert_eq!(
+        ants.deployment_status(),
+        Ok(::client::DeploymentStatus::Partial)
+    );
+}
+
+#[test]
+fn isis_ants_deploy() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8742);
+
+    let ants = IsisAntsClient::new(mock_client(&config_file));
+
+    assert_eq!(ants.arm(true), Ok(()));
+    assert_eq!(
+        ants.arm(false),
+        Err(ClientError::Failed("Disarm rejected".to_owned()))
+    );
+    assert_eq!(ants.deploy(DeployTarget::All, false, 5), Ok(()));
+    assert_eq!(
+        ants.deploy(DeployTarget::Antenna3, true, 10),
+        Err(ClientError::Failed("Antenna3 true 10".to_owned()))
+    );
+}
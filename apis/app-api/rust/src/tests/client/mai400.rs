@@ -0,0 +1,181 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::mock_client;
+use client::*;
+use juniper::FieldResult;
+use kubos_service::{self, Service};
+use kubos_system::Config as ServiceConfig;
+use tempfile::TempDir;
+
+// A mock MAI-400 service, with the same argument types as the real one
+struct Subsystem;
+type Context = kubos_service::Context<Subsystem>;
+
+#[derive(GraphQLEnum, Clone, Copy, Debug)]
+enum Mode {
+    TestMode = 0,
+    RateNulling = 1,
+    Reserved1 = 2,
+    NadirPointing = 3,
+    LatLongPointing = 4,
+    QbxMode = 5,
+    Reserved2 = 6,
+    NormalSun = 7,
+    LatLongSun = 8,
+    Qintertial = 9,
+    Reserved3 = 10,
+    Qtable = 11,
+    SunRam = 12,
+    Unknown = 0xFF,
+}
+
+#[derive(GraphQLEnum)]
+enum PowerState {
+    On,
+    Off,
+    Reset,
+}
+
+#[derive(GraphQLObject)]
+struct GetPowerResponse {
+    state: PowerState,
+    uptime: i32,
+}
+
+#[derive(GraphQLObject)]
+struct Spin {
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+#[derive(GraphQLObject)]
+struct GenericResponse {
+    errors: String,
+    success: bool,
+}
+
+#[derive(GraphQLInputObject)]
+struct RVInput {
+    eci_pos: Vec<f64>,
+    eci_vel: Vec<f64>,
+    time_epoch: i32,
+}
+
+struct QueryRoot;
+
+graphql_object!(QueryRoot: Context as "Query" |&self| {
+    field ping() -> FieldResult<String> {
+        Ok(String::from("pong"))
+    }
+
+    field power(&executor) -> FieldResult<GetPowerResponse> {
+        Ok(GetPowerResponse { state: PowerState::On, uptime: 30 })
+    }
+
+    field mode(&executor) -> FieldResult<Mode> {
+        Ok(Mode::RateNulling)
+    }
+
+    field spin(&executor) -> FieldResult<Spin> {
+        Ok(Spin { x: 1.5, y: -2.0, z: 0.0 })
+    }
+});
+
+struct MutationRoot;
+
+graphql_object!(MutationRoot: Context as "Mutation" |&self| {
+    field noop(&executor) -> FieldResult<GenericResponse> {
+        Ok(GenericResponse { errors: String::new(), success: true })
+    }
+
+    // Only the test mode is rejected, reporting the arguments which were received
+    field set_mode(
+        &executor,
+        mode: Mode,
+        qbi_cmd = {vec![0,0,0,0]}: Vec<i32>,
+        sun_angle_enable = false: bool,
+        sun_rot_angle = 0.0: f64)
+    -> FieldResult<GenericResponse> {
+        Ok(match mode {
+            Mode::TestMode => GenericResponse {
+                errors: format!("{:?} {:?}", mode, qbi_cmd),
+                success: false,
+            },
+            _ => GenericResponse { errors: String::new(), success: true },
+        })
+    }
+
+    field update(&executor, gps_time: Option<i32>, rv: Option<RVInput>)
+    -> FieldResult<GenericResponse> {
+        Ok(match gps_time {
+            Some(time) if time < 0 => GenericResponse {
+                errors: format!("Invalid GPS time: {}", time),
+                success: false,
+            },
+            _ => GenericResponse { errors: String::new(), success: true },
+        })
+    }
+});
+
+#[test]
+fn mai400_queries() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8746);
+
+    let mai = Mai400Client::new(mock_client(&config_file));
+
+    assert_eq!(mai.ping(), Ok(()));
+    assert_eq!(
+        mai.power(),
+        Ok(PowerStatus {
+            state: ::client::PowerState::On,
+            uptime: 30,
+        })
+    );
+    assert_eq!(mai.mode(), Ok("RATE_NULLING".to_owned()));
+    assert_eq!(
+        mai.spin(),
+        Ok(::client::Spin {
+            x: 1.5,
+            y: -2.0,
+            z: 0.0,
+        })
+    );
+}
+
+#[test]
+fn mai400_mutations() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8745);
+
+    let mai = Mai400Client::new(mock_client(&config_file));
+
+    assert_eq!(mai.noop(), Ok(()));
+    assert_eq!(mai.set_mode("NADIR_POINTING", [0, 0, 0, 0]), Ok(()));
+    assert_eq!(
+        mai.set_mode("TEST_MODE", [1, 2, 3, 4]),
+        Err(ClientError::Failed("TestMode [1, 2, 3, 4]".to_owned()))
+    );
+    assert_eq!(mai.update_time(1_198_800_018), Ok(()));
+    assert_eq!(
+        mai.update_time(-1),
+        Err(ClientError::Failed("Invalid GPS time: -1".to_owned()))
+    );
+}
@@ -0,0 +1,200 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use client::*;
+use juniper::FieldResult;
+use kubos_service::{self, Service};
+use kubos_system::Config as ServiceConfig;
+use std::path::Path;
+use tempfile::TempDir;
+
+mod app_service;
+mod isis_ants;
+mod mai400;
+mod monitor;
+mod novatel;
+
+// A mock telemetry database service, which knows of two entries
+struct Subsystem;
+type Context = kubos_service::Context<Subsystem>;
+
+struct Entry(i32, &'static str, &'static str);
+
+graphql_object!(Entry: () |&self| {
+    field timestamp() -> i32 { self.0 }
+    field subsystem() -> String { "eps".to_owned() }
+    field parameter() -> String { self.1.to_owned() }
+    field value() -> String { self.2.to_owned() }
+});
+
+struct InsertResponse(Result<(), String>);
+
+graphql_object!(InsertResponse: () |&self| {
+    field success() -> bool { self.0.is_ok() }
+    field errors() -> String { self.0.clone().err().unwrap_or_default() }
+});
+
+struct QueryRoot;
+
+graphql_object!(QueryRoot: Context as "Query" |&self| {
+    field ping() -> FieldResult<String> {
+        Ok(String::from("pong"))
+    }
+
+    field telemetry(
+        timestamp_ge: Option<i32>,
+        timestamp_le: Option<i32>,
+        subsystem: Option<String>,
+        parameter: Option<String>,
+        limit: Option<i32>,
+    ) -> FieldResult<Vec<Entry>> {
+        let entries = vec![Entry(1000, "voltage", "3.3"), Entry(900, "current", "0.5")];
+        Ok(entries
+            .into_iter()
+            .filter(|entry| parameter.as_ref().map_or(true, |param| param == entry.1))
+            .filter(|_| subsystem.as_ref().map_or(true, |name| name == "eps"))
+            .filter(|entry| timestamp_ge.map_or(true, |time| entry.0 >= time))
+            .filter(|entry| timestamp_le.map_or(true, |time| entry.0 <= time))
+            .take(limit.unwrap_or(10) as usize)
+            .collect())
+    }
+});
+
+struct MutationRoot;
+
+graphql_object!(MutationRoot: Context as "Mutation" |&self| {
+    field insert(timestamp: Option<i32>, subsystem: String, parameter: String, value: String)
+        -> FieldResult<InsertResponse>
+    {
+        if timestamp.map_or(false, |time| time < 0) || subsystem.is_empty() || parameter.is_empty() {
+            return Ok(InsertResponse(Err("Invalid entry".to_owned())));
+        }
+
+        Ok(InsertResponse(match value.parse::<f64>() {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!("Invalid value: {}", value)),
+        }))
+    }
+});
+
+pub fn mock_client(config_file: &Path) -> ServiceClient {
+    ServiceClient::from_config(ServiceConfig::new_from_path(
+        "mock-service",
+        config_file.to_string_lossy().to_string(),
+    ))
+}
+
+#[test]
+fn client_insert() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8758);
+
+    let telemetry = TelemetryClient::new(mock_client(&config_file));
+
+    assert_eq!(telemetry.insert("eps", "voltage", "3.3"), Ok(()));
+    assert_eq!(
+        telemetry.insert_at(1000, "eps", "voltage", "high"),
+        Err(ClientError::Failed("Invalid value: high".to_owned()))
+    );
+}
+
+#[test]
+fn client_query() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8757);
+
+    let telemetry = TelemetryClient::new(mock_client(&config_file));
+
+    let filter = TelemetryFilter {
+        parameter: Some("current".to_owned()),
+        ..Default::default()
+    };
+    assert_eq!(
+        telemetry.query(&filter),
+        Ok(vec![TelemetryEntry {
+            timestamp: 900,
+            subsystem: "eps".to_owned(),
+            parameter: "current".to_owned(),
+            value: "0.5".to_owned(),
+        }])
+    );
+
+    let entries = telemetry.query(&TelemetryFilter::default()).unwrap();
+    assert_eq!(entries.len(), 2);
+}
+
+#[test]
+fn client_hardware_ping() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8756);
+
+    let ants = IsisAntsClient::new(mock_client(&config_file));
+
+    assert_eq!(ants.ping(), Ok(()));
+}
+
+#[test]
+fn client_service_error() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8755);
+
+    let client = mock_client(&config_file);
+
+    match client.query::<String>("{ power { state } }", json!({}), "power") {
+        Err(ClientError::Service(_)) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn client_bad_response() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8754);
+
+    let client = mock_client(&config_file);
+
+    match client.query::<i32>("{ ping }", json!({}), "ping") {
+        Err(ClientError::Response(_)) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+    assert_eq!(
+        client.query::<String>("{ ping }", json!({}), "pong"),
+        Err(ClientError::Response(
+            "No 'pong' field in {\"ping\":\"pong\"}".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn client_no_service() {
+    let client = ServiceClient::from_config(ServiceConfig::new_from_path(
+        "monitor-service",
+        "/fake/path".to_owned(),
+    ))
+    .retries(1);
+
+    assert_eq!(
+        MonitorClient::new(client).mem_info(),
+        Err(ClientError::Communication(
+            "Connection refused (os error 111)".to_owned()
+        ))
+    );
+}
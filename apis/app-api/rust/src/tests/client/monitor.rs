@@ -0,0 +1,137 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::mock_client;
+use client::*;
+use juniper::FieldResult;
+use kubos_service::{self, Service};
+use kubos_system::Config as ServiceConfig;
+use tempfile::TempDir;
+
+// A mock monitor service, with the same argument types as the real one.
+// It reports on two running processes.
+struct Subsystem;
+type Context = kubos_service::Context<Subsystem>;
+
+#[derive(GraphQLObject)]
+struct MemInfoResponse {
+    total: Option<i32>,
+    free: Option<i32>,
+    available: Option<i32>,
+    low_free: Option<i32>,
+}
+
+#[derive(GraphQLObject)]
+struct PSResponse {
+    pid: i32,
+    uid: Option<i32>,
+    gid: Option<i32>,
+    usr: Option<String>,
+    grp: Option<String>,
+    state: Option<String>,
+    ppid: Option<i32>,
+    mem: Option<i32>,
+    rss: Option<i32>,
+    threads: Option<i32>,
+    cmd: Option<String>,
+}
+
+fn process(pid: i32) -> PSResponse {
+    PSResponse {
+        pid,
+        uid: Some(0),
+        gid: Some(0),
+        usr: Some("root".to_owned()),
+        grp: Some("root".to_owned()),
+        state: Some("S".to_owned()),
+        ppid: Some(1),
+        mem: Some(4096),
+        rss: Some(10),
+        threads: Some(1),
+        cmd: Some(format!("process-{}", pid)),
+    }
+}
+
+struct QueryRoot;
+
+graphql_object!(QueryRoot: Context as "Query" |&self| {
+    field ping() -> FieldResult<String> {
+        Ok(String::from("pong"))
+    }
+
+    field mem_info(&executor) -> FieldResult<MemInfoResponse> {
+        Ok(MemInfoResponse {
+            total: Some(4096),
+            free: Some(1024),
+            available: Some(2048),
+            low_free: None,
+        })
+    }
+
+    field ps(&executor, pids: Option<Vec<i32>>, sample_ms = 0: i32) -> FieldResult<Vec<PSResponse>>
+    {
+        Ok(pids
+            .unwrap_or_else(|| vec![10, 20])
+            .into_iter()
+            .filter(|pid| *pid == 10 || *pid == 20)
+            .map(process)
+            .collect())
+    }
+});
+
+struct MutationRoot;
+
+graphql_object!(MutationRoot: Context as "Mutation" |&self| {
+    field noop(&executor) -> FieldResult<bool> {
+        Ok(true)
+    }
+});
+
+#[test]
+fn monitor_mem_info() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8739);
+
+    let monitor = MonitorClient::new(mock_client(&config_file));
+
+    assert_eq!(
+        monitor.mem_info(),
+        Ok(MemInfo {
+            total: Some(4096),
+            free: Some(1024),
+            available: Some(2048),
+            low_free: None,
+        })
+    );
+}
+
+#[test]
+fn monitor_ps() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8738);
+
+    let monitor = MonitorClient::new(mock_client(&config_file));
+
+    let all: Vec<i32> = monitor.ps(None).unwrap().iter().map(|p| p.pid).collect();
+    assert_eq!(all, vec![10, 20]);
+
+    let selected = monitor.ps(Some(&[20, 30])).unwrap();
+    assert_eq!(selected.len(), 1);
+    assert_eq!(selected[0].pid, 20);
+    assert_eq!(selected[0].cmd, Some("process-20".to_owned()));
+}
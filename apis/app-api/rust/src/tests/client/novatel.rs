@@ -0,0 +1,132 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::mock_client;
+use client::*;
+use juniper::FieldResult;
+use kubos_service::{self, Service};
+use kubos_system::Config as ServiceConfig;
+use tempfile::TempDir;
+
+// A mock NovAtel OEM6 service, with the same response types as the real one
+struct Subsystem;
+type Context = kubos_service::Context<Subsystem>;
+
+#[derive(GraphQLObject)]
+struct OEMTime {
+    week: i32,
+    ms: i32,
+}
+
+#[derive(GraphQLEnum)]
+enum SolutionStatus {
+    SolComputed,
+    InsufficientObservations,
+}
+
+#[derive(GraphQLEnum)]
+enum PosVelType {
+    None,
+    Single,
+}
+
+#[derive(GraphQLEnum)]
+enum RefTimeStatus {
+    Unknown,
+    FineSteering,
+}
+
+#[derive(GraphQLObject)]
+struct LockStatus {
+    time_status: RefTimeStatus,
+    time: OEMTime,
+    position_status: SolutionStatus,
+    position_type: PosVelType,
+    velocity_status: SolutionStatus,
+    velocity_type: PosVelType,
+}
+
+#[derive(GraphQLObject)]
+struct LockInfo {
+    time: OEMTime,
+    position: Vec<f64>,
+    velocity: Vec<f64>,
+}
+
+struct QueryRoot;
+
+graphql_object!(QueryRoot: Context as "Query" |&self| {
+    field ping() -> FieldResult<String> {
+        Ok(String::from("pong"))
+    }
+
+    field lock_status(&executor) -> FieldResult<LockStatus> {
+        Ok(LockStatus {
+            time_status: RefTimeStatus::FineSteering,
+            time: OEMTime { week: 1980, ms: 3000 },
+            position_status: SolutionStatus::SolComputed,
+            position_type: PosVelType::Single,
+            velocity_status: SolutionStatus::InsufficientObservations,
+            velocity_type: PosVelType::None,
+        })
+    }
+
+    field lock_info(&executor) -> FieldResult<LockInfo> {
+        Ok(LockInfo {
+            time: OEMTime { week: 1980, ms: 2000 },
+            position: vec![1.0, 2.0, 3.0],
+            velocity: vec![4.0, 5.0, 6.0],
+        })
+    }
+});
+
+struct MutationRoot;
+
+graphql_object!(MutationRoot: Context as "Mutation" |&self| {
+    field noop(&executor) -> FieldResult<String> {
+        Ok(String::from("Not Implemented"))
+    }
+});
+
+#[test]
+fn novatel_lock() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8744);
+
+    let gps = NovatelClient::new(mock_client(&config_file));
+
+    assert_eq!(gps.ping(), Ok(()));
+    assert_eq!(
+        gps.lock_status(),
+        Ok(::client::LockStatus {
+            time_status: "FINE_STEERING".to_owned(),
+            time: OemTime { week: 1980, ms: 3000 },
+            position_status: "SOL_COMPUTED".to_owned(),
+            position_type: "SINGLE".to_owned(),
+            velocity_status: "INSUFFICIENT_OBSERVATIONS".to_owned(),
+            velocity_type: "NONE".to_owned(),
+        })
+    );
+    assert_eq!(
+        gps.lock_info(),
+        Ok(::client::LockInfo {
+            time: OemTime { week: 1980, ms: 2000 },
+            position: vec![1.0, 2.0, 3.0],
+            velocity: vec![4.0, 5.0, 6.0],
+        })
+    );
+}
@@ -0,0 +1,106 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use framework::*;
+use libc;
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Default)]
+struct EventApp {
+    ticks: Cell<u32>,
+    messages: RefCell<Vec<String>>,
+    shutdown: Cell<bool>,
+}
+
+impl AppHandler for EventApp {
+    fn on_boot(&self, _args: Vec<String>) {}
+
+    fn on_command(&self, _args: Vec<String>) {}
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(10))
+    }
+
+    fn on_tick(&self) {
+        self.ticks.set(self.ticks.get() + 1);
+    }
+
+    fn on_message(&self, message: String) {
+        if message == "stop" {
+            request_shutdown();
+        }
+        self.messages.borrow_mut().push(message);
+    }
+
+    fn on_shutdown(&self) {
+        self.shutdown.set(true);
+    }
+}
+
+struct SimpleApp;
+
+impl AppHandler for SimpleApp {
+    fn on_boot(&self, _args: Vec<String>) {}
+
+    fn on_command(&self, _args: Vec<String>) {}
+}
+
+#[test]
+fn default_hooks() {
+    assert!(!SimpleApp.keep_alive());
+    assert_eq!(SimpleApp.tick_interval(), None);
+    assert!(EventApp::default().keep_alive());
+}
+
+// The shutdown flag is shared by the whole process, so everything which
+// touches it is tested in order here
+#[test]
+fn app_events() {
+    SHUTDOWN.store(false, Ordering::SeqCst);
+
+    let app = EventApp::default();
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        sender.send("hello".to_owned()).unwrap();
+        thread::sleep(Duration::from_millis(200));
+        sender.send("stop".to_owned()).unwrap();
+    });
+
+    run_events(&app, &receiver);
+
+    assert_eq!(*app.messages.borrow(), vec!["hello", "stop"]);
+    assert!(app.ticks.get() >= 5);
+    assert!(app.shutdown.get());
+
+    // SIGTERM should request a shutdown rather than killing the app
+    SHUTDOWN.store(false, Ordering::SeqCst);
+    install_signal_handlers();
+    unsafe {
+        libc::raise(libc::SIGTERM);
+    }
+    assert!(shutdown_requested());
+
+    let app = EventApp::default();
+    let (_sender, receiver) = mpsc::channel();
+    run_events(&app, &receiver);
+    assert!(app.shutdown.get());
+    assert_eq!(app.ticks.get(), 0);
+}
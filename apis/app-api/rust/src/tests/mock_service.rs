@@ -31,6 +31,11 @@ graphql_object!(QueryRoot: Context as "Query" |&self| {
             false => Ok(String::from("query"))
         }
     }
+
+    field echo(value: String) -> FieldResult<String>
+    {
+        Ok(value)
+    }
 });
 
 pub struct MutationRoot;
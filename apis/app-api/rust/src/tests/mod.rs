@@ -18,13 +18,18 @@ mod mock_service;
 
 macro_rules! mock_service {
     ($config:ident, $addr:expr, $port:expr) => {{
+        mock_service!($config, $addr, $port, "")
+    }};
+    ($config:ident, $addr:expr, $port:expr, $settings:expr) => {{
         let config = format!(
             r#"
+            [mock-service]
+            {}
             [mock-service.addr]
             ip = "{}"
             port = {}
             "#,
-            $addr, $port
+            $settings, $addr, $port
         );
 
         ::std::fs::write($config.clone(), config).unwrap();
@@ -44,4 +49,6 @@ macro_rules! mock_service {
     }};
 }
 
+mod client;
+mod framework;
 mod query;
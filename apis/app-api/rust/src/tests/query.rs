@@ -16,9 +16,12 @@
 
 use super::mock_service::*;
 use kubos_service::Service;
-use kubos_system::Config as ServiceConfig;
-use query::query;
+use kubos_system::{fragment_response, Config as ServiceConfig};
+use query::*;
+use serde_json;
 
+use std::net::UdpSocket;
+use std::thread;
 use std::time::Duration;
 use tempfile::TempDir;
 
@@ -63,7 +66,20 @@ fn query_error() {
 
     let result_str = format!("{}", result);
 
-    assert_eq!(result_str, "{\"message\":\"Query failed\",\"locations\":[{\"line\":2,\"column\":13}],\"path\":[\"ping\"]}");
+    assert_eq!(result_str, "Query failed");
+    assert_eq!(
+        result.downcast::<QueryError>().unwrap(),
+        QueryError {
+            errors: vec![ServiceError {
+                message: "Query failed".to_owned(),
+                locations: vec![ErrorLocation {
+                    line: 2,
+                    column: 13
+                }],
+                path: vec!["ping".to_owned()],
+            }],
+        }
+    );
 }
 
 #[test]
@@ -126,3 +142,215 @@ fn query_mutation() {
 
     assert_eq!(result, expected);
 }
+
+#[test]
+fn query_large_request() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8761);
+
+    let value = "a".repeat(10000);
+    let request = format!(r#"{{ echo(value: "{}") }}"#, value);
+
+    let expected = json!({ "echo": value });
+
+    let result = query(
+        ServiceConfig::new_from_path("mock-service", config_file.to_string_lossy().to_string()),
+        &request,
+        Some(Duration::from_secs(1)),
+    ).unwrap();
+
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn query_fragmented_response() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8760, "chunk_size = 512");
+
+    let value = "b".repeat(10000);
+    let request = format!(r#"{{ echo(value: "{}") }}"#, value);
+
+    let expected = json!({ "echo": value });
+
+    let result = query(
+        ServiceConfig::new_from_path("mock-service", config_file.to_string_lossy().to_string()),
+        &request,
+        Some(Duration::from_secs(1)),
+    ).unwrap();
+
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn query_variables() {
+    let config_dir = TempDir::new().unwrap();
+    let config_file = config_dir.path().join("config.toml");
+    mock_service!(config_file, "0.0.0.0", 8759);
+
+    let request = r#"query Echo($value: String!) {
+            echo(value: $value)
+        }"#;
+
+    let expected = json!({
+            "echo": "quoted \"value\""
+        });
+
+    let result = query_with_variables(
+        ServiceConfig::new_from_path("mock-service", config_file.to_string_lossy().to_string()),
+        request,
+        json!({ "value": "quoted \"value\"" }),
+        Some(Duration::from_secs(1)),
+    ).unwrap();
+
+    assert_eq!(result, expected);
+}
+
+// Start a fake service which answers each request with the datagrams built by `reply`.
+// Requests which aren't JSON envelopes are passed to `reply` as a JSON string
+fn fake_service_raw<F>(port: u16, reply: F) -> ServiceConfig
+where
+    F: Fn(usize, &serde_json::Value) -> Vec<Vec<u8>> + Send + 'static,
+{
+    let socket = UdpSocket::bind(("127.0.0.1", port)).unwrap();
+
+    thread::spawn(move || {
+        let mut buf = vec![0; 4096];
+        let mut count = 0;
+        loop {
+            let (size, peer) = socket.recv_from(&mut buf).unwrap();
+            let request: serde_json::Value = serde_json::from_slice(&buf[0..size])
+                .unwrap_or_else(|_| {
+                    serde_json::Value::String(String::from_utf8_lossy(&buf[0..size]).into_owned())
+                });
+            for response in reply(count, &request) {
+                socket.send_to(&response, peer).unwrap();
+            }
+            count += 1;
+        }
+    });
+
+    ServiceConfig::new_from_str(
+        "fake-service",
+        &format!("[fake-service.addr]\nip = \"127.0.0.1\"\nport = {}\n", port),
+    )
+}
+
+// Start a fake service which answers each request with the responses built by `reply`
+fn fake_service<F>(port: u16, reply: F) -> ServiceConfig
+where
+    F: Fn(usize, &serde_json::Value) -> Vec<serde_json::Value> + Send + 'static,
+{
+    fake_service_raw(port, move |count, request| {
+        reply(count, request)
+            .into_iter()
+            .map(|response| response.to_string().into_bytes())
+            .collect()
+    })
+}
+
+#[test]
+fn query_plain_string() {
+    // Services which only understand plain query strings echo back what they received
+    let config = fake_service(8748, |_, request| {
+        vec![json!({"errs": "", "msg": {"request": request}})]
+    });
+
+    let result = query(config, "{ ping }", Some(Duration::from_secs(1))).unwrap();
+
+    assert_eq!(result, json!({"request": "{ ping }"}));
+}
+
+#[test]
+fn query_retry_new_socket() {
+    // The first response is slow, and its second fragment arrives while the retry is
+    // being reassembled
+    let config = fake_service_raw(8747, |count, request| match count {
+        0 => {
+            thread::sleep(Duration::from_millis(150));
+            vec![b"\xFF\x00\x01\x00\x02junk".to_vec()]
+        }
+        _ => {
+            let response = json!({"errs": "", "id": request["id"], "msg": {"ping": "pong"}});
+            fragment_response(response.to_string().as_bytes(), Some(20)).unwrap()
+        }
+    });
+
+    let options = QueryOptions {
+        timeout: Some(Duration::from_millis(100)),
+        retries: 1,
+        backoff: Duration::from_millis(10),
+    };
+
+    let result = query_with_options(config, "{ ping }", None, &options).unwrap();
+
+    assert_eq!(result, json!({"ping": "pong"}));
+}
+
+#[test]
+fn query_retry() {
+    // The first request is lost
+    let config = fake_service(8752, |count, request| match count {
+        0 => vec![],
+        _ => vec![json!({"errs": "", "id": request["id"], "msg": {"ping": "pong"}})],
+    });
+
+    let options = QueryOptions {
+        timeout: Some(Duration::from_millis(100)),
+        retries: 1,
+        backoff: Duration::from_millis(10),
+    };
+
+    let result = query_with_options(config, "{ ping }", None, &options).unwrap();
+
+    assert_eq!(result, json!({"ping": "pong"}));
+}
+
+#[test]
+fn query_retries_exhausted() {
+    let config = fake_service(8751, |_, _| vec![]);
+
+    let options = QueryOptions {
+        timeout: Some(Duration::from_millis(50)),
+        retries: 2,
+        backoff: Duration::from_millis(10),
+    };
+
+    assert!(query_with_options(config, "{ ping }", None, &options).is_err());
+}
+
+#[test]
+fn query_skips_stale_response() {
+    // A late response to an earlier request arrives before the real one
+    let config = fake_service(8750, |_, request| {
+        vec![
+            json!({"errs": "", "id": "stale", "msg": {"ping": "old"}}),
+            json!({"errs": "", "id": request["id"], "msg": {"ping": "new"}}),
+        ]
+    });
+
+    let result = query_with_variables(config, "{ ping }", json!({}), Some(Duration::from_secs(1)))
+        .unwrap();
+
+    assert_eq!(result, json!({"ping": "new"}));
+}
+
+#[test]
+fn query_multiple_errors() {
+    let config = fake_service(8749, |_, request| {
+        vec![json!({
+            "errs": "{\"message\":\"First\",\"path\":[\"a\"]}{\"message\":\"Second\",\"path\":[\"b\", 0]}",
+            "id": request["id"],
+            "msg": null
+        })]
+    });
+
+    let err = query(config, "{ a, b }", Some(Duration::from_secs(1)))
+        .unwrap_err()
+        .downcast::<QueryError>()
+        .unwrap();
+
+    assert_eq!(err.to_string(), "First; Second");
+    assert_eq!(err.errors[1].path, vec!["b", "0"]);
+}
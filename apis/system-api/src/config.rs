@@ -14,10 +14,13 @@
 // limitations under the License.
 //
 use getopts::Options;
+use serde::de::DeserializeOwned;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
+use std::net::IpAddr;
 use toml;
 use toml::Value;
 
@@ -28,7 +31,7 @@ pub static DEFAULT_IP: &str = "127.0.0.1";
 /// The default port for service bindings
 pub const DEFAULT_PORT: u16 = 8080;
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 /// A simple address consisting of an IP address and port number
 pub struct Address {
     ip: Option<String>,
@@ -59,6 +62,33 @@ impl Address {
     }
 }
 
+/// Errors which can be encountered while loading or deserializing configuration data
+#[derive(Clone, Debug, Fail, PartialEq)]
+pub enum ConfigError {
+    /// The config file could not be read
+    #[fail(display = "Failed to read config file {}: {}", path, description)]
+    Read {
+        /// Path of the config file
+        path: String,
+        /// Description of the underlying I/O error
+        description: String,
+    },
+    /// The config data is not valid TOML
+    #[fail(display = "Failed to parse config: {}", _0)]
+    Parse(String),
+    /// The config data has no section for the requested category
+    #[fail(display = "No [{}] section found in config", _0)]
+    MissingSection(String),
+    /// A value in the config data is missing, unexpected or has the wrong type
+    #[fail(display = "Invalid value in [{}]: {}", section, description)]
+    Invalid {
+        /// The section containing the bad value
+        section: String,
+        /// Description of the problem
+        description: String,
+    },
+}
+
 /// KubOS config used by either Apps or Services. KubOS config files use the TOML format, and can
 /// may contain multiple named Categories. Typically each category corresponds to an App or Service
 /// name. This allows one config file to store configuration for multiple Apps / Services at a
@@ -76,8 +106,16 @@ impl Address {
 ///
 /// When `addr`, `addr.ip`, or `addr.port` are not provided in the config file, the default IP
 /// `"127.0.0.1"` and default port `8080` are used instead.
-#[derive(Debug)]
+///
+/// The `new*` constructors silently fall back to this default configuration if the
+/// config file can't be loaded. Services which should refuse to start with a bad config file
+/// should use the `try_new*` constructors instead, which report the problem as an error.
+///
+/// Service-specific settings can be deserialized into a typed struct with `Config::deserialize`,
+/// and the effective configuration (including any default values) can be printed with `Display`.
+#[derive(Clone, Debug)]
 pub struct Config {
+    name: String,
     addr: Address,
     raw: Value,
 }
@@ -85,12 +123,43 @@ pub struct Config {
 impl Default for Config {
     fn default() -> Self {
         Config {
+            name: String::new(),
             addr: Address::default(),
             raw: Value::String("".to_string()),
         }
     }
 }
 
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut section = match self.raw {
+            Value::Table(ref table) => table.clone(),
+            _ => toml::value::Table::new(),
+        };
+
+        let mut addr = toml::value::Table::new();
+        addr.insert("ip".to_owned(), Value::String(self.addr.ip().to_owned()));
+        addr.insert(
+            "port".to_owned(),
+            Value::Integer(i64::from(self.addr.port())),
+        );
+        section.insert("addr".to_owned(), Value::Table(addr));
+
+        let value = if self.name.is_empty() {
+            Value::Table(section)
+        } else {
+            let mut root = toml::value::Table::new();
+            root.insert(self.name.clone(), Value::Table(section));
+            Value::Table(root)
+        };
+
+        match toml::to_string(&value) {
+            Ok(text) => write!(f, "{}", text),
+            Err(_) => Err(fmt::Error),
+        }
+    }
+}
+
 impl Config {
     /// Creates and parses configuration data from the system configuration
     /// file or the path passed as the '-c' or '--config' option to this
@@ -108,7 +177,10 @@ impl Config {
     /// `name` - Category name used as a key in the config file
     /// `path` - Path to configuration file
     pub fn new_from_path(name: &str, path: String) -> Self {
-        parse_config_file(name, path).unwrap_or(Config::default())
+        parse_config_file(name, path).unwrap_or_else(|_| Config {
+            name: name.to_owned(),
+            ..Config::default()
+        })
     }
 
     /// Creates and parses configuration data from the passed in configuration
@@ -117,7 +189,69 @@ impl Config {
     /// `name` - Category name used as a key in the config
     /// `config` - Config data as a string
     pub fn new_from_str(name: &str, config: &str) -> Self {
-        parse_config_str(name, config).unwrap_or(Config::default())
+        parse_config_str(name, config).unwrap_or_else(|_| Config {
+            name: name.to_owned(),
+            ..Config::default()
+        })
+    }
+
+    /// Loads and validates configuration data from the system configuration
+    /// file or the path passed as the '-c' or '--config' option to this
+    /// executable.
+    ///
+    /// Unlike `Config::new`, this fails if the file can't be read or parsed, if it has no
+    /// section for `name`, or if the `[name.addr]` section is invalid.
+    ///
+    /// # Arguments
+    /// `name` - Category name used as a key in the config file
+    ///
+    /// ### Examples
+    ///
+    /// ```rust,no_run
+    /// use kubos_system::Config;
+    /// use std::process;
+    ///
+    /// let config = Config::try_new("example-service").unwrap_or_else(|err| {
+    ///     eprintln!("{}", err);
+    ///     process::exit(1);
+    /// });
+    /// println!("Effective config:\n{}", config);
+    /// ```
+    pub fn try_new(name: &str) -> Result<Self, ConfigError> {
+        Self::try_new_from_path(name, get_config_path())
+    }
+
+    /// Loads and validates configuration data from the passed in configuration path.
+    ///
+    /// # Arguments
+    /// `name` - Category name used as a key in the config file
+    /// `path` - Path to configuration file
+    pub fn try_new_from_path(name: &str, path: String) -> Result<Self, ConfigError> {
+        let contents = get_file_data(path.clone()).map_err(|err| ConfigError::Read {
+            path,
+            description: err.to_string(),
+        })?;
+        Self::try_new_from_str(name, &contents)
+    }
+
+    /// Loads and validates configuration data from the passed in configuration string.
+    ///
+    /// # Arguments
+    /// `name` - Category name used as a key in the config
+    /// `config` - Config data as a string
+    pub fn try_new_from_str(name: &str, config: &str) -> Result<Self, ConfigError> {
+        let data: Value =
+            toml::from_str(config).map_err(|err| ConfigError::Parse(err.to_string()))?;
+
+        let section = data
+            .get(name)
+            .ok_or_else(|| ConfigError::MissingSection(name.to_owned()))?;
+
+        if let Some(address) = section.get("addr") {
+            validate_address(name, address)?;
+        }
+
+        parse_config_str(name, config)
     }
 
     /// Returns the configured hosturl string in the following
@@ -154,9 +288,124 @@ impl Config {
             None => None,
         }
     }
+
+    // Copies the values of `keys` from another config of the same category,
+    // removing any of them which it doesn't have
+    pub(crate) fn with_values_from(mut self, other: &Config, keys: &[String]) -> Config {
+        if let Value::Table(ref mut table) = self.raw {
+            for key in keys {
+                match other.raw.get(key.as_str()) {
+                    Some(value) => table.insert(key.clone(), value.clone()),
+                    None => table.remove(key),
+                };
+            }
+        }
+        if keys.iter().any(|key| key == "addr") {
+            self.addr = other.addr.clone();
+        }
+        self
+    }
+
+    /// Deserializes the category's configuration values (other than `addr`) into a typed struct.
+    ///
+    /// Missing values can be given defaults with `#[serde(default)]`, and typos can be caught
+    /// with `#[serde(deny_unknown_fields)]`.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust,no_run
+    /// extern crate kubos_system;
+    /// #[macro_use]
+    /// extern crate serde_derive;
+    ///
+    /// use kubos_system::Config;
+    ///
+    /// #[derive(Deserialize)]
+    /// #[serde(deny_unknown_fields)]
+    /// struct Settings {
+    ///     bus: String,
+    ///     #[serde(default)]
+    ///     retries: u32,
+    /// }
+    ///
+    /// # fn main() {
+    /// let config = Config::new("example-service");
+    /// let settings: Settings = config.deserialize().unwrap();
+    /// # }
+    /// ```
+    pub fn deserialize<T>(&self) -> Result<T, ConfigError>
+    where
+        T: DeserializeOwned,
+    {
+        self.deserialize_ignoring(&[])
+    }
+
+    /// Deserializes the category's configuration values into a typed struct, like
+    /// `Config::deserialize`, skipping the values under the given keys.
+    ///
+    /// This allows structs which use `#[serde(deny_unknown_fields)]` to leave out settings
+    /// which are read elsewhere, like the `kubos_service::SERVICE_CONFIG_KEYS` shared by every
+    /// service.
+    ///
+    /// # Arguments
+    /// `ignored` - Keys of the values to skip
+    pub fn deserialize_ignoring<T>(&self, ignored: &[&str]) -> Result<T, ConfigError>
+    where
+        T: DeserializeOwned,
+    {
+        let mut values = match self.raw {
+            Value::Table(ref table) => table.clone(),
+            _ => toml::value::Table::new(),
+        };
+        values.remove("addr");
+        for key in ignored {
+            values.remove(*key);
+        }
+
+        Value::Table(values)
+            .try_into()
+            .map_err(|err| ConfigError::Invalid {
+                section: self.name.clone(),
+                description: err.to_string(),
+            })
+    }
+}
+
+fn validate_address(name: &str, address: &Value) -> Result<(), ConfigError> {
+    let invalid = |description: String| ConfigError::Invalid {
+        section: format!("{}.addr", name),
+        description,
+    };
+
+    let table = address
+        .as_table()
+        .ok_or_else(|| invalid("expected a table".to_owned()))?;
+
+    for (key, value) in table {
+        match key.as_ref() {
+            "ip" => {
+                let ip = value
+                    .as_str()
+                    .ok_or_else(|| invalid("ip must be a string".to_owned()))?;
+                ip.parse::<IpAddr>()
+                    .map_err(|_| invalid(format!("'{}' is not a valid IP address", ip)))?;
+            }
+            "port" => {
+                let port = value
+                    .as_integer()
+                    .ok_or_else(|| invalid("port must be an integer".to_owned()))?;
+                if port < 0 || port > i64::from(u16::max_value()) {
+                    return Err(invalid(format!("{} is not a valid port", port)));
+                }
+            }
+            other => return Err(invalid(format!("unknown field `{}`", other))),
+        }
+    }
+
+    Ok(())
 }
 
-fn get_config_path() -> String {
+pub(crate) fn get_config_path() -> String {
     let args: Vec<String> = env::args().collect();
 
     let mut opts = Options::new();
@@ -174,25 +423,35 @@ fn get_config_path() -> String {
     }
 }
 
-fn get_file_data(path: String) -> Result<String, io::Error> {
+pub(crate) fn get_file_data(path: String) -> Result<String, io::Error> {
     let mut contents = String::new();
     let mut file = File::open(path)?;
     file.read_to_string(&mut contents)?;
     Ok(contents)
 }
 
-fn parse_config_file(name: &str, path: String) -> Result<Config, toml::de::Error> {
-    let contents = get_file_data(path).unwrap_or("".to_string());
+fn parse_config_file(name: &str, path: String) -> Result<Config, ConfigError> {
+    let contents = get_file_data(path.clone()).map_err(|err| ConfigError::Read {
+        path,
+        description: err.to_string(),
+    })?;
     parse_config_str(name, &contents)
 }
 
-fn parse_config_str(name: &str, contents: &str) -> Result<Config, toml::de::Error> {
-    let data: Value = toml::from_str(&contents)?;
+fn parse_config_str(name: &str, contents: &str) -> Result<Config, ConfigError> {
+    let data: Value =
+        toml::from_str(&contents).map_err(|err| ConfigError::Parse(err.to_string()))?;
     let mut config = Config::default();
+    config.name = name.to_owned();
 
     if let Some(data) = data.get(name) {
         if let Some(address) = data.get("addr") {
-            config.addr = address.clone().try_into()?;
+            config.addr = address.clone().try_into().map_err(|err: toml::de::Error| {
+                ConfigError::Invalid {
+                    section: format!("{}.addr", name),
+                    description: err.to_string(),
+                }
+            })?;
         }
         config.raw = data.clone();
     }
@@ -0,0 +1,203 @@
+//
+// Copyright (C) 2018 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use config::{get_config_path, get_file_data, Config};
+use failure::Error;
+use fragment::recv_response;
+use serde_json;
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+use toml;
+use toml::Value;
+
+/// Introspection query used to check that a service is up and to summarize its schema
+const SUMMARY_QUERY: &str =
+    "{ __schema { queryType { fields { name } } mutationType { fields { name } } } }";
+
+/// A service found in the system configuration file
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServiceEntry {
+    name: String,
+    hosturl: String,
+}
+
+impl ServiceEntry {
+    /// Returns the name of the service's config category
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the address the service listens on, in the format `0.0.0.0:0000`
+    pub fn hosturl(&self) -> &str {
+        &self.hosturl
+    }
+
+    /// Checks whether the service is running by sending it a GraphQL introspection query.
+    ///
+    /// # Arguments
+    /// `timeout` - How long to wait for the service to respond
+    pub fn status(&self, timeout: Duration) -> ServiceStatus {
+        let start = Instant::now();
+
+        match introspect(&self.hosturl, timeout) {
+            Ok(schema) => ServiceStatus {
+                alive: true,
+                latency: Some(start.elapsed()),
+                schema: Some(schema),
+                error: None,
+            },
+            Err(err) => ServiceStatus {
+                alive: false,
+                latency: None,
+                schema: None,
+                error: Some(err.to_string()),
+            },
+        }
+    }
+}
+
+/// The result of checking on a service
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServiceStatus {
+    /// Whether the service responded to the request
+    pub alive: bool,
+    /// How long the service took to respond
+    pub latency: Option<Duration>,
+    /// Summary of the service's GraphQL schema
+    pub schema: Option<SchemaSummary>,
+    /// The reason the service couldn't be reached
+    pub error: Option<String>,
+}
+
+/// The top-level queries and mutations exposed by a service
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SchemaSummary {
+    /// Names of the fields of the service's query root
+    pub queries: Vec<String>,
+    /// Names of the fields of the service's mutation root
+    pub mutations: Vec<String>,
+}
+
+/// Directory of all the services listed in a KubOS config file.
+///
+/// Every category with an `[service-name.addr]` section is treated as a service.
+///
+/// ### Examples
+///
+/// ```rust,no_run
+/// use kubos_system::ServiceDirectory;
+/// use std::time::Duration;
+///
+/// let directory = ServiceDirectory::new();
+/// for service in directory.services() {
+///     let status = service.status(Duration::from_millis(500));
+///     println!("{} ({}): alive={}", service.name(), service.hosturl(), status.alive);
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct ServiceDirectory {
+    services: Vec<ServiceEntry>,
+}
+
+impl ServiceDirectory {
+    /// Creates a directory from the system configuration file or the path passed
+    /// as the '-c' or '--config' option to this executable.
+    pub fn new() -> Self {
+        Self::new_from_path(get_config_path())
+    }
+
+    /// Creates a directory from the passed in configuration path
+    ///
+    /// # Arguments
+    /// `path` - Path to configuration file
+    pub fn new_from_path(path: String) -> Self {
+        let contents = get_file_data(path).unwrap_or("".to_string());
+        Self::new_from_str(&contents)
+    }
+
+    /// Creates a directory from the passed in configuration string
+    ///
+    /// # Arguments
+    /// `config` - Config data as a string
+    pub fn new_from_str(config: &str) -> Self {
+        parse_directory(config).unwrap_or(ServiceDirectory::default())
+    }
+
+    /// Returns all of the services in the directory, sorted by name
+    pub fn services(&self) -> &[ServiceEntry] {
+        &self.services
+    }
+
+    /// Looks up a service by name
+    ///
+    /// # Arguments
+    /// `name` - Category name of the service
+    pub fn get(&self, name: &str) -> Option<&ServiceEntry> {
+        self.services.iter().find(|service| service.name == name)
+    }
+}
+
+fn parse_directory(contents: &str) -> Result<ServiceDirectory, toml::de::Error> {
+    let data: Value = toml::from_str(contents)?;
+
+    let mut services = vec![];
+    if let Some(table) = data.as_table() {
+        // toml tables are ordered by key, so the entries come out sorted by name
+        for (name, section) in table {
+            if section.get("addr").is_some() {
+                let config = Config::new_from_str(name, contents);
+                services.push(ServiceEntry {
+                    name: name.to_owned(),
+                    hosturl: config.hosturl(),
+                });
+            }
+        }
+    }
+
+    Ok(ServiceDirectory { services })
+}
+
+fn introspect(hosturl: &str, timeout: Duration) -> Result<SchemaSummary, Error> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect(hosturl)?;
+    socket.send(SUMMARY_QUERY.as_bytes())?;
+
+    let response = recv_response(&socket)?;
+    let response: serde_json::Value = serde_json::from_slice(&response)?;
+
+    let schema = match response.get("msg").and_then(|msg| msg.get("__schema")) {
+        Some(schema) => schema,
+        None => bail!("Unexpected response: {}", response),
+    };
+
+    Ok(SchemaSummary {
+        queries: field_names(&schema["queryType"]),
+        mutations: field_names(&schema["mutationType"]),
+    })
+}
+
+fn field_names(object_type: &serde_json::Value) -> Vec<String> {
+    object_type["fields"]
+        .as_array()
+        .map(|fields| {
+            fields
+                .iter()
+                .filter_map(|field| field["name"].as_str().map(|name| name.to_owned()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,156 @@
+//
+// Copyright (C) 2018 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Splitting of service responses into UDP datagrams, and their reassembly by clients
+
+use failure::Error;
+use std::collections::BTreeMap;
+use std::net::UdpSocket;
+
+/// Largest payload a single UDP datagram can carry
+pub const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// Marker byte which begins every datagram of a fragmented response.
+///
+/// A complete response is always JSON text, which can never start with this byte,
+/// so clients can use it to tell fragments apart from regular responses.
+pub const FRAGMENT_MARKER: u8 = 0xFF;
+
+/// Size of the header prepended to each response fragment:
+/// the marker byte, followed by the fragment's sequence number and the total
+/// number of fragments, each encoded as a big-endian `u16`.
+pub const FRAGMENT_HEADER_SIZE: usize = 5;
+
+/// Splits a response into the datagrams which should be sent to the client.
+///
+/// Responses which fit within `chunk_size` (or any response, if chunking is disabled)
+/// are sent as-is in a single datagram. Larger responses are split into fragments,
+/// each prefixed with a header of `FRAGMENT_HEADER_SIZE` bytes.
+///
+/// An error is returned if the response would need more fragments than the header can count.
+///
+/// # Arguments
+///
+/// * `response` - The complete response
+/// * `chunk_size` - The largest datagram to send, including the fragment header.
+///                  Must be larger than `FRAGMENT_HEADER_SIZE`
+pub fn fragment_response(response: &[u8], chunk_size: Option<usize>) -> Result<Vec<Vec<u8>>, Error> {
+    let chunk_size = match chunk_size {
+        Some(size) if response.len() > size => size,
+        _ => return Ok(vec![response.to_vec()]),
+    };
+    if chunk_size <= FRAGMENT_HEADER_SIZE {
+        bail!(
+            "Chunk size {} must be larger than {} bytes",
+            chunk_size,
+            FRAGMENT_HEADER_SIZE
+        );
+    }
+
+    let chunks: Vec<&[u8]> = response
+        .chunks(chunk_size - FRAGMENT_HEADER_SIZE)
+        .collect();
+    if chunks.len() > u16::max_value() as usize {
+        bail!(
+            "Response of {} bytes needs {} fragments, more than the limit of {}",
+            response.len(),
+            chunks.len(),
+            u16::max_value()
+        );
+    }
+    let total = chunks.len() as u16;
+
+    Ok(chunks
+        .iter()
+        .enumerate()
+        .map(|(seq, chunk)| {
+            let seq = seq as u16;
+            let mut datagram = Vec::with_capacity(FRAGMENT_HEADER_SIZE + chunk.len());
+            datagram.push(FRAGMENT_MARKER);
+            datagram.push((seq >> 8) as u8);
+            datagram.push(seq as u8);
+            datagram.push((total >> 8) as u8);
+            datagram.push(total as u8);
+            datagram.extend_from_slice(chunk);
+            datagram
+        })
+        .collect())
+}
+
+/// Splits a response fragment into its sequence number, total fragment count, and data
+///
+/// # Arguments
+///
+/// * `datagram` - A datagram starting with `FRAGMENT_MARKER`
+pub fn parse_fragment(datagram: &[u8]) -> Result<(u16, u16, &[u8]), Error> {
+    if datagram.len() < FRAGMENT_HEADER_SIZE || datagram[0] != FRAGMENT_MARKER {
+        bail!("Received malformed response fragment");
+    }
+
+    let seq = u16::from(datagram[1]) << 8 | u16::from(datagram[2]);
+    let total = u16::from(datagram[3]) << 8 | u16::from(datagram[4]);
+
+    if seq >= total {
+        bail!(
+            "Fragment {} is out of range of {} total fragments",
+            seq,
+            total
+        );
+    }
+
+    Ok((seq, total, &datagram[FRAGMENT_HEADER_SIZE..]))
+}
+
+/// Receives a complete response from a service, collecting and reassembling
+/// all of the pieces if the response was fragmented.
+///
+/// Any read timeout set on the socket applies to each datagram of the response
+///
+/// # Arguments
+///
+/// * `socket` - The socket the request was sent from
+pub fn recv_response(socket: &UdpSocket) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+    let size = socket.recv(&mut buf)?;
+
+    if size == 0 || buf[0] != FRAGMENT_MARKER {
+        buf.truncate(size);
+        return Ok(buf);
+    }
+
+    let mut fragments: BTreeMap<u16, Vec<u8>> = BTreeMap::new();
+    let total = {
+        let (seq, total, data) = parse_fragment(&buf[0..size])?;
+        fragments.insert(seq, data.to_vec());
+        total
+    };
+
+    while fragments.len() < total as usize {
+        let size = socket.recv(&mut buf)?;
+        let (seq, frag_total, data) = parse_fragment(&buf[0..size])?;
+        if frag_total != total {
+            bail!(
+                "Fragment {} claims {} total fragments, expected {}",
+                seq,
+                frag_total,
+                total
+            );
+        }
+        fragments.insert(seq, data.to_vec());
+    }
+
+    Ok(fragments.into_iter().flat_map(|(_, data)| data).collect())
+}
@@ -18,19 +18,33 @@
 
 //! KubOS System level APIs
 
+extern crate blake2_rfc;
 #[macro_use]
 extern crate failure;
 
 extern crate getopts;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 extern crate toml;
 
 mod config;
+mod directory;
+mod fragment;
 mod uboot;
+mod upgrade;
+mod watcher;
 
 pub use config::*;
-pub use uboot::UBootVars;
+pub use directory::{SchemaSummary, ServiceDirectory, ServiceEntry, ServiceStatus};
+pub use fragment::{
+    fragment_response, parse_fragment, recv_response, FRAGMENT_HEADER_SIZE, FRAGMENT_MARKER,
+    MAX_DATAGRAM_SIZE,
+};
+pub use uboot::{UBootBatch, UBootVars};
+pub use upgrade::{UpgradeStatus, Upgrader, UPGRADE_DIR};
+pub use watcher::{ConfigChange, ConfigWatcher};
 
 /// The name of the KubOS app service that can be used to derive service configuration
 pub const SERVICE_APP: &'static str = "app-service";
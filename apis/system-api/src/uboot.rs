@@ -16,30 +16,42 @@
 
 use failure::Error;
 
-use std::process::Command;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
 use std::str::FromStr;
 
 pub const VAR_KUBOS_CURR_VERSION: &'static str = "kubos_curr_version";
 pub const VAR_KUBOS_PREV_VERSION: &'static str = "kubos_prev_version";
 pub const VAR_KUBOS_INITIAL_DEPLOY: &'static str = "kubos_initial_deploy";
+pub const VAR_KUBOS_UPDATE_FILE: &'static str = "kubos_updatefile";
+pub const VAR_BOOTCOUNT: &'static str = "bootcount";
 
 const PRINTENV_PATH: &'static str = "/usr/sbin/fw_printenv";
+const SETENV_PATH: &'static str = "/usr/sbin/fw_setenv";
 
-/// A convenience wrapper for fetching UBoot variables used by KubOS
+/// A convenience wrapper for fetching and updating UBoot variables used by KubOS
 pub struct UBootVars {
     cmd_path: String,
+    setenv_path: String,
 }
 
 impl UBootVars {
     /// Default constructor that fetches UBoot vars using `/usr/sbin/fw_printenv`
+    /// and updates them using `/usr/sbin/fw_setenv`
     pub fn new() -> Self {
-        Self::new_from_path(PRINTENV_PATH)
+        Self::new_from_paths(PRINTENV_PATH, SETENV_PATH)
     }
 
     /// Constructor that fetches UBoot vars with a custom path to `fw_printenv`
     pub fn new_from_path(path: &str) -> Self {
+        Self::new_from_paths(path, SETENV_PATH)
+    }
+
+    /// Constructor that uses custom paths to both `fw_printenv` and `fw_setenv`
+    pub fn new_from_paths(printenv_path: &str, setenv_path: &str) -> Self {
         Self {
-            cmd_path: String::from(path),
+            cmd_path: String::from(printenv_path),
+            setenv_path: String::from(setenv_path),
         }
     }
 
@@ -85,4 +97,152 @@ impl UBootVars {
             Err(_) => None,
         }
     }
+
+    /// Sets a UBoot variable to a u32 value
+    pub fn set_u32(&self, name: &str, value: u32) -> Result<(), Error> {
+        self.batch().set_u32(name, value).commit()
+    }
+
+    /// Sets a UBoot variable to a string value
+    pub fn set_str(&self, name: &str, value: &str) -> Result<(), Error> {
+        self.batch().set_str(name, value).commit()
+    }
+
+    /// Sets a UBoot variable to a bool value, stored as `1` or `0`
+    pub fn set_bool(&self, name: &str, value: bool) -> Result<(), Error> {
+        self.batch().set_bool(name, value).commit()
+    }
+
+    /// Removes a UBoot variable from the environment
+    pub fn unset(&self, name: &str) -> Result<(), Error> {
+        self.batch().unset(name).commit()
+    }
+
+    /// Starts a batch of changes which will all be written to the environment at once.
+    ///
+    /// Nothing is written until `UBootBatch::commit` is called. If any of the changes is
+    /// invalid, none of them are applied.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust,no_run
+    /// use kubos_system::UBootVars;
+    ///
+    /// let vars = UBootVars::new();
+    /// vars.batch()
+    ///     .set_str("kubos_updatefile", "kpack-2.0.itb")
+    ///     .set_u32("bootcount", 0)
+    ///     .commit()
+    ///     .unwrap();
+    /// ```
+    pub fn batch(&self) -> UBootBatch<'_> {
+        UBootBatch {
+            vars: self,
+            changes: vec![],
+        }
+    }
+
+    fn write(&self, script: &str) -> Result<(), Error> {
+        let output = run_with_input(&self.setenv_path, &["-s", "-"], script)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!(
+                "{} failed ({}): {}",
+                self.setenv_path,
+                output.status,
+                stderr.trim()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// A set of UBoot variable changes which are written to the environment together.
+///
+/// Created with `UBootVars::batch`.
+pub struct UBootBatch<'a> {
+    vars: &'a UBootVars,
+    changes: Vec<(String, Option<String>)>,
+}
+
+impl<'a> UBootBatch<'a> {
+    /// Adds a u32 value to the batch
+    pub fn set_u32(self, name: &str, value: u32) -> Self {
+        self.set_str(name, &value.to_string())
+    }
+
+    /// Adds a string value to the batch
+    pub fn set_str(mut self, name: &str, value: &str) -> Self {
+        self.changes.push((name.to_owned(), Some(value.to_owned())));
+        self
+    }
+
+    /// Adds a bool value to the batch, stored as `1` or `0`
+    pub fn set_bool(self, name: &str, value: bool) -> Self {
+        self.set_str(name, if value { "1" } else { "0" })
+    }
+
+    /// Adds the removal of a variable to the batch
+    pub fn unset(mut self, name: &str) -> Self {
+        self.changes.push((name.to_owned(), None));
+        self
+    }
+
+    /// Writes all of the changes in the batch to the environment with a single
+    /// `fw_setenv` call, so that they are either all applied or none are
+    pub fn commit(self) -> Result<(), Error> {
+        let mut script = String::new();
+
+        for (name, value) in self.changes {
+            if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '=') {
+                bail!("Invalid variable name: {:?}", name);
+            }
+
+            match value {
+                Some(ref value) if value.contains('\n') => {
+                    bail!("Invalid value for {}: values can't contain newlines", name)
+                }
+                // An empty value would delete the variable, so only accept it through `unset`
+                Some(ref value) if value.is_empty() => {
+                    bail!("Invalid value for {}: values can't be empty", name)
+                }
+                Some(value) => script.push_str(&format!("{} {}\n", name, value)),
+                None => script.push_str(&format!("{}\n", name)),
+            }
+        }
+
+        if script.is_empty() {
+            return Ok(());
+        }
+
+        self.vars.write(&script)
+    }
+}
+
+fn run_with_input(cmd: &str, args: &[&str], input: &str) -> Result<Output, Error> {
+    let mut child = Command::new(cmd)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| format_err!("Failed to execute: {}", cmd))?;
+
+    // Always wait for the command, even if it stopped reading its input early, so that
+    // it isn't left as a zombie and its own error output isn't lost
+    let written = match child.stdin.take() {
+        Some(mut stdin) => stdin.write_all(input.as_bytes()),
+        None => Ok(()),
+    };
+
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format_err!("Failed to wait for {}: {}", cmd, err))?;
+
+    match written {
+        Err(err) if output.status.success() => bail!("Failed to write to {}: {}", cmd, err),
+        _ => Ok(output),
+    }
 }
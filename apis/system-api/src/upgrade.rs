@@ -0,0 +1,298 @@
+//
+// Copyright (C) 2018 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use blake2_rfc::blake2s::Blake2s;
+use failure::Error;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+use uboot::{self, UBootVars};
+
+/// The default directory which KubOS upgrade packages are installed from
+pub const UPGRADE_DIR: &str = "/upgrade";
+
+/// Size (in bytes) of the BLAKE2s checksum of an upgrade package.
+/// This matches the hash used by the file transfer service.
+const HASH_SIZE: usize = 16;
+
+/// The versions of KubOS known to the bootloader
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpgradeStatus {
+    /// The version which is currently running
+    pub current: Option<String>,
+    /// The version which was running before the current one
+    pub previous: Option<String>,
+    /// The version which will be installed on the next boot, if any
+    pub pending: Option<String>,
+}
+
+/// Manages KubOS upgrades and rollbacks.
+///
+/// Upgrade packages (`kpack-<version>.itb` files) are staged in the upgrade directory and
+/// the U-Boot environment is updated so that the bootloader installs the package on the next
+/// boot. Nothing is changed in the running system, so a reboot is needed to finish the upgrade.
+///
+/// ### Examples
+///
+/// ```rust,no_run
+/// use kubos_system::Upgrader;
+///
+/// let upgrader = Upgrader::new()
+///     .verifier("/home/system/usr/bin/verify-kpack")
+///     .signing_key("/home/system/etc/kpack-key.pem");
+/// upgrader
+///     .stage(
+///         "/home/system/file-transfer/kpack-2.0.itb",
+///         "e3e2e2d34cc5e1b7c6a2a1d0a1c1e4f5",
+///         Some("/home/system/file-transfer/kpack-2.0.itb.sig"),
+///     )
+///     .unwrap();
+/// ```
+pub struct Upgrader {
+    vars: UBootVars,
+    upgrade_dir: String,
+    verify_cmd: Option<String>,
+    signing_key: Option<String>,
+}
+
+impl Upgrader {
+    /// Creates an upgrader which uses the default U-Boot tools and `/upgrade` directory
+    pub fn new() -> Self {
+        Self::new_with(UBootVars::new(), UPGRADE_DIR)
+    }
+
+    /// Creates an upgrader with a custom U-Boot environment and upgrade directory
+    pub fn new_with(vars: UBootVars, upgrade_dir: &str) -> Self {
+        Upgrader {
+            vars,
+            upgrade_dir: upgrade_dir.to_owned(),
+            verify_cmd: None,
+            signing_key: None,
+        }
+    }
+
+    /// Sets the command used to check the signature of upgrade packages.
+    ///
+    /// The command is run as `<cmd> <package> <signature>` (followed by the signing key, if one
+    /// is set) and must exit successfully for the package to be staged. The package it's given
+    /// is the copy in the upgrade directory which will be staged. Once a verifier is set,
+    /// every package needs a signature.
+    pub fn verifier(mut self, cmd: &str) -> Self {
+        self.verify_cmd = Some(cmd.to_owned());
+        self
+    }
+
+    /// Sets the key which upgrade packages must be signed with.
+    ///
+    /// Once a key is set, every package needs a signature, which is checked by running the
+    /// verifier as `<cmd> <package> <signature> <key>`. Packages can't be staged at all if no
+    /// verifier has been set.
+    pub fn signing_key(mut self, key: &str) -> Self {
+        self.signing_key = Some(key.to_owned());
+        self
+    }
+
+    /// Returns the versions of KubOS known to the bootloader
+    pub fn status(&self) -> UpgradeStatus {
+        UpgradeStatus {
+            current: self.vars.get_str(uboot::VAR_KUBOS_CURR_VERSION),
+            previous: self.vars.get_str(uboot::VAR_KUBOS_PREV_VERSION),
+            pending: self.vars.get_str(uboot::VAR_KUBOS_UPDATE_FILE),
+        }
+    }
+
+    /// Verifies an upgrade package and sets it to be installed on the next boot.
+    ///
+    /// The package is copied into the upgrade directory first, and the copy is what gets verified.
+    ///
+    /// Returns the name of the staged package.
+    ///
+    /// # Arguments
+    /// `package` - Path to the upgrade package (ex. a file delivered by the file transfer service)
+    /// `hash` - The expected BLAKE2s checksum of the package, as a hex string
+    /// `signature` - Path to the package's signature file, if a verifier or signing key is configured
+    pub fn stage(
+        &self,
+        package: &str,
+        hash: &str,
+        signature: Option<&str>,
+    ) -> Result<String, Error> {
+        let name = package_name(package)?;
+
+        // Check the configuration before copying anything
+        let key = self.signing_key.as_ref().map(|key| key.as_str());
+        match (self.verify_cmd.as_ref(), key, signature) {
+            (None, Some(_), _) => bail!("A signing key is set, but no signature verifier"),
+            (Some(_), _, None) => bail!("A signature is required to stage {}", name),
+            (None, None, Some(_)) => bail!("No signature verifier has been configured"),
+            _ => {}
+        }
+
+        // Copy the package into place before checking it, so that the file which is
+        // verified is the one which gets staged, and before touching the boot environment,
+        // so that the bootloader never points at a partially written file
+        fs::create_dir_all(&self.upgrade_dir)?;
+        let dest = Path::new(&self.upgrade_dir).join(&name);
+        let staged = if dest != Path::new(package) {
+            let tmp = Path::new(&self.upgrade_dir).join(format!(".{}.tmp", name));
+            fs::copy(package, &tmp)?;
+            File::open(&tmp)?.sync_all()?;
+            tmp
+        } else {
+            dest.clone()
+        };
+
+        if let Err(err) = self.verify(&name, &staged, hash, signature) {
+            if staged != dest {
+                let _ = fs::remove_file(&staged);
+            }
+            return Err(err);
+        }
+
+        if staged != dest {
+            fs::rename(&staged, &dest)?;
+        }
+
+        self.vars
+            .batch()
+            .set_str(uboot::VAR_KUBOS_UPDATE_FILE, &name)
+            .set_u32(uboot::VAR_BOOTCOUNT, 0)
+            .commit()?;
+
+        Ok(name)
+    }
+
+    // Check a package against its checksum and, if a verifier is configured, its signature.
+    // Packages are only checked against their checksum if neither a verifier nor a
+    // signing key has been set
+    fn verify(
+        &self,
+        name: &str,
+        package: &Path,
+        hash: &str,
+        signature: Option<&str>,
+    ) -> Result<(), Error> {
+        let actual = file_hash(package)?;
+        if !actual.eq_ignore_ascii_case(hash.trim()) {
+            bail!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                name,
+                hash.trim(),
+                actual
+            );
+        }
+
+        let key = self.signing_key.as_ref().map(|key| key.as_str());
+        if let (Some(cmd), Some(signature)) = (self.verify_cmd.as_ref(), signature) {
+            verify_signature(cmd, package, signature, key)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the previous version of KubOS to be reinstalled on the next boot.
+    ///
+    /// Returns the name of the version which will be restored.
+    pub fn rollback(&self) -> Result<String, Error> {
+        let previous = match self.vars.get_str(uboot::VAR_KUBOS_PREV_VERSION) {
+            Some(ref prev) if !prev.is_empty() => prev.clone(),
+            _ => bail!("No previous version of KubOS to roll back to"),
+        };
+
+        if !Path::new(&self.upgrade_dir).join(&previous).is_file() {
+            bail!(
+                "Package for previous version {} not found in {}",
+                previous,
+                self.upgrade_dir
+            );
+        }
+
+        self.vars
+            .batch()
+            .set_str(uboot::VAR_KUBOS_UPDATE_FILE, &previous)
+            .set_u32(uboot::VAR_BOOTCOUNT, 0)
+            .commit()?;
+
+        Ok(previous)
+    }
+
+    /// Cancels a pending upgrade or rollback
+    pub fn cancel(&self) -> Result<(), Error> {
+        self.vars.unset(uboot::VAR_KUBOS_UPDATE_FILE)
+    }
+}
+
+// The bootloader derives the version from the package's file name,
+// so only accept names in the expected `kpack-<version>.itb` format
+fn package_name(package: &str) -> Result<String, Error> {
+    let name = Path::new(package)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    if !name.starts_with("kpack-") || !name.ends_with(".itb") || name.len() <= 10 {
+        bail!(
+            "Invalid upgrade package name {:?}: expected kpack-<version>.itb",
+            name
+        );
+    }
+
+    Ok(name)
+}
+
+fn file_hash(path: &Path) -> Result<String, Error> {
+    let mut file = File::open(path)
+        .map_err(|err| format_err!("Failed to open {}: {}", path.display(), err))?;
+    let mut hasher = Blake2s::new(HASH_SIZE);
+    let mut buf = vec![0; 4096];
+
+    loop {
+        let size = file.read(&mut buf)?;
+        if size == 0 {
+            break;
+        }
+        hasher.update(&buf[0..size]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .as_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+fn verify_signature(
+    cmd: &str,
+    package: &Path,
+    signature: &str,
+    key: Option<&str>,
+) -> Result<(), Error> {
+    let output = Command::new(cmd)
+        .arg(package)
+        .arg(signature)
+        .args(key)
+        .output()
+        .map_err(|_| format_err!("Failed to execute: {}", cmd))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("Signature verification failed: {}", stderr.trim());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,247 @@
+//
+// Copyright (C) 2018 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use config::{get_config_path, Config, ConfigError};
+use std::collections::BTreeSet;
+use std::fs;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use toml::Value;
+
+/// The default number of seconds between checks of the config file
+const DEFAULT_INTERVAL_SECS: u64 = 1;
+
+/// Notification sent to subscribers when a watched config file changes
+#[derive(Clone, Debug)]
+pub struct ConfigChange {
+    /// The configuration now in effect. Keys which require a restart keep the values
+    /// the service is running with
+    pub config: Config,
+    /// Names of all the keys in the category whose values changed
+    pub changed: Vec<String>,
+    /// Names of the changed keys which can't be applied until the service restarts
+    pub restart_required: Vec<String>,
+}
+
+struct WatchState {
+    // The configuration in effect
+    config: Config,
+    // The configuration most recently read from the file
+    loaded: Config,
+    modified: Option<SystemTime>,
+    subscribers: Vec<Sender<ConfigChange>>,
+    error_subscribers: Vec<Sender<ConfigError>>,
+}
+
+/// Watches a config file for changes to one category, so that long-running services
+/// can pick up new settings without restarting.
+///
+/// Changes to `addr` (and any other keys marked with `restart_required`) are still
+/// reported, but are flagged as only taking effect after a restart. Until then, the
+/// watcher's config keeps the values the service is running with.
+///
+/// ### Examples
+///
+/// ```rust,no_run
+/// use kubos_system::ConfigWatcher;
+///
+/// let watcher = ConfigWatcher::new("example-service").restart_required(&["bus"]);
+/// let updates = watcher.subscribe();
+/// let errors = watcher.errors();
+/// watcher.start();
+///
+/// std::thread::spawn(move || {
+///     for err in errors {
+///         eprintln!("Ignoring invalid config: {}", err);
+///     }
+/// });
+///
+/// for change in updates {
+///     for key in change.restart_required {
+///         println!("{} changed. Restart the service to apply it", key);
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ConfigWatcher {
+    name: String,
+    path: String,
+    interval: Duration,
+    restart_keys: Vec<String>,
+    state: Arc<Mutex<WatchState>>,
+}
+
+impl ConfigWatcher {
+    /// Creates a watcher for the system configuration file or the path passed as
+    /// the '-c' or '--config' option to this executable
+    ///
+    /// # Arguments
+    /// `name` - Category name used as a key in the config file
+    pub fn new(name: &str) -> Self {
+        Self::new_from_path(name, get_config_path())
+    }
+
+    /// Creates a watcher for the passed in configuration path
+    ///
+    /// # Arguments
+    /// `name` - Category name used as a key in the config file
+    /// `path` - Path to configuration file
+    pub fn new_from_path(name: &str, path: String) -> Self {
+        let config = Config::new_from_path(name, path.clone());
+        let state = WatchState {
+            config: config.clone(),
+            loaded: config,
+            modified: modified_time(&path),
+            subscribers: vec![],
+            error_subscribers: vec![],
+        };
+
+        ConfigWatcher {
+            name: name.to_owned(),
+            path,
+            interval: Duration::from_secs(DEFAULT_INTERVAL_SECS),
+            restart_keys: vec!["addr".to_owned()],
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Marks additional keys which can only be applied by restarting the service
+    pub fn restart_required(mut self, keys: &[&str]) -> Self {
+        self.restart_keys
+            .extend(keys.iter().map(|key| (*key).to_owned()));
+        self
+    }
+
+    /// Sets how often the config file is checked for changes (once a second by default)
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns the configuration currently in effect
+    pub fn config(&self) -> Config {
+        self.lock().config.clone()
+    }
+
+    /// Returns a channel which will receive a notification every time the config changes
+    pub fn subscribe(&self) -> Receiver<ConfigChange> {
+        let (sender, receiver) = channel();
+        self.lock().subscribers.push(sender);
+        receiver
+    }
+
+    /// Returns a channel which will receive the error every time the config file is
+    /// changed in a way which can't be loaded, while it's being watched by `start`.
+    /// The current config is kept when this happens.
+    pub fn errors(&self) -> Receiver<ConfigError> {
+        let (sender, receiver) = channel();
+        self.lock().error_subscribers.push(sender);
+        receiver
+    }
+
+    /// Starts a background thread which checks the config file for changes
+    /// every `interval` and notifies subscribers of them.
+    ///
+    /// The thread stops once it finds that every receiver from `subscribe` and `errors`
+    /// has been dropped, so subscribe before starting the watcher.
+    pub fn start(&self) {
+        let watcher = self.clone();
+
+        thread::spawn(move || loop {
+            thread::sleep(watcher.interval);
+
+            {
+                let state = watcher.lock();
+                if state.subscribers.is_empty() && state.error_subscribers.is_empty() {
+                    break;
+                }
+                if modified_time(&watcher.path) == state.modified {
+                    continue;
+                }
+            }
+
+            if let Err(err) = watcher.reload() {
+                // Drop any subscribers which have gone away
+                watcher
+                    .lock()
+                    .error_subscribers
+                    .retain(|subscriber| subscriber.send(err.clone()).is_ok());
+            }
+        });
+    }
+
+    /// Re-reads the config file immediately (ex. in response to a SIGHUP) and notifies
+    /// subscribers if the category's values have changed.
+    ///
+    /// If the new file is invalid, the current config is kept and the error is returned.
+    pub fn reload(&self) -> Result<Option<ConfigChange>, ConfigError> {
+        let mut state = self.lock();
+        state.modified = modified_time(&self.path);
+
+        let config = Config::try_new_from_path(&self.name, self.path.clone())?;
+
+        let changed = changed_keys(&state.loaded.raw(), &config.raw());
+        if changed.is_empty() {
+            return Ok(None);
+        }
+
+        let restart_required = changed
+            .iter()
+            .filter(|key| self.restart_keys.contains(key))
+            .cloned()
+            .collect();
+
+        state.loaded = config.clone();
+        state.config = config.with_values_from(&state.config, &self.restart_keys);
+
+        let change = ConfigChange {
+            config: state.config.clone(),
+            changed,
+            restart_required,
+        };
+
+        // Drop any subscribers which have gone away
+        state
+            .subscribers
+            .retain(|subscriber| subscriber.send(change.clone()).is_ok());
+
+        Ok(Some(change))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, WatchState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn changed_keys(old: &Value, new: &Value) -> Vec<String> {
+    let keys: BTreeSet<&String> = old
+        .as_table()
+        .into_iter()
+        .chain(new.as_table())
+        .flat_map(|table| table.keys())
+        .collect();
+
+    keys.into_iter()
+        .filter(|key| old.get(key.as_str()) != new.get(key.as_str()))
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,66 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+// Helpers shared by the integration tests
+
+#![allow(dead_code)]
+
+use kubos_system::UBootVars;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tempfile::TempDir;
+
+// Fake U-Boot environment tools which keep variables in a `name=value` file,
+// so that tests can update variables and read them back
+const FILE_PRINTENV: &'static str = r#"#!/bin/bash
+set -o pipefail
+STATE="$(dirname "$0")/env"
+grep -m 1 "^$2=" "$STATE" 2>/dev/null | cut -d= -f2- || exit 1
+"#;
+
+const FILE_SETENV: &'static str = r#"#!/bin/bash
+STATE="$(dirname "$0")/env"
+[[ -e "$(dirname "$0")/fail" ]] && { echo "Cannot access environment" >&2; exit 1; }
+touch "$STATE"
+while read -r name value; do
+    grep -v "^$name=" "$STATE" > "$STATE.tmp"
+    [[ -n "$value" ]] && echo "$name=$value" >> "$STATE.tmp"
+    mv "$STATE.tmp" "$STATE"
+done
+"#;
+
+pub fn write_script(path: &Path, contents: &str) {
+    fs::write(path, contents).unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// Creates a fake U-Boot environment in a new temporary directory.
+/// Creating a file named `fail` in the directory makes `fw_setenv` fail.
+pub fn setup_file_vars() -> (TempDir, UBootVars) {
+    let dir = TempDir::new().unwrap();
+    write_script(&dir.path().join("fw_printenv"), FILE_PRINTENV);
+    write_script(&dir.path().join("fw_setenv"), FILE_SETENV);
+
+    let vars = open_vars(dir.path());
+    (dir, vars)
+}
+
+/// Opens another handle to a fake U-Boot environment created by `setup_file_vars`
+pub fn open_vars(dir: &Path) -> UBootVars {
+    let printenv = dir.join("fw_printenv");
+    let setenv = dir.join("fw_setenv");
+    UBootVars::new_from_paths(printenv.to_str().unwrap(), setenv.to_str().unwrap())
+}
@@ -15,6 +15,9 @@
  */
 #![deny(warnings)]
 extern crate kubos_system;
+extern crate tempfile;
+
+mod common;
 
 use std::env;
 use std::fs;
@@ -86,3 +89,103 @@ fn str_vars() {
     env::set_var("currv", "");
     assert_eq!(vars.get_str("currv"), Some(String::from("")));
 }
+
+#[test]
+fn set_vars() {
+    let (_dir, vars) = common::setup_file_vars();
+
+    vars.set_u32("bootcount", 3).unwrap();
+    vars.set_str("kubos_curr_version", "kpack-1.2.itb").unwrap();
+    vars.set_bool("kubos_initial_deploy", true).unwrap();
+
+    assert_eq!(vars.get_u32("bootcount"), Some(3));
+    assert_eq!(
+        vars.get_str("kubos_curr_version"),
+        Some(String::from("kpack-1.2.itb"))
+    );
+    assert_eq!(vars.get_bool("kubos_initial_deploy"), Some(true));
+
+    vars.set_bool("kubos_initial_deploy", false).unwrap();
+    assert_eq!(vars.get_bool("kubos_initial_deploy"), Some(false));
+}
+
+#[test]
+fn unset_var() {
+    let (_dir, vars) = common::setup_file_vars();
+
+    vars.set_u32("bootcount", 1).unwrap();
+    vars.unset("bootcount").unwrap();
+
+    assert_eq!(vars.get_u32("bootcount"), None);
+}
+
+#[test]
+fn batch_vars() {
+    let (_dir, vars) = common::setup_file_vars();
+
+    vars.set_str("kubos_updatefile", "kpack-1.0.itb").unwrap();
+    vars.batch()
+        .set_str("kubos_curr_version", "kpack-2.0.itb")
+        .set_u32("bootcount", 0)
+        .unset("kubos_updatefile")
+        .commit()
+        .unwrap();
+
+    assert_eq!(
+        vars.get_str("kubos_curr_version"),
+        Some(String::from("kpack-2.0.itb"))
+    );
+    assert_eq!(vars.get_u32("bootcount"), Some(0));
+    assert_eq!(vars.get_str("kubos_updatefile"), None);
+}
+
+#[test]
+fn batch_invalid_applies_nothing() {
+    let (_dir, vars) = common::setup_file_vars();
+
+    let result = vars
+        .batch()
+        .set_u32("bootcount", 1)
+        .set_str("bad name", "value")
+        .commit();
+
+    assert!(result.is_err());
+    assert_eq!(vars.get_u32("bootcount"), None);
+}
+
+#[test]
+fn set_invalid_value() {
+    let (_dir, vars) = common::setup_file_vars();
+
+    assert!(vars.set_str("kubos_curr_version", "line1\nline2").is_err());
+    assert!(vars.set_str("kubos_curr_version", "").is_err());
+    assert_eq!(vars.get_str("kubos_curr_version"), None);
+}
+
+#[test]
+fn set_command_fails() {
+    let (dir, vars) = common::setup_file_vars();
+    fs::write(dir.path().join("fail"), "").unwrap();
+
+    let err = vars.set_u32("bootcount", 1).unwrap_err();
+    assert!(err.to_string().contains("Cannot access environment"));
+}
+
+#[test]
+fn set_command_fails_without_reading() {
+    let (dir, vars) = common::setup_file_vars();
+    fs::write(dir.path().join("fail"), "").unwrap();
+
+    // More input than fits in a pipe, so writing it fails once the command exits
+    let value = "x".repeat(1024 * 1024);
+    let err = vars.set_str("kubos_curr_version", &value).unwrap_err();
+    assert!(err.to_string().contains("Cannot access environment"));
+}
+
+#[test]
+fn set_missing_command() {
+    let vars = UBootVars::new_from_paths("/fake/fw_printenv", "/fake/fw_setenv");
+
+    let err = vars.set_u32("bootcount", 1).unwrap_err();
+    assert_eq!(err.to_string(), "Failed to execute: /fake/fw_setenv");
+}
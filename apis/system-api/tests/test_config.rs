@@ -15,9 +15,12 @@
  */
 #![deny(warnings)]
 extern crate kubos_system;
+#[macro_use]
+extern crate serde_derive;
 extern crate tempfile;
 extern crate toml;
 
+use kubos_system::ConfigError;
 use std::io::Write;
 use tempfile::NamedTempFile;
 use toml::Value;
@@ -168,3 +171,220 @@ fn only_category_config() {
     assert_eq!(config.get("c"), None);
     assert_eq!(config.get("d"), None);
 }
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Settings {
+    bus: String,
+    #[serde(default)]
+    retries: u32,
+}
+
+#[test]
+fn try_new_from_str() {
+    let config = kubos_system::Config::try_new_from_str(
+        "category-1",
+        r#"
+    [category-1]
+    bus = "/dev/ttyS1"
+    [category-1.addr]
+    ip = "10.0.1.1"
+    port = 9876
+    "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.hosturl(), "10.0.1.1:9876");
+    assert_eq!(
+        config.get("bus"),
+        Some(Value::String("/dev/ttyS1".to_owned()))
+    );
+}
+
+#[test]
+fn try_new_missing_section() {
+    let result = kubos_system::Config::try_new_from_str(
+        "category-1",
+        r#"
+    [categroy-1.addr]
+    port = 9876
+    "#,
+    );
+
+    assert_eq!(
+        result.unwrap_err(),
+        ConfigError::MissingSection("category-1".to_owned())
+    );
+}
+
+#[test]
+fn try_new_address_typo() {
+    let result = kubos_system::Config::try_new_from_str(
+        "category-1",
+        r#"
+    [category-1.addr]
+    ip = "10.0.1.1"
+    prot = 9876
+    "#,
+    );
+
+    assert_eq!(
+        result.unwrap_err(),
+        ConfigError::Invalid {
+            section: "category-1.addr".to_owned(),
+            description: "unknown field `prot`".to_owned(),
+        }
+    );
+}
+
+#[test]
+fn try_new_bad_ip() {
+    let result = kubos_system::Config::try_new_from_str(
+        "category-1",
+        r#"
+    [category-1.addr]
+    ip = "10.0.1"
+    "#,
+    );
+
+    assert_eq!(
+        result.unwrap_err(),
+        ConfigError::Invalid {
+            section: "category-1.addr".to_owned(),
+            description: "'10.0.1' is not a valid IP address".to_owned(),
+        }
+    );
+}
+
+#[test]
+fn try_new_bad_toml() {
+    let result = kubos_system::Config::try_new_from_str("category-1", "[category-1\na = 1");
+
+    match result {
+        Err(ConfigError::Parse(_)) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn try_new_bad_path() {
+    let result = kubos_system::Config::try_new_from_path("category-1", "/fake/path".to_owned());
+
+    match result {
+        Err(ConfigError::Read { path, .. }) => assert_eq!(path, "/fake/path"),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn deserialize_settings() {
+    let config = kubos_system::Config::new_from_str(
+        "category-1",
+        r#"
+    [category-1]
+    bus = "/dev/ttyS1"
+    [category-1.addr]
+    port = 9876
+    "#,
+    );
+
+    assert_eq!(
+        config.deserialize::<Settings>().unwrap(),
+        Settings {
+            bus: "/dev/ttyS1".to_owned(),
+            retries: 0,
+        }
+    );
+}
+
+#[test]
+fn deserialize_unknown_field() {
+    let config = kubos_system::Config::new_from_str(
+        "category-1",
+        r#"
+    [category-1]
+    bus = "/dev/ttyS1"
+    retires = 3
+    "#,
+    );
+
+    match config.deserialize::<Settings>() {
+        Err(ConfigError::Invalid {
+            section,
+            description,
+        }) => {
+            assert_eq!(section, "category-1");
+            assert!(description.contains("retires"));
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn deserialize_ignoring_keys() {
+    let config = kubos_system::Config::new_from_str(
+        "category-1",
+        r#"
+    [category-1]
+    bus = "/dev/ttyS1"
+    chunk_size = 1024
+    "#,
+    );
+
+    assert_eq!(
+        config
+            .deserialize_ignoring::<Settings>(&["chunk_size"])
+            .unwrap(),
+        Settings {
+            bus: "/dev/ttyS1".to_owned(),
+            retries: 0,
+        }
+    );
+    assert!(config.deserialize::<Settings>().is_err());
+
+    // Misspelled keys aren't ignored
+    let config = kubos_system::Config::new_from_str(
+        "category-1",
+        r#"
+    [category-1]
+    bus = "/dev/ttyS1"
+    chunck_size = 1024
+    "#,
+    );
+
+    match config.deserialize_ignoring::<Settings>(&["chunk_size"]) {
+        Err(ConfigError::Invalid { description, .. }) => {
+            assert!(description.contains("chunck_size"))
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn deserialize_missing_field() {
+    let config = kubos_system::Config::new_from_str("category-1", "");
+
+    match config.deserialize::<Settings>() {
+        Err(ConfigError::Invalid { description, .. }) => assert!(description.contains("bus")),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn display_effective_config() {
+    let config = kubos_system::Config::new_from_str(
+        "category-1",
+        r#"
+    [category-1]
+    bus = "/dev/ttyS1"
+    [category-1.addr]
+    port = 9876
+    "#,
+    );
+
+    // The printed config can be read back, and includes the default values used
+    let printed = kubos_system::Config::new_from_str("category-1", &config.to_string());
+    assert_eq!(printed.hosturl(), "127.0.0.1:9876");
+    assert_eq!(printed.get("bus"), config.get("bus"));
+    assert!(config.to_string().starts_with("[category-1]"));
+}
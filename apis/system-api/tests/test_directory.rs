@@ -0,0 +1,174 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+#![deny(warnings)]
+extern crate kubos_system;
+extern crate tempfile;
+
+use kubos_system::{SchemaSummary, ServiceDirectory};
+use std::io::Write;
+use std::net::UdpSocket;
+use std::thread;
+use std::time::Duration;
+use tempfile::NamedTempFile;
+
+const CONFIG: &str = r#"
+    [telemetry-service]
+    database = "/var/lib/telemetry.db"
+
+    [telemetry-service.addr]
+    ip = "127.0.0.1"
+    port = 8089
+
+    [app-service.addr]
+    ip = "0.0.0.0"
+    port = 8000
+
+    [my-app]
+    setting = 1
+    "#;
+
+// Reply to a single request with the given response
+fn mock_service(port: u16, response: &'static [u8]) {
+    let socket = UdpSocket::bind(("127.0.0.1", port)).unwrap();
+
+    thread::spawn(move || {
+        let mut buf = [0; 1024];
+        let (_, peer) = socket.recv_from(&mut buf).unwrap();
+        socket.send_to(response, &peer).unwrap();
+    });
+}
+
+#[test]
+fn list_services() {
+    let directory = ServiceDirectory::new_from_str(CONFIG);
+
+    let services: Vec<(&str, &str)> = directory
+        .services()
+        .iter()
+        .map(|service| (service.name(), service.hosturl()))
+        .collect();
+
+    assert_eq!(
+        services,
+        vec![
+            ("app-service", "0.0.0.0:8000"),
+            ("telemetry-service", "127.0.0.1:8089"),
+        ]
+    );
+}
+
+#[test]
+fn get_service() {
+    let directory = ServiceDirectory::new_from_str(CONFIG);
+
+    assert_eq!(
+        directory.get("telemetry-service").unwrap().hosturl(),
+        "127.0.0.1:8089"
+    );
+    assert!(directory.get("my-app").is_none());
+}
+
+#[test]
+fn new_from_path() {
+    let mut config_file = NamedTempFile::new().unwrap();
+    write!(config_file, "{}", CONFIG).unwrap();
+
+    let directory =
+        ServiceDirectory::new_from_path(config_file.path().to_string_lossy().to_string());
+
+    assert_eq!(directory.services().len(), 2);
+}
+
+#[test]
+fn new_from_bad_path() {
+    let directory = ServiceDirectory::new_from_path("/fake/path".to_owned());
+
+    assert!(directory.services().is_empty());
+}
+
+#[test]
+fn service_status_alive() {
+    mock_service(
+        8140,
+        br#"{"errs": "", "msg": {"__schema": {
+            "queryType": {"fields": [{"name": "ping"}, {"name": "power"}]},
+            "mutationType": {"fields": [{"name": "noop"}]}
+        }}}"#,
+    );
+
+    let directory = ServiceDirectory::new_from_str(
+        r#"
+        [mock-service.addr]
+        ip = "127.0.0.1"
+        port = 8140
+        "#,
+    );
+    let status = directory
+        .get("mock-service")
+        .unwrap()
+        .status(Duration::from_secs(1));
+
+    assert!(status.alive);
+    assert!(status.latency.is_some());
+    assert_eq!(status.error, None);
+    assert_eq!(
+        status.schema,
+        Some(SchemaSummary {
+            queries: vec!["ping".to_owned(), "power".to_owned()],
+            mutations: vec!["noop".to_owned()],
+        })
+    );
+}
+
+#[test]
+fn service_status_down() {
+    let directory = ServiceDirectory::new_from_str(
+        r#"
+        [missing-service.addr]
+        ip = "127.0.0.1"
+        port = 8141
+        "#,
+    );
+    let status = directory
+        .get("missing-service")
+        .unwrap()
+        .status(Duration::from_millis(100));
+
+    assert!(!status.alive);
+    assert_eq!(status.schema, None);
+    assert!(status.error.is_some());
+}
+
+#[test]
+fn service_status_bad_fragment() {
+    // A fragment claiming there are no fragments can never complete
+    mock_service(8142, &[0xFF, 0, 0, 0, 0]);
+
+    let directory = ServiceDirectory::new_from_str(
+        r#"
+        [mock-service.addr]
+        ip = "127.0.0.1"
+        port = 8142
+        "#,
+    );
+    let status = directory
+        .get("mock-service")
+        .unwrap()
+        .status(Duration::from_secs(5));
+
+    assert!(!status.alive);
+    assert!(status.error.unwrap().contains("out of range"));
+}
@@ -0,0 +1,135 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+#![deny(warnings)]
+extern crate kubos_system;
+
+use kubos_system::{fragment_response, parse_fragment, recv_response};
+use std::net::UdpSocket;
+use std::time::Duration;
+
+// Create a pair of sockets connected to each other
+fn socket_pair() -> (UdpSocket, UdpSocket) {
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let service = UdpSocket::bind("127.0.0.1:0").unwrap();
+    client.connect(service.local_addr().unwrap()).unwrap();
+    service.connect(client.local_addr().unwrap()).unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_millis(500)))
+        .unwrap();
+    (client, service)
+}
+
+#[test]
+fn fragment_disabled() {
+    let response = vec![b'a'; 10000];
+
+    assert_eq!(fragment_response(&response, None).unwrap(), vec![response]);
+}
+
+#[test]
+fn fragment_small_response() {
+    let response = b"{\"errs\":\"\",\"msg\":{\"ping\":\"pong\"}}".to_vec();
+
+    assert_eq!(
+        fragment_response(&response, Some(100)).unwrap(),
+        vec![response]
+    );
+}
+
+#[test]
+fn fragment_large_response() {
+    let response: Vec<u8> = (0..20).collect();
+    let fragments = fragment_response(&response, Some(13)).unwrap();
+
+    assert_eq!(
+        fragments,
+        vec![
+            vec![0xFF, 0, 0, 0, 3, 0, 1, 2, 3, 4, 5, 6, 7],
+            vec![0xFF, 0, 1, 0, 3, 8, 9, 10, 11, 12, 13, 14, 15],
+            vec![0xFF, 0, 2, 0, 3, 16, 17, 18, 19],
+        ]
+    );
+}
+
+#[test]
+fn fragment_too_many() {
+    // One byte of data per fragment needs one fragment more than the header can count
+    let response = vec![b'a'; 65536];
+
+    assert!(fragment_response(&response, Some(6)).is_err());
+    assert_eq!(
+        fragment_response(&response[1..], Some(6)).unwrap().len(),
+        65535
+    );
+}
+
+#[test]
+fn parse_fragment_good() {
+    let (seq, total, data) = parse_fragment(&[0xFF, 0, 1, 0, 3, 8, 9]).unwrap();
+
+    assert_eq!((seq, total, data), (1, 3, &[8, 9][..]));
+}
+
+#[test]
+fn parse_fragment_out_of_range() {
+    assert!(parse_fragment(&[0xFF, 0, 3, 0, 3, 8, 9]).is_err());
+    assert!(parse_fragment(&[0xFF, 0, 0, 0, 0, 8, 9]).is_err());
+}
+
+#[test]
+fn parse_fragment_truncated() {
+    assert!(parse_fragment(&[0xFF, 0, 0, 0]).is_err());
+}
+
+#[test]
+fn recv_plain_response() {
+    let (client, service) = socket_pair();
+    service.send(b"{\"msg\":\"pong\"}").unwrap();
+
+    assert_eq!(recv_response(&client).unwrap(), b"{\"msg\":\"pong\"}".to_vec());
+}
+
+#[test]
+fn recv_fragmented_response() {
+    let (client, service) = socket_pair();
+    let response: Vec<u8> = (0..20).collect();
+
+    // Fragments may arrive in any order
+    let mut fragments = fragment_response(&response, Some(13)).unwrap();
+    fragments.reverse();
+    for fragment in fragments {
+        service.send(&fragment).unwrap();
+    }
+
+    assert_eq!(recv_response(&client).unwrap(), response);
+}
+
+#[test]
+fn recv_zero_fragments() {
+    let (client, service) = socket_pair();
+    service.send(&[0xFF, 0, 0, 0, 0]).unwrap();
+
+    assert!(recv_response(&client).is_err());
+}
+
+#[test]
+fn recv_mismatched_total() {
+    let (client, service) = socket_pair();
+    service.send(&[0xFF, 0, 0, 0, 2, 1]).unwrap();
+    service.send(&[0xFF, 0, 1, 0, 3, 2]).unwrap();
+
+    assert!(recv_response(&client).is_err());
+}
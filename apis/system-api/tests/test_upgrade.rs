@@ -0,0 +1,297 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+#![deny(warnings)]
+extern crate blake2_rfc;
+extern crate kubos_system;
+extern crate tempfile;
+
+mod common;
+
+use blake2_rfc::blake2s::blake2s;
+use kubos_system::{UpgradeStatus, Upgrader};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+const PACKAGE_DATA: &[u8] = b"fake kubos upgrade package";
+
+// Pretends to check a signature by comparing it against the expected contents
+const VERIFIER: &'static str = r#"#!/bin/bash
+[[ "$(cat "$2")" == "valid" ]] || { echo "Bad signature for $1" >&2; exit 1; }
+"#;
+
+// Pretends to check a signature against a key by comparing both with the expected contents
+const KEY_VERIFIER: &'static str = r#"#!/bin/bash
+[[ "$(cat "$3")" == "key" ]] || { echo "Wrong key" >&2; exit 1; }
+[[ "$(cat "$2")" == "valid" ]] || { echo "Bad signature for $1" >&2; exit 1; }
+"#;
+
+fn hash(data: &[u8]) -> String {
+    blake2s(16, &[], data)
+        .as_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn write_package(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, PACKAGE_DATA).unwrap();
+    path
+}
+
+fn setup() -> (TempDir, Upgrader, String) {
+    let (dir, vars) = common::setup_file_vars();
+    let upgrade_dir = dir.path().join("upgrade");
+    let upgrader = Upgrader::new_with(vars, upgrade_dir.to_str().unwrap());
+    (dir, upgrader, upgrade_dir.to_string_lossy().to_string())
+}
+
+#[test]
+fn stage_package() {
+    let (dir, upgrader, upgrade_dir) = setup();
+    let package = write_package(dir.path(), "kpack-2.0.itb");
+
+    let staged = upgrader
+        .stage(package.to_str().unwrap(), &hash(PACKAGE_DATA), None)
+        .unwrap();
+
+    assert_eq!(staged, "kpack-2.0.itb");
+    assert_eq!(
+        fs::read(Path::new(&upgrade_dir).join("kpack-2.0.itb")).unwrap(),
+        PACKAGE_DATA
+    );
+
+    let vars = common::open_vars(dir.path());
+    assert_eq!(
+        vars.get_str("kubos_updatefile"),
+        Some("kpack-2.0.itb".to_owned())
+    );
+    assert_eq!(vars.get_u32("bootcount"), Some(0));
+    assert_eq!(upgrader.status().pending, Some("kpack-2.0.itb".to_owned()));
+}
+
+#[test]
+fn stage_bad_checksum() {
+    let (dir, upgrader, upgrade_dir) = setup();
+    let package = write_package(dir.path(), "kpack-2.0.itb");
+
+    let err = upgrader
+        .stage(package.to_str().unwrap(), "0123456789abcdef", None)
+        .unwrap_err();
+
+    assert!(err.to_string().starts_with("Checksum mismatch"));
+    assert!(!Path::new(&upgrade_dir).join("kpack-2.0.itb").exists());
+    assert!(!Path::new(&upgrade_dir).join(".kpack-2.0.itb.tmp").exists());
+    assert_eq!(upgrader.status().pending, None);
+}
+
+#[test]
+fn stage_bad_name() {
+    let (dir, upgrader, _upgrade_dir) = setup();
+    let package = write_package(dir.path(), "upgrade.bin");
+
+    let err = upgrader
+        .stage(package.to_str().unwrap(), &hash(PACKAGE_DATA), None)
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Invalid upgrade package name \"upgrade.bin\": expected kpack-<version>.itb"
+    );
+}
+
+#[test]
+fn stage_signed_package() {
+    let (dir, upgrader, _upgrade_dir) = setup();
+    let verifier = dir.path().join("verify");
+    common::write_script(&verifier, VERIFIER);
+    let upgrader = upgrader.verifier(verifier.to_str().unwrap());
+
+    let package = write_package(dir.path(), "kpack-2.0.itb");
+    let signature = dir.path().join("kpack-2.0.itb.sig");
+
+    fs::write(&signature, "forged").unwrap();
+    let err = upgrader
+        .stage(
+            package.to_str().unwrap(),
+            &hash(PACKAGE_DATA),
+            signature.to_str(),
+        )
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("Signature verification failed: Bad signature"));
+    assert_eq!(upgrader.status().pending, None);
+
+    fs::write(&signature, "valid").unwrap();
+    upgrader
+        .stage(
+            package.to_str().unwrap(),
+            &hash(PACKAGE_DATA),
+            signature.to_str(),
+        )
+        .unwrap();
+    assert_eq!(upgrader.status().pending, Some("kpack-2.0.itb".to_owned()));
+}
+
+#[test]
+fn stage_signing_key() {
+    let (dir, upgrader, _upgrade_dir) = setup();
+    let verifier = dir.path().join("verify");
+    common::write_script(&verifier, KEY_VERIFIER);
+    let key = dir.path().join("kpack-key.pem");
+    fs::write(&key, "key").unwrap();
+    let upgrader = upgrader
+        .verifier(verifier.to_str().unwrap())
+        .signing_key(key.to_str().unwrap());
+
+    let package = write_package(dir.path(), "kpack-2.0.itb");
+    let signature = dir.path().join("kpack-2.0.itb.sig");
+    fs::write(&signature, "valid").unwrap();
+
+    let err = upgrader
+        .stage(package.to_str().unwrap(), &hash(PACKAGE_DATA), None)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "A signature is required to stage kpack-2.0.itb"
+    );
+
+    upgrader
+        .stage(
+            package.to_str().unwrap(),
+            &hash(PACKAGE_DATA),
+            signature.to_str(),
+        )
+        .unwrap();
+    assert_eq!(upgrader.status().pending, Some("kpack-2.0.itb".to_owned()));
+}
+
+#[test]
+fn stage_package_changed_during_checks() {
+    let (dir, upgrader, upgrade_dir) = setup();
+    let package = write_package(dir.path(), "kpack-2.0.itb");
+    let signature = dir.path().join("kpack-2.0.itb.sig");
+    fs::write(&signature, "valid").unwrap();
+
+    // Replaces the original package while its signature is being checked
+    let verifier = dir.path().join("verify");
+    common::write_script(
+        &verifier,
+        &format!("#!/bin/bash\necho tampered > {}\n", package.display()),
+    );
+    let upgrader = upgrader.verifier(verifier.to_str().unwrap());
+
+    upgrader
+        .stage(
+            package.to_str().unwrap(),
+            &hash(PACKAGE_DATA),
+            signature.to_str(),
+        )
+        .unwrap();
+
+    // The package which was checked is the one which was staged
+    assert_eq!(
+        fs::read(Path::new(&upgrade_dir).join("kpack-2.0.itb")).unwrap(),
+        PACKAGE_DATA
+    );
+}
+
+#[test]
+fn stage_signing_key_no_verifier() {
+    let (dir, upgrader, _upgrade_dir) = setup();
+    let upgrader = upgrader.signing_key("/fake/key.pem");
+    let package = write_package(dir.path(), "kpack-2.0.itb");
+
+    // Packages are never staged unchecked while a key is set
+    let err = upgrader
+        .stage(package.to_str().unwrap(), &hash(PACKAGE_DATA), None)
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "A signing key is set, but no signature verifier"
+    );
+    assert_eq!(upgrader.status().pending, None);
+}
+
+#[test]
+fn stage_missing_signature() {
+    let (dir, upgrader, _upgrade_dir) = setup();
+    let upgrader = upgrader.verifier("/fake/verify");
+    let package = write_package(dir.path(), "kpack-2.0.itb");
+
+    let err = upgrader
+        .stage(package.to_str().unwrap(), &hash(PACKAGE_DATA), None)
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "A signature is required to stage kpack-2.0.itb"
+    );
+}
+
+#[test]
+fn rollback() {
+    let (dir, upgrader, upgrade_dir) = setup();
+    let vars = common::open_vars(dir.path());
+    vars.batch()
+        .set_str("kubos_curr_version", "kpack-2.0.itb")
+        .set_str("kubos_prev_version", "kpack-1.0.itb")
+        .set_u32("bootcount", 2)
+        .commit()
+        .unwrap();
+
+    fs::create_dir_all(&upgrade_dir).unwrap();
+    write_package(Path::new(&upgrade_dir), "kpack-1.0.itb");
+
+    assert_eq!(upgrader.rollback().unwrap(), "kpack-1.0.itb");
+    assert_eq!(
+        upgrader.status(),
+        UpgradeStatus {
+            current: Some("kpack-2.0.itb".to_owned()),
+            previous: Some("kpack-1.0.itb".to_owned()),
+            pending: Some("kpack-1.0.itb".to_owned()),
+        }
+    );
+    assert_eq!(vars.get_u32("bootcount"), Some(0));
+
+    upgrader.cancel().unwrap();
+    assert_eq!(upgrader.status().pending, None);
+}
+
+#[test]
+fn rollback_no_previous() {
+    let (_dir, upgrader, _upgrade_dir) = setup();
+
+    let err = upgrader.rollback().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "No previous version of KubOS to roll back to"
+    );
+}
+
+#[test]
+fn rollback_missing_package() {
+    let (dir, upgrader, _upgrade_dir) = setup();
+    common::open_vars(dir.path())
+        .set_str("kubos_prev_version", "kpack-1.0.itb")
+        .unwrap();
+
+    assert!(upgrader.rollback().is_err());
+    assert_eq!(upgrader.status().pending, None);
+}
@@ -0,0 +1,177 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+#![deny(warnings)]
+extern crate kubos_system;
+extern crate tempfile;
+extern crate toml;
+
+use kubos_system::{ConfigError, ConfigWatcher};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+use tempfile::TempDir;
+use toml::Value;
+
+const CONFIG: &str = r#"
+    [test-service]
+    timeout = 2
+    bus = "/dev/ttyS1"
+
+    [test-service.addr]
+    port = 8000
+    "#;
+
+fn write_config(dir: &TempDir, contents: &str) -> String {
+    let path = dir.path().join("config.toml");
+    fs::write(&path, contents).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+#[test]
+fn reload_no_changes() {
+    let dir = TempDir::new().unwrap();
+    let path = write_config(&dir, CONFIG);
+
+    let watcher = ConfigWatcher::new_from_path("test-service", path);
+
+    assert!(watcher.reload().unwrap().is_none());
+}
+
+#[test]
+fn reload_live_change() {
+    let dir = TempDir::new().unwrap();
+    let path = write_config(&dir, CONFIG);
+
+    let watcher = ConfigWatcher::new_from_path("test-service", path);
+    let updates = watcher.subscribe();
+
+    write_config(&dir, &CONFIG.replace("timeout = 2", "timeout = 10"));
+    watcher.reload().unwrap();
+
+    let change = updates.try_recv().unwrap();
+    assert_eq!(change.changed, vec!["timeout".to_owned()]);
+    assert!(change.restart_required.is_empty());
+    assert_eq!(change.config.get("timeout"), Some(Value::Integer(10)));
+    assert_eq!(watcher.config().get("timeout"), Some(Value::Integer(10)));
+}
+
+#[test]
+fn reload_restart_required() {
+    let dir = TempDir::new().unwrap();
+    let path = write_config(&dir, CONFIG);
+
+    let watcher = ConfigWatcher::new_from_path("test-service", path).restart_required(&["bus"]);
+
+    let contents = CONFIG
+        .replace("ttyS1", "ttyS3")
+        .replace("port = 8000", "port = 8001")
+        .replace("timeout = 2", "timeout = 5");
+    write_config(&dir, &contents);
+
+    let change = watcher.reload().unwrap().unwrap();
+    assert_eq!(
+        change.changed,
+        vec!["addr".to_owned(), "bus".to_owned(), "timeout".to_owned()]
+    );
+    assert_eq!(
+        change.restart_required,
+        vec!["addr".to_owned(), "bus".to_owned()]
+    );
+
+    // Only the live settings take effect until the service restarts
+    for config in vec![change.config, watcher.config()] {
+        assert_eq!(config.get("timeout"), Some(Value::Integer(5)));
+        assert_eq!(config.get("bus"), Some(Value::String("/dev/ttyS1".to_owned())));
+        assert_eq!(config.hosturl(), "127.0.0.1:8000");
+    }
+
+    // Restart-only keys which are still changed aren't reported again
+    write_config(&dir, &contents.replace("timeout = 5", "timeout = 6"));
+    let change = watcher.reload().unwrap().unwrap();
+    assert_eq!(change.changed, vec!["timeout".to_owned()]);
+    assert_eq!(change.config.hosturl(), "127.0.0.1:8000");
+}
+
+#[test]
+fn reload_invalid_keeps_config() {
+    let dir = TempDir::new().unwrap();
+    let path = write_config(&dir, CONFIG);
+
+    let watcher = ConfigWatcher::new_from_path("test-service", path);
+
+    write_config(&dir, "[test-service\ntimeout = 10");
+    assert!(watcher.reload().is_err());
+    assert_eq!(watcher.config().get("timeout"), Some(Value::Integer(2)));
+}
+
+#[test]
+fn watch_file_changes() {
+    let dir = TempDir::new().unwrap();
+    let path = write_config(&dir, CONFIG);
+
+    let watcher =
+        ConfigWatcher::new_from_path("test-service", path).interval(Duration::from_millis(10));
+    let updates = watcher.subscribe();
+    watcher.start();
+
+    write_config(&dir, &CONFIG.replace("timeout = 2", "timeout = 10"));
+
+    let change = updates.recv_timeout(Duration::from_secs(2)).unwrap();
+    assert_eq!(change.changed, vec!["timeout".to_owned()]);
+}
+
+#[test]
+fn watch_invalid_file() {
+    let dir = TempDir::new().unwrap();
+    let path = write_config(&dir, CONFIG);
+
+    let watcher =
+        ConfigWatcher::new_from_path("test-service", path).interval(Duration::from_millis(10));
+    let errors = watcher.errors();
+    watcher.start();
+
+    write_config(&dir, "[test-service\ntimeout = 10");
+
+    match errors.recv_timeout(Duration::from_secs(2)).unwrap() {
+        ConfigError::Parse(_) => {}
+        other => panic!("Unexpected error: {:?}", other),
+    }
+    assert_eq!(watcher.config().get("timeout"), Some(Value::Integer(2)));
+}
+
+#[test]
+fn watch_stops_without_subscribers() {
+    let dir = TempDir::new().unwrap();
+    let path = write_config(&dir, CONFIG);
+
+    let watcher =
+        ConfigWatcher::new_from_path("test-service", path).interval(Duration::from_millis(10));
+    let updates = watcher.subscribe();
+    watcher.start();
+
+    write_config(&dir, &CONFIG.replace("timeout = 2", "timeout = 10"));
+    updates.recv_timeout(Duration::from_secs(2)).unwrap();
+
+    // The next change finds that the only subscriber is gone, and stops the watcher
+    drop(updates);
+    write_config(&dir, &CONFIG.replace("timeout = 2", "timeout = 20"));
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(watcher.config().get("timeout"), Some(Value::Integer(20)));
+
+    write_config(&dir, &CONFIG.replace("timeout = 2", "timeout = 30"));
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(watcher.config().get("timeout"), Some(Value::Integer(20)));
+}
@@ -13,6 +13,29 @@ import socket
 import json
 
 
+def parse_request(data):
+    """
+    Split a request into its query, variables, operation name and ID.
+
+    Requests may either be a plain GraphQL query string, or a JSON object
+    containing the `query` along with optional `variables`, `operationName`
+    and `id` fields. Anything which isn't a valid JSON object with a `query`
+    is treated as a plain query string.
+    """
+    try:
+        request = json.loads(data)
+    except ValueError:
+        return data, None, None, None
+
+    if not isinstance(request, dict) or "query" not in request:
+        return data, None, None, None
+
+    return (request["query"],
+            request.get("variables"),
+            request.get("operationName"),
+            request.get("id"))
+
+
 def start(config, schema, context={}):
     print "{} starting on {}:{}".format(config.name, config.ip, config.port)
     sock = socket.socket(socket.AF_INET,  # Internet
@@ -22,11 +45,15 @@ def start(config, schema, context={}):
 
     while True:
         try:
-            data, source = sock.recvfrom(1024)
+            data, source = sock.recvfrom(65507)
+            query, variables, operation_name, request_id = parse_request(data)
             errs = None
             msg = None
             try:
-                result = base_schema.execute(data, context_value=context)
+                result = base_schema.execute(query,
+                                             context_value=context,
+                                             variable_values=variables,
+                                             operation_name=operation_name)
                 msg = result.data
                 if result.errors:
                     errs = []
@@ -36,10 +63,15 @@ def start(config, schema, context={}):
             except Exception as e:
                 errs = "Exception encountered {}".format(e)
 
-            result = json.dumps({
+            response = {
                 "msg": msg,
                 "errs": errs
-            })
+            }
+            # Echo the request's ID, so the client can match up the response
+            if request_id is not None:
+                response["id"] = request_id
+
+            result = json.dumps(response)
             sock.sendto(result, source)
         except Exception as e:
             print "Exception encountered {}".format(e)
@@ -0,0 +1,237 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use blake2_rfc::blake2s::Blake2s;
+use logs;
+use registry::AppRegistryEntry;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Size (in bytes) of the BLAKE2s hash of an app binary.
+/// This matches the hash used by the file transfer service.
+const HASH_SIZE: usize = 16;
+
+/// Get the BLAKE2s hash of a file, as a hex string
+pub fn file_hash(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Blake2s::new(HASH_SIZE);
+    let mut buf = vec![0; 4096];
+
+    loop {
+        let size = file.read(&mut buf)?;
+        if size == 0 {
+            break;
+        }
+        hasher.update(&buf[0..size]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .as_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// The kinds of problems which can be found in the app registry
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProblemKind {
+    /// Files left behind by a registration which didn't finish
+    Incomplete,
+    /// A version directory without an `app.toml` file
+    MissingMetadata,
+    /// An `app.toml` file which couldn't be read or parsed
+    CorruptMetadata,
+    /// A registered app whose binary is missing
+    MissingBinary,
+    /// A registered app whose binary doesn't match the hash saved when it was registered
+    HashMismatch,
+    /// An app with more than one active version
+    MultipleActive,
+    /// An `active` link which doesn't point to an installed version
+    OrphanedLink,
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ProblemKind::Incomplete => "incomplete",
+            ProblemKind::MissingMetadata => "missing-metadata",
+            ProblemKind::CorruptMetadata => "corrupt-metadata",
+            ProblemKind::MissingBinary => "missing-binary",
+            ProblemKind::HashMismatch => "hash-mismatch",
+            ProblemKind::MultipleActive => "multiple-active",
+            ProblemKind::OrphanedLink => "orphaned-link",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A problem found in the app registry
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntegrityProblem {
+    /// The file or directory with the problem
+    pub path: String,
+    /// The UUID of the affected app
+    pub uuid: String,
+    /// What kind of problem was found
+    pub kind: ProblemKind,
+    /// A description of the problem
+    pub description: String,
+}
+
+/// Check the app registry directory for corrupt or orphaned entries.
+/// Returns the problems found, sorted by path.
+///
+/// # Arguments
+///
+/// * `apps_dir` - The root directory of the app registry
+pub fn check(apps_dir: &str) -> Vec<IntegrityProblem> {
+    let root = Path::new(apps_dir);
+    let mut problems = vec![];
+
+    for app_dir in subdirs(root) {
+        let uuid = file_name(&app_dir);
+        if uuid == "active" {
+            continue;
+        }
+
+        let mut active = vec![];
+        for version_dir in subdirs(&app_dir) {
+            let name = file_name(&version_dir);
+            if name == logs::LOGS_DIR {
+                continue;
+            }
+
+            let mut problem = |kind, description: String| {
+                problems.push(IntegrityProblem {
+                    path: version_dir.to_string_lossy().to_string(),
+                    uuid: uuid.clone(),
+                    kind,
+                    description,
+                })
+            };
+
+            if name.starts_with('.') {
+                problem(
+                    ProblemKind::Incomplete,
+                    "Left behind by an interrupted registration".to_owned(),
+                );
+                continue;
+            }
+
+            if !version_dir.join("app.toml").exists() {
+                problem(
+                    ProblemKind::MissingMetadata,
+                    "No app.toml file found".to_owned(),
+                );
+                continue;
+            }
+
+            let entry = match AppRegistryEntry::load(&version_dir) {
+                Ok(entry) => entry,
+                Err(err) => {
+                    problem(ProblemKind::CorruptMetadata, err);
+                    continue;
+                }
+            };
+
+            if entry.active_version {
+                active.push(entry.app.metadata.version.clone());
+            }
+
+            let binary = Path::new(&entry.app.path);
+            if !binary.is_file() {
+                problem(
+                    ProblemKind::MissingBinary,
+                    format!("{} does not exist", entry.app.path),
+                );
+                continue;
+            }
+
+            if let Some(ref expected) = entry.app.hash {
+                match file_hash(binary) {
+                    Ok(ref actual) if actual == expected => {}
+                    Ok(actual) => problem(
+                        ProblemKind::HashMismatch,
+                        format!(
+                            "{} has hash {}, expected {}",
+                            entry.app.path, actual, expected
+                        ),
+                    ),
+                    Err(err) => problem(
+                        ProblemKind::MissingBinary,
+                        format!("Couldn't read {}: {}", entry.app.path, err),
+                    ),
+                }
+            }
+        }
+
+        if active.len() > 1 {
+            active.sort();
+            problems.push(IntegrityProblem {
+                path: app_dir.to_string_lossy().to_string(),
+                uuid: uuid.clone(),
+                kind: ProblemKind::MultipleActive,
+                description: format!("Versions {} are all active", active.join(", ")),
+            });
+        }
+    }
+
+    if let Ok(links) = fs::read_dir(root.join("active")) {
+        for link in links.filter_map(|link| link.ok()) {
+            let uuid = link.file_name().to_string_lossy().to_string();
+            // Skip links which are still being moved into place
+            if uuid.starts_with('.') {
+                continue;
+            }
+
+            // Following the link fails if the version it points to is gone
+            if fs::metadata(link.path()).is_err() {
+                let target = fs::read_link(link.path())
+                    .map(|target| target.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                problems.push(IntegrityProblem {
+                    path: link.path().to_string_lossy().to_string(),
+                    uuid,
+                    kind: ProblemKind::OrphanedLink,
+                    description: format!("Points to missing version {}", target),
+                });
+            }
+        }
+    }
+
+    problems.sort_by(|a, b| a.path.cmp(&b.path));
+    problems
+}
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+            .map(|entry| entry.path())
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
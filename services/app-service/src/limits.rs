@@ -0,0 +1,149 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use libc;
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::ptr;
+
+/// Size of the buffer used to look up users
+const PASSWD_BUF_SIZE: usize = 4096;
+
+/// The resources an application is allowed to use, as declared in its manifest
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ResourceLimits {
+    /// The maximum size of the application's virtual memory, in bytes
+    pub memory: Option<u64>,
+    /// The CPU scheduling priority ("nice" level) of the application, from -20 to 19
+    pub nice: Option<i32>,
+    /// The maximum number of files the application may have open at once
+    pub open_files: Option<u64>,
+    /// The user (name or numeric ID) to run the application as
+    pub user: Option<String>,
+    /// The number of seconds the application may run for before it is killed
+    pub timeout: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Look up everything needed to apply the limits to a new process.
+    ///
+    /// This must be done before the process is forked, since only a few
+    /// system calls are safe to make between forking and executing the app.
+    pub fn prepare(&self) -> io::Result<PreparedLimits> {
+        let ids = match self.user {
+            Some(ref user) => Some(lookup_user(user)?),
+            None => None,
+        };
+
+        Ok(PreparedLimits {
+            memory: self.memory,
+            nice: self.nice,
+            open_files: self.open_files,
+            ids,
+        })
+    }
+}
+
+/// Resource limits which are ready to be applied to a new process
+#[derive(Clone, Copy, Debug)]
+pub struct PreparedLimits {
+    memory: Option<u64>,
+    nice: Option<i32>,
+    open_files: Option<u64>,
+    ids: Option<(libc::uid_t, libc::gid_t)>,
+}
+
+impl PreparedLimits {
+    /// Apply the limits to the current process. Called in the new process,
+    /// after it has been forked and before the app is executed.
+    pub fn apply(&self) -> io::Result<()> {
+        if let Some(memory) = self.memory {
+            set_limit(libc::RLIMIT_AS as _, memory)?;
+        }
+
+        if let Some(open_files) = self.open_files {
+            set_limit(libc::RLIMIT_NOFILE as _, open_files)?;
+        }
+
+        if let Some(nice) = self.nice {
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        // Drop privileges last, since the other changes may need them
+        if let Some((uid, gid)) = self.ids {
+            unsafe {
+                if libc::setgroups(0, ptr::null()) != 0
+                    || libc::setgid(gid) != 0
+                    || libc::setuid(uid) != 0
+                {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The type of the resource argument differs between C libraries (and `libc` versions),
+// so it's converted to whichever one `setrlimit` expects
+fn set_limit(resource: libc::c_int, value: u64) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: value as libc::rlim_t,
+        rlim_max: value as libc::rlim_t,
+    };
+
+    match unsafe { libc::setrlimit(resource as _, &limit) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+// Get the user and group IDs of a user, given their name or user ID
+fn lookup_user(user: &str) -> io::Result<(libc::uid_t, libc::gid_t)> {
+    let mut passwd: libc::passwd = unsafe { mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; PASSWD_BUF_SIZE];
+    let mut result = ptr::null_mut();
+
+    let ret = match user.parse::<libc::uid_t>() {
+        Ok(uid) => unsafe {
+            libc::getpwuid_r(uid, &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result)
+        },
+        Err(_) => {
+            let name = CString::new(user)?;
+            unsafe {
+                libc::getpwnam_r(
+                    name.as_ptr(),
+                    &mut passwd,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut result,
+                )
+            }
+        }
+    };
+
+    if ret != 0 || result.is_null() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Unknown user: {}", user),
+        ));
+    }
+
+    Ok((passwd.pw_uid, passwd.pw_gid))
+}
@@ -0,0 +1,167 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// The size a log file may grow to before it is rotated
+pub const MAX_LOG_SIZE: u64 = 256 * 1024;
+/// The number of rotated log files kept for each stream, in addition to the current one
+const MAX_ROTATED_LOGS: usize = 2;
+/// The output streams of an app which are captured
+pub const LOG_STREAMS: &[&str] = &["stdout", "stderr"];
+/// The name of the directory holding an app's logs, under its directory in the app registry
+pub const LOGS_DIR: &str = "logs";
+/// The most lines `tail` returns
+pub const MAX_TAIL_LINES: usize = 1000;
+
+/// Get the directory holding the logs of an app
+///
+/// # Arguments
+///
+/// * `apps_dir` - The root directory of the app registry
+/// * `uuid` - The UUID of the app
+pub fn log_dir(apps_dir: &str, uuid: &str) -> PathBuf {
+    Path::new(apps_dir).join(uuid).join(LOGS_DIR)
+}
+
+// The path of the current log file of a stream, or one of its rotated files
+fn log_path(dir: &Path, stream: &str, index: usize) -> PathBuf {
+    match index {
+        0 => dir.join(format!("{}.log", stream)),
+        _ => dir.join(format!("{}.log.{}", stream, index)),
+    }
+}
+
+/// Appends lines to a log file, rotating it once it grows too large.
+///
+/// The current file is `<stream>.log`. When it's rotated it becomes `<stream>.log.1`,
+/// the previous `<stream>.log.1` becomes `<stream>.log.2`, and so on.
+pub struct LogWriter {
+    dir: PathBuf,
+    stream: String,
+    max_size: u64,
+    file: File,
+    size: u64,
+}
+
+impl LogWriter {
+    /// Open the log file of an app's output stream, creating it if necessary
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The app's log directory
+    /// * `stream` - The name of the output stream
+    /// * `max_size` - The size the file may grow to before it is rotated
+    pub fn open(dir: &Path, stream: &str, max_size: u64) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let file = open_append(&log_path(dir, stream, 0))?;
+        let size = file.metadata()?.len();
+
+        Ok(LogWriter {
+            dir: dir.to_owned(),
+            stream: stream.to_owned(),
+            max_size,
+            file,
+            size,
+        })
+    }
+
+    /// Write a line to the log, rotating the file first if the line wouldn't fit
+    pub fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for index in (0..MAX_ROTATED_LOGS).rev() {
+            let from = log_path(&self.dir, &self.stream, index);
+            if from.exists() {
+                fs::rename(from, log_path(&self.dir, &self.stream, index + 1))?;
+            }
+        }
+
+        self.file = open_append(&log_path(&self.dir, &self.stream, 0))?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Copy everything read from an app's output stream into its log, until the stream closes
+pub fn capture<R: Read + Send + 'static>(reader: R, mut log: LogWriter) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut line = vec![];
+
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+
+            if let Err(err) = log.write_line(&line) {
+                eprintln!("Failed to write to {} log: {}", log.stream, err);
+                break;
+            }
+        }
+    });
+}
+
+/// Read the last lines of an app's log, including its rotated files
+///
+/// # Arguments
+///
+/// * `dir` - The app's log directory
+/// * `stream` - The name of the output stream
+/// * `count` - The maximum number of lines to return, up to `MAX_TAIL_LINES`
+pub fn tail(dir: &Path, stream: &str, count: usize) -> io::Result<Vec<String>> {
+    let count = count.min(MAX_TAIL_LINES);
+    let mut lines = VecDeque::with_capacity(count);
+
+    // Read from the oldest file to the newest, keeping only the last lines
+    for index in (0..MAX_ROTATED_LOGS + 1).rev() {
+        let path = log_path(dir, stream, index);
+        if !path.exists() {
+            continue;
+        }
+
+        let mut contents = vec![];
+        File::open(path)?.read_to_end(&mut contents)?;
+
+        for line in String::from_utf8_lossy(&contents).lines() {
+            if lines.len() == count {
+                lines.pop_front();
+            }
+            if count > 0 {
+                lines.push_back(line.to_owned());
+            }
+        }
+    }
+
+    Ok(lines.into_iter().collect())
+}
@@ -15,11 +15,14 @@
  */
 #![deny(warnings)]
 
+extern crate blake2_rfc;
 extern crate getopts;
 #[macro_use]
 extern crate juniper;
 extern crate kubos_app;
 extern crate kubos_service;
+extern crate kubos_system;
+extern crate libc;
 #[macro_use]
 extern crate serde_derive;
 #[cfg(test)]
@@ -30,7 +33,12 @@ extern crate tempfile;
 extern crate toml;
 extern crate uuid;
 
+mod integrity;
+mod limits;
+mod logs;
+mod monitor;
 mod registry;
+mod scheduler;
 mod schema;
 #[cfg(test)]
 mod tests;
@@ -59,13 +67,18 @@ fn main() {
         None => Config::new("app-service"),
     };
 
-    let registry = {
+    let mut registry = {
         match config.get("registry-dir") {
             Some(dir) => AppRegistry::new_from_dir(dir.as_str().unwrap()),
             None => AppRegistry::new(),
         }
     };
 
+    // Apps' required services are checked against the same config file as ours
+    if let Some(file) = matches.opt_str("c") {
+        registry.config_path = file;
+    }
+
     match matches.opt_present("b") {
         true => registry
             .run_onboot()
@@ -73,5 +86,7 @@ fn main() {
         false => {}
     }
 
+    registry.scheduler.start(registry.monitor.clone());
+
     Service::new(config, registry, schema::QueryRoot, schema::MutationRoot).start();
 }
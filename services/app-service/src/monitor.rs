@@ -0,0 +1,747 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use libc;
+use limits::ResourceLimits;
+use logs::{self, LogWriter};
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The number of exits remembered for each app
+const MAX_EXIT_HISTORY: usize = 10;
+/// The delay before the first restart of a crashing app
+const DEFAULT_BACKOFF_MS: u64 = 1000;
+/// The longest delay between restarts of a crashing app
+const MAX_BACKOFF_SECS: u64 = 300;
+/// Apps which run for at least this long are considered healthy, resetting their backoff
+const HEALTHY_RUN_SECS: u64 = 60;
+/// How often to check whether a stopped app has exited
+const STOP_POLL_MS: u64 = 10;
+/// How long `stop` waits for an app to exit before returning. The rest of the grace
+/// period is waited out in the background, so the service isn't held up
+const STOP_WAIT_MS: u64 = 1000;
+/// Messages sent to an app must be shorter than this, so each one is written to its stdin in one piece
+const MAX_MESSAGE_LEN: usize = libc::PIPE_BUF;
+
+/// The signals which may be used to stop an app
+const SIGNALS: &[(&str, i32)] = &[
+    ("SIGHUP", libc::SIGHUP),
+    ("SIGINT", libc::SIGINT),
+    ("SIGQUIT", libc::SIGQUIT),
+    ("SIGKILL", libc::SIGKILL),
+    ("SIGUSR1", libc::SIGUSR1),
+    ("SIGUSR2", libc::SIGUSR2),
+    ("SIGTERM", libc::SIGTERM),
+];
+
+/// Look up a signal by name (ex. "SIGTERM" or "TERM") or number
+pub fn parse_signal(name: &str) -> Result<i32, String> {
+    let name = name.trim().to_uppercase();
+
+    if let Ok(num) = name.parse::<i32>() {
+        if SIGNALS.iter().any(|&(_, signal)| signal == num) {
+            return Ok(num);
+        }
+    }
+
+    SIGNALS
+        .iter()
+        .find(|&&(sig_name, _)| sig_name == name || &sig_name[3..] == name)
+        .map(|&(_, signal)| signal)
+        .ok_or_else(|| format!("Unsupported signal: {}", name))
+}
+
+// Record a crash, and check whether the app has crashed often enough to be rolled back
+fn too_many_crashes(policy: RollbackPolicy, crashes: &mut VecDeque<Instant>) -> bool {
+    let now = Instant::now();
+    crashes.push_back(now);
+    while crashes
+        .front()
+        .map(|time| now.duration_since(*time) > Duration::from_secs(policy.window))
+        .unwrap_or(false)
+    {
+        crashes.pop_front();
+    }
+
+    crashes.len() >= policy.crashes as usize
+}
+
+// Check the process table for a live process with the given pid
+fn process_exists(pid: u32) -> bool {
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+/// What the applications service should do when an app exits
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Leave the app stopped
+    Never,
+    /// Restart the app if it exited with a non-zero code or was killed by a signal
+    OnFailure,
+    /// Always restart the app
+    Always,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy::Never
+    }
+}
+
+impl fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RestartPolicy::Never => write!(f, "never"),
+            RestartPolicy::OnFailure => write!(f, "on-failure"),
+            RestartPolicy::Always => write!(f, "always"),
+        }
+    }
+}
+
+/// When the applications service should give up on a new version of an app and
+/// reactivate the previous one
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RollbackPolicy {
+    /// The number of crashes which trigger a rollback
+    pub crashes: u32,
+    /// The window, in seconds, the crashes must happen within
+    pub window: u64,
+}
+
+/// The version of an app to fall back to if the current one keeps crashing
+#[derive(Clone, Debug)]
+pub struct Rollback {
+    /// When to roll back
+    pub policy: RollbackPolicy,
+    /// The version to roll back to
+    pub version: String,
+    /// The absolute path to the binary of the version to roll back to
+    pub path: String,
+    /// The absolute path to the config file of the version to roll back to
+    pub config: Option<String>,
+    /// The resource limits of the version to roll back to
+    pub limits: Option<ResourceLimits>,
+}
+
+/// Everything needed to start (or restart) an app process
+#[derive(Clone, Debug)]
+pub struct Launch {
+    /// The UUID of the app
+    pub uuid: String,
+    /// The absolute path to the app binary
+    pub path: String,
+    /// The run level the app was started with
+    pub run_level: String,
+    /// Any additional arguments to pass to the app
+    pub args: Vec<String>,
+    /// The directory to write the app's output to. If not set, the app's output
+    /// goes to the applications service's stdout and stderr
+    pub log_dir: Option<PathBuf>,
+    /// The absolute path to the app's config file, passed to the app in the
+    /// `KUBOS_APP_CONFIG` environment variable
+    pub config: Option<String>,
+    /// The resources the app is allowed to use
+    pub limits: Option<ResourceLimits>,
+    /// The version to fall back to if this one keeps crashing
+    pub rollback: Option<Rollback>,
+}
+
+impl Launch {
+    /// Spawn a new process for the app
+    pub fn spawn(&self) -> io::Result<Child> {
+        let mut cmd = Command::new(&self.path);
+        cmd.env("KUBOS_APP_UUID", self.uuid.clone())
+            .arg("-r")
+            .arg(&self.run_level)
+            .args(&self.args);
+
+        if let Some(ref config) = self.config {
+            cmd.env("KUBOS_APP_CONFIG", config);
+        }
+
+        if let Some(ref limits) = self.limits {
+            let limits = limits.prepare()?;
+            unsafe {
+                cmd.pre_exec(move || limits.apply());
+            }
+        }
+
+        // Messages for the app are written to its stdin
+        cmd.stdin(Stdio::piped());
+
+        let logs = match self.log_dir {
+            Some(ref dir) => match open_logs(dir) {
+                Ok(logs) => {
+                    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+                    Some(logs)
+                }
+                Err(err) => {
+                    eprintln!("Failed to open logs for app {}: {}", self.uuid, err);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let mut child = cmd.spawn()?;
+
+        if let Some((stdout_log, stderr_log)) = logs {
+            if let Some(stdout) = child.stdout.take() {
+                logs::capture(stdout, stdout_log);
+            }
+            if let Some(stderr) = child.stderr.take() {
+                logs::capture(stderr, stderr_log);
+            }
+        }
+
+        Ok(child)
+    }
+}
+
+fn open_logs(dir: &Path) -> io::Result<(LogWriter, LogWriter)> {
+    Ok((
+        LogWriter::open(dir, "stdout", logs::MAX_LOG_SIZE)?,
+        LogWriter::open(dir, "stderr", logs::MAX_LOG_SIZE)?,
+    ))
+}
+
+/// A record of one app process exiting
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExitRecord {
+    /// The process ID of the app
+    pub pid: u32,
+    /// The exit code of the app, if it exited normally
+    pub code: Option<i32>,
+    /// The signal which killed the app, if any
+    pub signal: Option<i32>,
+    /// When the app exited
+    pub time: SystemTime,
+    /// How long the app ran for
+    pub runtime: Duration,
+    /// Whether the app was restarted after exiting
+    pub restarted: bool,
+    /// Whether the app was killed for running longer than its `timeout` limit
+    pub timed_out: bool,
+}
+
+impl ExitRecord {
+    /// Whether the app exited with an error. Apps killed for running too long
+    /// didn't fail, so they aren't restarted or rolled back because of it.
+    pub fn failed(&self) -> bool {
+        self.code != Some(0) && !self.timed_out
+    }
+
+    /// The exit time in seconds since the Unix epoch
+    pub fn timestamp(&self) -> u64 {
+        self.time
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+/// The supervision state of an app
+#[derive(Clone, Debug, Default)]
+pub struct AppStatus {
+    /// The UUID of the app
+    pub uuid: String,
+    /// The process ID of the app, if it's currently running
+    pub pid: Option<u32>,
+    /// The path to the binary which was started
+    pub path: String,
+    /// The number of times the app has been restarted automatically
+    pub restarts: u32,
+    /// The most recent exits of the app, oldest first
+    pub exits: VecDeque<ExitRecord>,
+    /// The version the app was automatically rolled back to, if any
+    pub rollback: Option<String>,
+    // The pid of the process which was killed for running longer than its timeout
+    timed_out: Option<u32>,
+    // When each version (identified by the path to its binary) recently crashed. Kept here
+    // rather than by the supervisor thread, so crashes are counted across manual starts
+    crashes: HashMap<String, VecDeque<Instant>>,
+    // Incremented every time the app is started or stopped manually, so that
+    // older supervisor threads know to stop restarting their process
+    generation: u64,
+}
+
+/// Watches over running apps, reaping them when they exit and restarting them
+/// according to their restart policy.
+///
+/// Every supervised process gets a thread which waits for it to exit.
+/// Crashing apps are restarted with an exponential backoff, which resets once the
+/// app has stayed up for a minute.
+#[derive(Clone)]
+pub struct AppMonitor {
+    backoff: Duration,
+    apps: Arc<Mutex<HashMap<String, AppStatus>>>,
+    // Rollbacks which the app registry hasn't been told about yet
+    rollbacks: Arc<Mutex<Vec<(String, String)>>>,
+    // The stdin of each running app (and the pid it belongs to), used to send it messages
+    stdins: Arc<Mutex<HashMap<String, (u32, ChildStdin)>>>,
+}
+
+impl Default for AppMonitor {
+    fn default() -> Self {
+        AppMonitor::new()
+    }
+}
+
+impl AppMonitor {
+    /// Create a new monitor using the default restart backoff
+    pub fn new() -> Self {
+        Self::with_backoff(Duration::from_millis(DEFAULT_BACKOFF_MS))
+    }
+
+    /// Create a new monitor which waits `backoff` before the first restart of a crashing app
+    pub fn with_backoff(backoff: Duration) -> Self {
+        AppMonitor {
+            backoff,
+            apps: Arc::new(Mutex::new(HashMap::new())),
+            rollbacks: Arc::new(Mutex::new(vec![])),
+            stdins: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Start an app and supervise it. Returns the pid of the new process.
+    ///
+    /// # Arguments
+    ///
+    /// * `launch` - How to start the app
+    /// * `policy` - What to do when the app exits
+    pub fn start(&self, launch: Launch, policy: RestartPolicy) -> io::Result<u32> {
+        let mut child = launch.spawn()?;
+        let pid = child.id();
+        self.attach_stdin(&launch.uuid, &mut child);
+
+        let generation = {
+            let mut apps = self.lock();
+            let status = apps
+                .entry(launch.uuid.clone())
+                .or_insert_with(|| AppStatus {
+                    uuid: launch.uuid.clone(),
+                    ..Default::default()
+                });
+            status.pid = Some(pid);
+            status.path = launch.path.clone();
+            status.generation += 1;
+            status.generation
+        };
+
+        let monitor = self.clone();
+        thread::spawn(move || monitor.supervise(launch, child, policy, generation));
+
+        Ok(pid)
+    }
+
+    /// Stop a running app. Any pending automatic restart of the app is cancelled.
+    ///
+    /// The app is sent `signal` and given `grace` to exit before it is killed with SIGKILL.
+    /// This waits up to a second for the app to exit; after that, the app is left to exit
+    /// (or be killed) in the background.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid` - The UUID of the app to stop
+    /// * `signal` - The signal to send to the app first
+    /// * `grace` - How long to wait for the app to exit before killing it
+    pub fn stop(&self, uuid: &str, signal: i32, grace: Duration) -> Result<(), String> {
+        let pid = {
+            let mut apps = self.lock();
+            let status = match apps.get_mut(uuid) {
+                Some(status) => status,
+                None => return Err(format!("App {} is not running", uuid)),
+            };
+
+            let pid = match status.pid {
+                Some(pid) if process_exists(pid) => pid,
+                _ => return Err(format!("App {} is not running", uuid)),
+            };
+            status.generation += 1;
+            pid
+        };
+
+        if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+            return Err(format!("Failed to signal app {} (pid {})", uuid, pid));
+        }
+
+        let monitor = self.clone();
+        let app = uuid.to_owned();
+        thread::spawn(move || {
+            if !monitor.wait_exit(&app, pid, grace) {
+                eprintln!("App {} (pid {}) didn't stop in time. Killing it", app, pid);
+                unsafe {
+                    libc::kill(pid as libc::pid_t, libc::SIGKILL);
+                }
+            }
+        });
+
+        self.wait_exit(uuid, pid, Duration::from_millis(STOP_WAIT_MS));
+        Ok(())
+    }
+
+    /// Send a message to a running app. The message is written to the app's stdin as one line.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid` - The UUID of the app
+    /// * `message` - The message to send
+    pub fn send(&self, uuid: &str, message: &str) -> Result<(), String> {
+        if message.contains('\n') {
+            return Err("Messages can't contain newlines".to_owned());
+        }
+        // Writes up to this size can't be interleaved with others or partially written
+        if message.len() >= MAX_MESSAGE_LEN {
+            return Err(format!(
+                "Messages must be shorter than {} bytes",
+                MAX_MESSAGE_LEN
+            ));
+        }
+
+        let mut stdins = self.stdins.lock().unwrap_or_else(|err| err.into_inner());
+        let stdin = match stdins.get_mut(uuid) {
+            Some(&mut (pid, ref mut stdin)) if process_exists(pid) => stdin,
+            _ => return Err(format!("App {} is not running", uuid)),
+        };
+
+        match stdin.write_all(format!("{}\n", message).as_bytes()) {
+            Ok(()) => Ok(()),
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                Err(format!("App {} isn't accepting messages", uuid))
+            }
+            Err(err) => Err(format!("Failed to send message to app {}: {}", uuid, err)),
+        }
+    }
+
+    /// Get the pid of an app if the given binary is currently running
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid` - The UUID of the app
+    /// * `path` - The path to the binary of a particular version of the app
+    pub fn running_pid(&self, uuid: &str, path: &str) -> Option<u32> {
+        self.lock()
+            .get(uuid)
+            .filter(|status| status.path == path)
+            .and_then(|status| status.pid)
+            .filter(|pid| process_exists(*pid))
+    }
+
+    /// Get the supervision state of an app
+    pub fn status(&self, uuid: &str) -> Option<AppStatus> {
+        self.lock().get(uuid).cloned()
+    }
+
+    /// Get the supervision state of all apps which have been started, sorted by UUID
+    pub fn statuses(&self) -> Vec<AppStatus> {
+        let mut statuses: Vec<AppStatus> = self.lock().values().cloned().collect();
+        statuses.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+        statuses
+    }
+
+    /// Get the apps which have been rolled back since the last call, along with
+    /// the version each was rolled back to
+    pub fn take_rollbacks(&self) -> Vec<(String, String)> {
+        let mut rollbacks = self.rollbacks.lock().unwrap_or_else(|err| err.into_inner());
+        rollbacks.drain(..).collect()
+    }
+
+    fn supervise(
+        &self,
+        mut launch: Launch,
+        mut child: Child,
+        policy: RestartPolicy,
+        generation: u64,
+    ) {
+        let mut quick_exits = 0;
+
+        loop {
+            let pid = child.id();
+            let started = Instant::now();
+            if let Some(timeout) = launch.limits.as_ref().and_then(|limits| limits.timeout) {
+                self.watch_timeout(&launch.uuid, pid, Duration::from_secs(timeout));
+            }
+            let (code, signal) = match child.wait() {
+                Ok(status) => (status.code(), status.signal()),
+                Err(_) => (None, None),
+            };
+            let runtime = started.elapsed();
+            let timed_out = self.take_timed_out(&launch.uuid, pid);
+
+            let mut record = ExitRecord {
+                pid,
+                code,
+                signal,
+                time: SystemTime::now(),
+                runtime,
+                restarted: false,
+                timed_out,
+            };
+
+            let current = self.is_current(&launch.uuid, generation);
+            let mut rolled_back = false;
+
+            if current && record.failed() && self.record_crash(&launch) {
+                match self.roll_back(&mut launch) {
+                    Ok(()) => {
+                        rolled_back = true;
+                        quick_exits = 0;
+                    }
+                    Err(err) => eprintln!("Failed to roll back app {}: {}", launch.uuid, err),
+                }
+            }
+
+            record.restarted = current
+                && (rolled_back
+                    || match policy {
+                        RestartPolicy::Never => false,
+                        RestartPolicy::OnFailure => record.failed(),
+                        RestartPolicy::Always => true,
+                    });
+
+            self.record_exit(&launch.uuid, record.clone());
+            if !record.restarted {
+                return;
+            }
+
+            if runtime >= Duration::from_secs(HEALTHY_RUN_SECS) {
+                quick_exits = 0;
+            }
+            thread::sleep(self.delay(quick_exits));
+            quick_exits += 1;
+
+            // The app may have been started manually while we were waiting
+            if !self.is_current(&launch.uuid, generation) {
+                return;
+            }
+
+            child = match launch.spawn() {
+                Ok(child) => child,
+                Err(err) => {
+                    eprintln!("Failed to restart app {}: {}", launch.uuid, err);
+                    // Fall back to the previous version, if allowed
+                    if launch.rollback.is_none() {
+                        return;
+                    }
+                    if let Err(err) = self.roll_back(&mut launch) {
+                        eprintln!("Failed to roll back app {}: {}", launch.uuid, err);
+                        return;
+                    }
+                    match launch.spawn() {
+                        Ok(child) => child,
+                        Err(err) => {
+                            eprintln!("Failed to restart app {}: {}", launch.uuid, err);
+                            return;
+                        }
+                    }
+                }
+            };
+
+            self.attach_stdin(&launch.uuid, &mut child);
+            if let Some(status) = self.lock().get_mut(&launch.uuid) {
+                status.pid = Some(child.id());
+                status.path = launch.path.clone();
+                status.restarts += 1;
+            }
+        }
+    }
+
+    // Record a crash of the app's current version, and check whether it has crashed
+    // often enough to be rolled back
+    fn record_crash(&self, launch: &Launch) -> bool {
+        let policy = match launch.rollback {
+            Some(ref rollback) => rollback.policy,
+            None => return false,
+        };
+
+        let mut apps = self.lock();
+        let status = match apps.get_mut(&launch.uuid) {
+            Some(status) => status,
+            None => return false,
+        };
+        let crashes = status
+            .crashes
+            .entry(launch.path.clone())
+            .or_insert_with(VecDeque::new);
+
+        if too_many_crashes(policy, crashes) {
+            crashes.clear();
+            true
+        } else {
+            false
+        }
+    }
+
+    // Switch to the previous version of the app, and use it for any future restarts.
+    // The registry is changed to make it the active version the next time it's used
+    // (see `take_rollbacks`), so that its files are only changed from one thread.
+    fn roll_back(&self, launch: &mut Launch) -> Result<(), String> {
+        let rollback = match launch.rollback.clone() {
+            Some(rollback) => rollback,
+            None => return Err("No previous version to roll back to".to_owned()),
+        };
+
+        eprintln!(
+            "Rolled back app {} to version {}",
+            launch.uuid, rollback.version
+        );
+
+        // Only roll back once, so that two broken versions can't keep swapping
+        launch.path = rollback.path;
+        launch.config = rollback.config;
+        launch.limits = rollback.limits;
+        launch.rollback = None;
+
+        self.record_rollback(&launch.uuid, &rollback.version);
+
+        Ok(())
+    }
+
+    /// Record that an app was automatically rolled back to a previous version
+    pub fn record_rollback(&self, uuid: &str, version: &str) {
+        self.lock()
+            .entry(uuid.to_owned())
+            .or_insert_with(|| AppStatus {
+                uuid: uuid.to_owned(),
+                ..Default::default()
+            })
+            .rollback = Some(version.to_owned());
+
+        self.rollbacks
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push((uuid.to_owned(), version.to_owned()));
+    }
+
+    // Keep the stdin of a new app process, so that messages can be sent to it.
+    // The pipe is made non-blocking, so an app which doesn't read its messages
+    // can't hold up the service.
+    fn attach_stdin(&self, uuid: &str, child: &mut Child) {
+        let stdin = match child.stdin.take() {
+            Some(stdin) => stdin,
+            None => return,
+        };
+
+        let fd = stdin.as_raw_fd();
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+
+        self.stdins
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(uuid.to_owned(), (child.id(), stdin));
+    }
+
+    // Kill the app process if it's still running once its time is up
+    fn watch_timeout(&self, uuid: &str, pid: u32, timeout: Duration) {
+        let monitor = self.clone();
+        let uuid = uuid.to_owned();
+
+        thread::spawn(move || {
+            thread::sleep(timeout);
+
+            let mut apps = monitor.lock();
+            let status = match apps.get_mut(&uuid) {
+                Some(status) if status.pid == Some(pid) => status,
+                _ => return,
+            };
+
+            eprintln!("App {} (pid {}) timed out. Killing it", uuid, pid);
+            status.timed_out = Some(pid);
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGKILL);
+            }
+        });
+    }
+
+    // Check whether the process was killed by `watch_timeout`
+    fn take_timed_out(&self, uuid: &str, pid: u32) -> bool {
+        match self.lock().get_mut(uuid) {
+            Some(status) if status.timed_out == Some(pid) => {
+                status.timed_out = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Double the delay for every consecutive quick exit, up to the maximum
+    fn delay(&self, quick_exits: u32) -> Duration {
+        let factor = 1u32 << cmp::min(quick_exits, 16);
+        cmp::min(self.backoff * factor, Duration::from_secs(MAX_BACKOFF_SECS))
+    }
+
+    // Wait for the supervisor to reap the process
+    fn wait_exit(&self, uuid: &str, pid: u32, timeout: Duration) -> bool {
+        let start = Instant::now();
+        loop {
+            let running = self
+                .lock()
+                .get(uuid)
+                .map(|status| status.pid == Some(pid))
+                .unwrap_or(false);
+            if !running {
+                return true;
+            }
+            if start.elapsed() >= timeout {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(STOP_POLL_MS));
+        }
+    }
+
+    fn is_current(&self, uuid: &str, generation: u64) -> bool {
+        self.lock()
+            .get(uuid)
+            .map(|status| status.generation == generation)
+            .unwrap_or(false)
+    }
+
+    fn record_exit(&self, uuid: &str, record: ExitRecord) {
+        {
+            let mut stdins = self.stdins.lock().unwrap_or_else(|err| err.into_inner());
+            if stdins.get(uuid).map(|&(pid, _)| pid) == Some(record.pid) {
+                stdins.remove(uuid);
+            }
+        }
+
+        let mut apps = self.lock();
+        if let Some(status) = apps.get_mut(uuid) {
+            if status.pid == Some(record.pid) {
+                status.pid = None;
+            }
+            status.exits.push_back(record);
+            while status.exits.len() > MAX_EXIT_HISTORY {
+                status.exits.pop_front();
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<HashMap<String, AppStatus>> {
+        self.apps.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
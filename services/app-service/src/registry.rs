@@ -13,13 +13,18 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+use integrity::{self, IntegrityProblem};
 use kubos_app::RunLevel;
+use kubos_system::{self, Config};
+use limits::ResourceLimits;
+use logs;
+use monitor::{AppMonitor, Launch, RestartPolicy, Rollback, RollbackPolicy};
+use scheduler::{MissedRunPolicy, ScheduleEntry, Scheduler};
 use std::cell::RefCell;
 use std::fs;
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 use std::os::unix;
-use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::path::{Component, Path, PathBuf};
 
 use toml;
 use uuid::Uuid;
@@ -36,6 +41,28 @@ pub struct AppMetadata {
     pub version: String,
     /// The author of the application
     pub author: String,
+    /// What the applications service should do when the application exits
+    #[serde(default)]
+    pub restart_policy: RestartPolicy,
+    /// The name of the application's executable. Only needed if the application
+    /// directory holds more than one file
+    #[serde(default)]
+    pub executable: Option<String>,
+    /// The application's config file, relative to the application directory
+    #[serde(default)]
+    pub config: Option<String>,
+    /// The services the application needs. Each must be present in the system config file
+    #[serde(default)]
+    pub requires: Vec<String>,
+    /// Additional arguments to pass to the application when it is started on boot
+    #[serde(default)]
+    pub boot_args: Vec<String>,
+    /// When to automatically roll back to the previous version of the application
+    #[serde(default)]
+    pub rollback: Option<RollbackPolicy>,
+    /// The resources the application is allowed to use
+    #[serde(default)]
+    pub limits: Option<ResourceLimits>,
 }
 
 /// Kubos App struct
@@ -47,56 +74,513 @@ pub struct App {
     pub pid: u32,
     /// The absolute path to the application binary
     pub path: String,
+    /// The BLAKE2s hash of the application binary, taken when it was registered
+    #[serde(default)]
+    pub hash: Option<String>,
     /// The associated metadata of the application
     pub metadata: AppMetadata,
 }
 
+impl App {
+    /// Get the absolute path to the application's config file, if it has one
+    pub fn config_path(&self) -> Option<String> {
+        let config = self.metadata.config.as_ref()?;
+        Path::new(&self.path)
+            .parent()
+            .map(|dir| dir.join(config).to_string_lossy().to_string())
+    }
+}
+
 /// AppRegistryEntry
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct AppRegistryEntry {
     /// Whether or not this application is the active installation
     pub active_version: bool,
+    /// The version which was active before this one was activated, if any
+    #[serde(default)]
+    pub previous_version: Option<String>,
     /// The app itself
     pub app: App,
 }
 
 impl AppRegistryEntry {
+    /// Load the saved registry entry of an installed app version
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory the app version is installed in
+    pub fn load(dir: &Path) -> Result<AppRegistryEntry, String> {
+        let app_toml = dir.join("app.toml");
+
+        let mut buffer = String::new();
+        fs::File::open(&app_toml)
+            .and_then(|mut f| f.read_to_string(&mut buffer))
+            .or_else(|err| Err(format!("Failed to read {}: {}", app_toml.display(), err)))?;
+
+        toml::from_str::<AppRegistryEntry>(&buffer)
+            .or_else(|err| Err(format!("Failed to parse {}: {}", app_toml.display(), err)))
+    }
+
     fn from_dir(dir: &str) -> Option<AppRegistryEntry> {
-        let app_toml: PathBuf = [dir, "app.toml"].iter().collect();
-        if !app_toml.exists() {
+        let dir = Path::new(dir);
+        if !dir.join("app.toml").exists() {
             return None;
         }
 
-        match fs::File::open(app_toml) {
-            Ok(mut f) => {
-                let mut buffer = String::new();
-                match f.read_to_string(&mut buffer) {
-                    Ok(_) => match toml::from_str::<AppRegistryEntry>(&buffer) {
-                        Ok(entry) => Some(entry),
-                        Err(_) => None,
-                    },
-                    Err(_) => None,
-                }
+        match AppRegistryEntry::load(dir) {
+            Ok(entry) => Some(entry),
+            Err(err) => {
+                eprintln!("Skipping app: {}", err);
+                None
             }
-            Err(_) => None,
         }
     }
 
     fn save(&self) -> Result<bool, String> {
-        let mut app_toml = PathBuf::from(self.app.path.clone());
-        app_toml.set_file_name("app.toml");
-
-        match fs::File::create(app_toml) {
-            Ok(mut file) => match toml::to_string(&self) {
-                Ok(toml_str) => match file.write_all(&toml_str.into_bytes()) {
-                    Ok(_) => Ok(true),
-                    Err(err) => Err(format!("{}", err)),
-                },
-                Err(err) => Err(format!("{}", err)),
-            },
-            Err(err) => Err(format!("{}", err)),
+        match Path::new(&self.app.path).parent() {
+            Some(dir) => self.save_in(dir),
+            None => Err(format!("Invalid app path {}", self.app.path)),
         }
     }
+
+    // Write the entry's app.toml by writing a new file and moving it into place,
+    // so that a power loss can't leave a partially written file behind
+    fn save_in(&self, dir: &Path) -> Result<bool, String> {
+        let app_toml = dir.join("app.toml");
+        let tmp_toml = dir.join("app.toml.tmp");
+
+        let contents = toml::to_string(&self).or_else(|err| Err(format!("{}", err)))?;
+
+        fs::File::create(&tmp_toml)
+            .and_then(|mut file| {
+                file.write_all(contents.as_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&tmp_toml, &app_toml))
+            .map(|_| true)
+            .or_else(|err| Err(format!("{}", err)))
+    }
+}
+
+// Point the app's `active` symlink at the directory of one of its versions.
+// The new link is moved into place, so the active version is never missing.
+fn set_active_symlink(apps_dir: &str, app_uuid: &str, version_dir: &Path) -> Result<(), String> {
+    let active_symlink = PathBuf::from(format!("{}/active/{}", apps_dir, app_uuid));
+    let tmp_symlink = PathBuf::from(format!("{}/active/.{}.tmp", apps_dir, app_uuid));
+
+    if fs::symlink_metadata(&tmp_symlink).is_ok() {
+        fs::remove_file(&tmp_symlink).or_else(|err| {
+            Err(format!(
+                "Couldn't remove symlink {}: {:?}",
+                tmp_symlink.display(),
+                err
+            ))
+        })?;
+    }
+
+    unix::fs::symlink(version_dir, &tmp_symlink).or_else(|err| {
+        Err(format!(
+            "Couldn't symlink {} to {}: {:?}",
+            active_symlink.display(),
+            version_dir.display(),
+            err
+        ))
+    })?;
+
+    fs::rename(&tmp_symlink, &active_symlink).or_else(|err| {
+        Err(format!(
+            "Couldn't replace symlink {}: {:?}",
+            active_symlink.display(),
+            err
+        ))
+    })
+}
+
+/// Make one of the installed versions of an app the active version, updating the
+/// saved registry entries and the `active` symlink.
+///
+/// Only the files in the registry directory are changed, so any `AppRegistry`
+/// instances need to update their own entries.
+pub fn activate_version(apps_dir: &str, app_uuid: &str, version: &str) -> Result<(), String> {
+    let app_dir = Path::new(apps_dir).join(app_uuid);
+    let version_dir = app_dir.join(version);
+    if !version_dir.join("app.toml").exists() {
+        return Err(format!(
+            "App with UUID {} and version {} does not exist",
+            app_uuid, version
+        ));
+    }
+
+    let versions = fs::read_dir(&app_dir)
+        .or_else(|err| Err(format!("Failed to read app directory: {}", err)))?;
+
+    for dir in versions.filter_map(|dir| dir.ok()) {
+        let path = dir.path();
+        if is_staged(&path) {
+            continue;
+        }
+        let mut entry = match path.to_str().and_then(AppRegistryEntry::from_dir) {
+            Some(entry) => entry,
+            None => continue,
+        };
+
+        let active = entry.app.metadata.version == version;
+        if entry.active_version != active {
+            entry.active_version = active;
+            entry.save()?;
+        }
+    }
+
+    set_active_symlink(apps_dir, app_uuid, &version_dir)
+}
+
+// Whether a directory holds a version which hasn't finished being registered
+// (or an old copy of a version which was being replaced)
+fn is_staged(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy().starts_with('.'))
+        .unwrap_or(false)
+}
+
+// Copy an app's files and its registry entry into a staging directory
+fn stage_version(
+    staging_dir: &Path,
+    files: &[fs::DirEntry],
+    entry: &AppRegistryEntry,
+) -> Result<(), String> {
+    if staging_dir.exists() {
+        fs::remove_dir_all(staging_dir).or_else(|err| {
+            Err(format!(
+                "Couldn't clear staging dir {}: {:?}",
+                staging_dir.display(),
+                err
+            ))
+        })?;
+    }
+
+    fs::create_dir_all(staging_dir).or_else(|err| {
+        Err(format!(
+            "Couldn't create app dir {}: {:?}",
+            staging_dir.display(),
+            err
+        ))
+    })?;
+
+    for file in files {
+        copy_package_file(&file.path(), &staging_dir.join(file.file_name()))
+            .or_else(|err| Err(format!("Couldn't copy app files: {:?}", err)))?;
+    }
+
+    entry.save_in(staging_dir).map(|_| ())
+}
+
+// Move a staged version into place, replacing any existing copy of the version.
+// The existing copy is moved aside first, so it can be restored if the power drops
+// before the staged version is in place.
+fn install_version(staging_dir: &Path, version_dir: &Path) -> Result<(), String> {
+    let old_dir = old_version_dir(version_dir);
+
+    if version_dir.exists() {
+        if old_dir.exists() {
+            fs::remove_dir_all(&old_dir)
+                .or_else(|err| Err(format!("Couldn't remove {}: {:?}", old_dir.display(), err)))?;
+        }
+        fs::rename(version_dir, &old_dir).or_else(|err| {
+            Err(format!(
+                "Couldn't move {}: {:?}",
+                version_dir.display(),
+                err
+            ))
+        })?;
+    }
+
+    fs::rename(staging_dir, version_dir).or_else(|err| {
+        Err(format!(
+            "Couldn't install app dir {}: {:?}",
+            version_dir.display(),
+            err
+        ))
+    })?;
+
+    if old_dir.exists() {
+        if let Err(err) = fs::remove_dir_all(&old_dir) {
+            eprintln!("Couldn't remove {}: {:?}", old_dir.display(), err);
+        }
+    }
+
+    Ok(())
+}
+
+fn staging_version_dir(version_dir: &Path) -> PathBuf {
+    hidden_sibling(version_dir, "staging")
+}
+
+fn old_version_dir(version_dir: &Path) -> PathBuf {
+    hidden_sibling(version_dir, "old")
+}
+
+fn hidden_sibling(path: &Path, suffix: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}", name, suffix))
+}
+
+// Clean up after any registrations which were interrupted. Partially staged versions
+// are removed, and versions which were being replaced are restored if needed.
+fn recover_staged(apps_dir: &Path) {
+    let app_dirs = match fs::read_dir(apps_dir) {
+        Ok(dirs) => dirs,
+        Err(_) => return,
+    };
+
+    for app_dir in app_dirs.filter_map(|dir| dir.ok()) {
+        let versions = match fs::read_dir(app_dir.path()) {
+            Ok(versions) => versions,
+            Err(_) => continue,
+        };
+
+        for version in versions.filter_map(|version| version.ok()) {
+            let path = version.path();
+            let name = version.file_name().to_string_lossy().to_string();
+            if !name.starts_with('.') || !path.is_dir() {
+                continue;
+            }
+
+            let result = if name.ends_with(".staging") {
+                eprintln!("Removing incomplete app registration {}", path.display());
+                fs::remove_dir_all(&path)
+            } else if name.ends_with(".old") {
+                let version_dir = app_dir.path().join(&name[1..name.len() - ".old".len()]);
+                if version_dir.exists() {
+                    fs::remove_dir_all(&path)
+                } else {
+                    eprintln!("Restoring {}", version_dir.display());
+                    fs::rename(&path, &version_dir)
+                }
+            } else {
+                Ok(())
+            };
+
+            if let Err(err) = result {
+                eprintln!("Couldn't recover {}: {:?}", path.display(), err);
+            }
+        }
+    }
+}
+
+// Load the saved registry entries of every installed version of an app
+fn discover_versions(app_dir: PathBuf) -> Vec<AppRegistryEntry> {
+    let mut reg_entries: Vec<AppRegistryEntry> = Vec::new();
+    if let Ok(versions) = fs::read_dir(app_dir) {
+        for version in versions {
+            if version.is_err() {
+                continue;
+            }
+
+            let version = version.unwrap();
+            // Skip versions which are still being registered
+            if is_staged(&version.path()) {
+                continue;
+            }
+
+            match version.file_type() {
+                Ok(v_file_type) => {
+                    if v_file_type.is_dir() {
+                        let v_path = version.path();
+                        let version_path = match v_path.to_str() {
+                            Some(v) => v,
+                            None => continue,
+                        };
+
+                        if let Some(entry) = AppRegistryEntry::from_dir(version_path) {
+                            reg_entries.push(entry);
+                        }
+                    }
+                }
+                Err(_) => continue,
+            }
+        }
+    }
+    reg_entries
+}
+
+// Get the path of a file in an app package, making sure it stays inside the package
+fn package_file(app_path: &Path, name: &str) -> Result<PathBuf, String> {
+    let relative = !name.is_empty()
+        && Path::new(name)
+            .components()
+            .all(|component| match component {
+                Component::Normal(_) => true,
+                _ => false,
+            });
+    if !relative {
+        return Err(format!(
+            "Invalid app file {}: must be relative to the app directory",
+            name
+        ));
+    }
+
+    Ok(app_path.join(name))
+}
+
+// Copy a file or directory from an app package into the registry
+fn copy_package_file(from: &Path, to: &Path) -> io::Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_package_file(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+// Gather everything needed to start the active version of an app
+fn build_launch(
+    apps_dir: &str,
+    entries: &[AppRegistryEntry],
+    app_uuid: &str,
+    run_level: &RunLevel,
+    args: Vec<String>,
+) -> Result<(Launch, RestartPolicy), String> {
+    let entry = match entries
+        .iter()
+        .find(|ref e| e.active_version && e.app.uuid == app_uuid)
+    {
+        Some(entry) => entry,
+        None => return Err(format!("Active app with UUID {} does not exist", app_uuid)),
+    };
+    let app = &entry.app;
+
+    let app_path = PathBuf::from(&app.path);
+    if !app_path.exists() {
+        // TODO: Unregister app if path doesn't exist
+        return Err(format!("{} does not exist", &app.path));
+    }
+
+    // Make sure the binary hasn't been corrupted since it was registered
+    if let Some(ref expected) = app.hash {
+        let actual = integrity::file_hash(&app_path)
+            .or_else(|err| Err(format!("Couldn't read {}: {}", app.path, err)))?;
+        if &actual != expected {
+            return Err(format!(
+                "{} is corrupt: hash {} does not match registered hash {}",
+                app.path, actual, expected
+            ));
+        }
+    }
+
+    let rollback = match (app.metadata.rollback, entry.previous_version.as_ref()) {
+        (Some(policy), Some(version)) => entries
+            .iter()
+            .find(|e| e.app.uuid == app_uuid && &e.app.metadata.version == version)
+            .map(|previous| Rollback {
+                policy,
+                version: version.clone(),
+                path: previous.app.path.clone(),
+                config: previous.app.config_path(),
+                limits: previous.app.metadata.limits.clone(),
+            }),
+        _ => None,
+    };
+
+    // Apps started on boot get the arguments from their manifest, unless they were given others
+    let args = if *run_level == RunLevel::OnBoot && args.is_empty() {
+        app.metadata.boot_args.clone()
+    } else {
+        args
+    };
+
+    let launch = Launch {
+        uuid: app.uuid.clone(),
+        path: app.path.clone(),
+        run_level: format!("{}", run_level),
+        args,
+        log_dir: Some(logs::log_dir(apps_dir, &app.uuid)),
+        config: app.config_path(),
+        limits: app.metadata.limits.clone(),
+        rollback,
+    };
+
+    Ok((launch, app.metadata.restart_policy))
+}
+
+// Start the active version of an app, falling back to the previous version
+// if it fails to start and the app allows it.
+//
+// This may be called from the scheduler thread, so a rollback is only recorded
+// with the monitor. The registry makes the previous version active on disk the
+// next time the main thread uses it (see `AppRegistry::apply_rollbacks`).
+fn start_active(
+    apps_dir: &str,
+    monitor: &AppMonitor,
+    entries: &[AppRegistryEntry],
+    app_uuid: &str,
+    run_level: &RunLevel,
+    args: Vec<String>,
+) -> Result<u32, String> {
+    let (launch, policy) = build_launch(apps_dir, entries, app_uuid, run_level, args.clone())?;
+
+    let err = match monitor.start(launch.clone(), policy) {
+        Ok(pid) => return Ok(pid),
+        Err(err) => err,
+    };
+
+    let rollback = match launch.rollback {
+        Some(rollback) => rollback,
+        None => return Err(format!("Failed to spawn app: {:?}", err)),
+    };
+
+    eprintln!(
+        "Failed to spawn app {}: {:?}. Rolling back to version {}",
+        app_uuid, err, rollback.version
+    );
+    monitor.record_rollback(app_uuid, &rollback.version);
+
+    let entries: Vec<AppRegistryEntry> = entries
+        .iter()
+        .cloned()
+        .map(|mut entry| {
+            if entry.app.uuid == app_uuid {
+                entry.active_version = entry.app.metadata.version == rollback.version;
+            }
+            entry
+        })
+        .collect();
+
+    let (mut launch, policy) = build_launch(apps_dir, &entries, app_uuid, run_level, args)?;
+    launch.rollback = None;
+
+    match monitor.start(launch, policy) {
+        Ok(pid) => Ok(pid),
+        Err(err) => Err(format!("Failed to spawn app: {:?}", err)),
+    }
+}
+
+/// Start the active version of an app, using the registry entries saved in the
+/// registry directory rather than an `AppRegistry`. This allows apps to be started
+/// from outside the service's main thread (ex. by the scheduler).
+///
+/// # Arguments
+///
+/// * `apps_dir` - The root directory of the app registry
+/// * `monitor` - The monitor which should supervise the app
+/// * `app_uuid` - The UUID generated for the app when it was registered
+/// * `run_level` - Which Run Level to run the app with
+/// * `args` - Any additional arguments to pass to the app
+pub fn start_saved_app(
+    apps_dir: &str,
+    monitor: &AppMonitor,
+    app_uuid: &str,
+    run_level: &RunLevel,
+    args: Vec<String>,
+) -> Result<u32, String> {
+    let entries = discover_versions(Path::new(apps_dir).join(app_uuid));
+    start_active(apps_dir, monitor, &entries, app_uuid, run_level, args)
 }
 
 /// AppRegistry
@@ -106,6 +590,15 @@ pub struct AppRegistry {
     pub entries: RefCell<Vec<AppRegistryEntry>>,
     /// The managed root directory of the AppRegistry
     pub apps_dir: String,
+    /// Supervisor for the applications started by the AppRegistry
+    #[serde(skip)]
+    pub monitor: AppMonitor,
+    /// Scheduled runs of the applications in the AppRegistry
+    #[serde(skip)]
+    pub scheduler: Scheduler,
+    /// The system config file, used to check that the services apps require are available
+    #[serde(skip)]
+    pub config_path: String,
 }
 
 impl AppRegistry {
@@ -125,6 +618,9 @@ impl AppRegistry {
         let registry = AppRegistry {
             entries: RefCell::new(Vec::new()),
             apps_dir: String::from(apps_dir),
+            monitor: AppMonitor::new(),
+            scheduler: Scheduler::load(apps_dir),
+            config_path: kubos_system::DEFAULT_PATH.to_owned(),
         };
 
         let apps_dir = Path::new(apps_dir);
@@ -135,7 +631,7 @@ impl AppRegistry {
             }
         }
 
-        let active_dir = apps_dir.clone().join("active");
+        let active_dir = apps_dir.join("active");
         if !active_dir.exists() {
             if let Err(err) = fs::create_dir_all(&active_dir) {
                 eprintln!(
@@ -147,6 +643,8 @@ impl AppRegistry {
             }
         }
 
+        recover_staged(apps_dir);
+
         registry
             .entries
             .borrow_mut()
@@ -175,7 +673,7 @@ impl AppRegistry {
                 if let Ok(entry) = entry {
                     if let Ok(file_type) = entry.file_type() {
                         if file_type.is_dir() && entry.file_name().to_str() != Some("active") {
-                            reg_entries.extend(self.discover_versions(entry.path()));
+                            reg_entries.extend(discover_versions(entry.path()));
                         }
                     }
                 }
@@ -185,36 +683,6 @@ impl AppRegistry {
         reg_entries
     }
 
-    fn discover_versions(&self, app_dir: PathBuf) -> Vec<AppRegistryEntry> {
-        let mut reg_entries: Vec<AppRegistryEntry> = Vec::new();
-        if let Ok(versions) = fs::read_dir(app_dir) {
-            for version in versions {
-                if version.is_err() {
-                    continue;
-                }
-
-                let version = version.unwrap();
-                match version.file_type() {
-                    Ok(v_file_type) => {
-                        if v_file_type.is_dir() {
-                            let v_path = version.path();
-                            let version_path = match v_path.to_str() {
-                                Some(v) => v,
-                                None => continue,
-                            };
-
-                            if let Some(entry) = AppRegistryEntry::from_dir(version_path) {
-                                reg_entries.push(entry);
-                            }
-                        }
-                    }
-                    Err(_) => continue,
-                }
-            }
-        }
-        reg_entries
-    }
-
     /// Register an application binary with the AppRegistry, extracting metadata and installing it
     /// into the proper folder structure under the AppRegistry directory.
     ///
@@ -239,124 +707,132 @@ impl AppRegistry {
             return Err(format!("{} is not a directory", path));
         }
 
+        let manifest = app_path.join("manifest.toml");
+        if !manifest.is_file() {
+            return Err("Failed to find manifest file".to_owned());
+        }
+
+        let mut data = String::new();
+        fs::File::open(&manifest)
+            .and_then(|mut fp| fp.read_to_string(&mut data))
+            .or_else(|error| return Err(format!("Failed to read manifest: {}", error)))?;
+
+        let metadata: AppMetadata = toml::from_str(&data)
+            .or_else(|error| return Err(format!("Failed to parse manifest: {}", error)))?;
+
         let files: Vec<fs::DirEntry> = match fs::read_dir(app_path) {
-            Ok(v) => v.filter_map(|file| file.ok()).collect(),
+            Ok(v) => v
+                .filter_map(|file| file.ok())
+                .filter(|file| file.file_name().to_str() != Some("manifest.toml"))
+                .collect(),
             Err(error) => return Err(format!("Failed to read directory: {}", error)),
         };
 
-        if files.len() != 2 {
-            return Err("Exactly two files should be present in the app directory".to_owned());
+        // Apps with more than one file must say which one is the executable
+        let executable = match metadata.executable {
+            Some(ref name) if name.contains('/') => {
+                return Err(format!(
+                    "Invalid app executable {}: must be a file name",
+                    name
+                ))
+            }
+            Some(ref name) => package_file(app_path, name)?,
+            None => match files.len() {
+                0 => return Err("Failed to find app file".to_owned()),
+                1 => files[0].path(),
+                _ => return Err("The manifest must name the app executable".to_owned()),
+            },
+        };
+        if !executable.is_file() {
+            return Err(format!("Failed to find app file {}", executable.display()));
         }
 
-        let mut manifest_file: Option<fs::DirEntry> = None;
-        let mut app_file: Option<fs::DirEntry> = None;
-
-        for file in files {
-            match file.file_name().to_str() {
-                Some("manifest.toml") => manifest_file = Some(file),
-                Some(_) => app_file = Some(file),
-                _ => {}
+        if let Some(ref config) = metadata.config {
+            let config = package_file(app_path, config)?;
+            if !config.is_file() {
+                return Err(format!("Failed to find config file {}", config.display()));
             }
         }
 
-        let manifest = match manifest_file {
-            Some(file) => file,
-            None => return Err("Failed to find manifest file".to_owned()),
-        };
-        let app = match app_file {
-            Some(file) => file,
-            None => return Err("Failed to find app file".to_owned()),
-        };
+        for service in metadata.requires.iter() {
+            if let Err(err) = Config::try_new_from_path(service, self.config_path.clone()) {
+                return Err(format!(
+                    "Required service {} is not available: {}",
+                    service, err
+                ));
+            }
+        }
 
-        let mut data = String::new();
-        fs::File::open(manifest.path())
-            .and_then(|mut fp| fp.read_to_string(&mut data))
-            .or_else(|error| return Err(format!("Failed to read manifest: {}", error)))?;
+        let hash = integrity::file_hash(&executable)
+            .or_else(|err| Err(format!("Couldn't hash app binary: {}", err)))?;
 
-        let metadata: AppMetadata = toml::from_str(&data)
-            .or_else(|error| return Err(format!("Failed to parse manifest: {}", error)))?;
+        self.apply_rollbacks();
 
         let mut entries = self.entries.borrow_mut();
         let mut app_uuid = Uuid::new_v4().hyphenated().to_string();
+        let mut previous_version = None;
+        let mut current = None;
         // TODO: Do the lookup based on the passed UUID
         // Also TODO: Allow a UUID to be passed...
-        for entry in entries.iter_mut() {
-            // Find the existing active version of the app and make it inactive.
+        for (index, entry) in entries.iter().enumerate() {
+            // Find the existing active version of the app.
             // Use the existing UUID for our new app
             if entry.active_version && entry.app.metadata.name == metadata.name {
-                entry.active_version = false;
                 app_uuid = entry.app.uuid.clone();
-                entry.save()?;
-                break;
-            }
-        }
-
-        let app_dir_str = format!(
-            "{}/{}/{}",
-            self.apps_dir,
-            app_uuid,
-            metadata.version.as_str()
-        );
-        let app_dir = Path::new(&app_dir_str);
-
-        if !app_dir.exists() {
-            fs::create_dir_all(app_dir).or_else(|err| {
-                return Err(format!(
-                    "Couldn't create app dir {}: {:?}",
-                    app_dir.display(),
-                    err
-                ));
-            })?;
-        }
-
-        match fs::copy(app.path(), app_dir.join(app.file_name())) {
-            Err(err) => {
-                return Err(format!("Couldn't copy app binary: {:?}", err));
-            }
-            Ok(_) => {}
-        }
-
-        let active_symlink = PathBuf::from(format!("{}/active/{}", self.apps_dir, app_uuid));
-        if active_symlink.exists() {
-            match fs::remove_file(active_symlink.clone()) {
-                Err(err) => {
-                    return Err(format!(
-                        "Couldn't remove symlink {}: {:?}",
-                        active_symlink.display(),
-                        err
-                    ));
+                if entry.app.metadata.version != metadata.version {
+                    previous_version = Some(entry.app.metadata.version.clone());
+                    current = Some(index);
                 }
-                Ok(_) => {}
+                break;
             }
         }
 
-        match unix::fs::symlink(&app_dir_str, active_symlink.clone()) {
-            Err(err) => {
-                return Err(format!(
-                    "Couldn't symlink {} to {}: {:?}",
-                    active_symlink.display(),
-                    app_dir_str,
-                    err
-                ));
-            }
-            Ok(_) => {}
-        }
+        let version = metadata.version.clone();
+        let app_dir = Path::new(&self.apps_dir).join(&app_uuid).join(&version);
 
         let reg_entry = AppRegistryEntry {
             app: App {
-                uuid: app_uuid,
+                uuid: app_uuid.clone(),
                 metadata: metadata,
                 pid: 0,
-                path: format!("{}/{}", app_dir_str, app.file_name().to_string_lossy()),
+                path: app_dir
+                    .join(executable.file_name().unwrap_or_default())
+                    .to_string_lossy()
+                    .to_string(),
+                hash: Some(hash),
             },
             active_version: true,
+            previous_version,
         };
 
-        // Add the new registry entry
-        entries.push(reg_entry);
-        // Create the app.toml file and save the metadata information
-        entries[entries.len() - 1].save()?;
-        Ok(entries[entries.len() - 1].clone())
+        // Copy everything (including the app.toml file) into a staging directory and then
+        // move it into place, so a power loss can't leave a partially installed version behind
+        let staging_dir = staging_version_dir(&app_dir);
+        if let Err(err) = stage_version(&staging_dir, &files, &reg_entry) {
+            let _ = fs::remove_dir_all(&staging_dir);
+            return Err(err);
+        }
+        install_version(&staging_dir, &app_dir)?;
+
+        // The new version is in place, so the old one can be made inactive
+        if let Some(index) = current {
+            entries[index].active_version = false;
+            entries[index].save()?;
+        }
+
+        set_active_symlink(&self.apps_dir, &app_uuid, &app_dir)?;
+
+        // Registering an installed version again replaces it
+        entries.retain(|e| !(e.app.uuid == app_uuid && e.app.metadata.version == version));
+        entries.push(reg_entry.clone());
+        Ok(reg_entry)
+    }
+
+    /// Check the app registry for corrupt or orphaned entries, such as versions whose
+    /// `app.toml` file is missing or can't be parsed, or whose binary has changed since
+    /// it was registered
+    pub fn integrity_report(&self) -> Vec<IntegrityProblem> {
+        integrity::check(&self.apps_dir)
     }
 
     /// Uninstall an application from the AppRegistry
@@ -375,6 +851,8 @@ impl AppRegistry {
     /// ```
     ///
     pub fn uninstall(&self, app_uuid: &str, version: &str) -> Result<bool, String> {
+        self.apply_rollbacks();
+
         let mut entries = self.entries.borrow_mut();
         let app_index = match entries.binary_search_by(|ref e| {
             e.app
@@ -394,7 +872,7 @@ impl AppRegistry {
                 None => return Err(String::from("Error finding parent path of app")),
             };
 
-            if let Err(err) = fs::remove_dir_all(parent.clone()) {
+            if let Err(err) = fs::remove_dir_all(parent) {
                 return Err(format!("Error removing app directory: {}", err));
             }
         }
@@ -403,11 +881,21 @@ impl AppRegistry {
             entries.remove(app_index);
         }
 
+        // Nothing is left to run once the last version of the app is gone
+        if !entries.iter().any(|e| e.app.uuid == app_uuid) {
+            if let Err(err) = self.scheduler.remove_app(app_uuid) {
+                eprintln!("Failed to remove schedule of app {}: {}", app_uuid, err);
+            }
+        }
+
         Ok(true)
     }
 
     /// Start an application. If successful, returns the pid of the application process.
     ///
+    /// The application is supervised while it runs, and is restarted according to
+    /// the restart policy in its manifest when it exits.
+    ///
     /// # Arguments
     ///
     /// * `app_uuid` - The UUID generated for the app when it was registered
@@ -426,36 +914,160 @@ impl AppRegistry {
         run_level: RunLevel,
         args: Option<Vec<String>>,
     ) -> Result<u32, String> {
-        let entries = self.entries.borrow();
+        self.apply_rollbacks();
+
+        let entries = self.entries.borrow().clone();
+        let result = start_active(
+            &self.apps_dir,
+            &self.monitor,
+            &entries,
+            app_uuid,
+            &run_level,
+            args.unwrap_or_default(),
+        );
+
+        // Pick up the new active version if the app had to be rolled back
+        self.apply_rollbacks();
+        result
+    }
+
+    /// Schedule an application to be run at a particular time, or periodically.
+    ///
+    /// # Arguments
+    ///
+    /// * `app_uuid` - The UUID generated for the app when it was registered
+    /// * `time` - When to first run the app, in seconds since the Unix epoch
+    /// * `period` - The number of seconds between runs, or `None` to only run the app once
+    /// * `run_level` - Which Run Level to run the app with
+    /// * `args` - Any additional arguments to pass to the app
+    /// * `missed` - What to do if a run is missed (ex. because the system was off)
+    pub fn schedule(
+        &self,
+        app_uuid: &str,
+        time: u64,
+        period: Option<u64>,
+        run_level: RunLevel,
+        args: Vec<String>,
+        missed: MissedRunPolicy,
+    ) -> Result<ScheduleEntry, String> {
+        if !self.entries.borrow().iter().any(|e| e.app.uuid == app_uuid) {
+            return Err(format!("App with UUID {} does not exist", app_uuid));
+        }
+
+        self.scheduler
+            .add(app_uuid, time, period, run_level, args, missed)
+    }
+
+    /// Make an installed version of an application the active version.
+    ///
+    /// The previously active version is remembered, so that the application can be
+    /// automatically rolled back to it if the new version keeps crashing.
+    ///
+    /// # Arguments
+    ///
+    /// * `app_uuid` - The UUID generated for the app when it was registered
+    /// * `version` - The version of the app to activate
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use kubos_app::registry::AppRegistry;
+    /// let registry = AppRegistry::new();
+    /// registry.set_version("01234567-89ab-cdef0-1234-56789abcdef0", "1.0");
+    /// ```
+    pub fn set_version(&self, app_uuid: &str, version: &str) -> Result<AppRegistryEntry, String> {
+        self.apply_rollbacks();
 
-        let app = match entries
-            .iter()
-            .find(|ref e| e.active_version && e.app.uuid == app_uuid)
         {
-            Some(entry) => &entry.app,
-            None => return Err(format!("Active app with UUID {} does not exist", app_uuid)),
-        };
+            let mut entries = self.entries.borrow_mut();
 
-        let app_path = PathBuf::from(&app.path);
-        if !app_path.exists() {
-            // TODO: Unregister app if path doesn't exist
-            return Err(format!("{} does not exist", &app.path));
+            let current = entries
+                .iter()
+                .find(|e| e.active_version && e.app.uuid == app_uuid)
+                .map(|e| e.app.metadata.version.clone());
+
+            let entry = match entries
+                .iter_mut()
+                .find(|e| e.app.uuid == app_uuid && e.app.metadata.version == version)
+            {
+                Some(entry) => entry,
+                None => {
+                    return Err(format!(
+                        "App with UUID {} and version {} does not exist",
+                        app_uuid, version
+                    ))
+                }
+            };
+
+            if current.is_some() && current.as_ref().map(|v| v.as_str()) != Some(version) {
+                entry.previous_version = current;
+                entry.save()?;
+            }
         }
 
-        let mut cmd = Command::new(app_path);
+        self.activate(app_uuid, version)?;
+
+        let entries = self.entries.borrow();
+        Ok(entries
+            .iter()
+            .find(|e| e.app.uuid == app_uuid && e.app.metadata.version == version)
+            .cloned()
+            .unwrap())
+    }
+
+    // Activate a version of an app on disk and in the registry
+    fn activate(&self, app_uuid: &str, version: &str) -> Result<(), String> {
+        activate_version(&self.apps_dir, app_uuid, version)?;
+        self.mark_active(app_uuid, version);
+        Ok(())
+    }
+
+    fn mark_active(&self, app_uuid: &str, version: &str) {
+        for entry in self
+            .entries
+            .borrow_mut()
+            .iter_mut()
+            .filter(|e| e.app.uuid == app_uuid)
+        {
+            entry.active_version = entry.app.metadata.version == version;
+        }
+    }
 
-        cmd.env("KUBOS_APP_UUID", app.uuid.clone())
-            .arg("-r")
-            .arg(format!("{}", run_level));
+    /// Update the registry with any automatic rollbacks which have happened since
+    /// it was last used, making the versions which were rolled back to active
+    pub fn apply_rollbacks(&self) {
+        for (app_uuid, version) in self.monitor.take_rollbacks() {
+            if let Err(err) = activate_version(&self.apps_dir, &app_uuid, &version) {
+                eprintln!(
+                    "Failed to activate version {} of app {}: {}",
+                    version, app_uuid, err
+                );
+                continue;
+            }
+            self.mark_active(&app_uuid, &version);
+        }
+    }
 
-        if let Some(add_args) = args {
-            cmd.args(&add_args);
+    /// Read the most recent output of an application.
+    ///
+    /// Output is captured from every run of the application, regardless of version.
+    ///
+    /// # Arguments
+    ///
+    /// * `app_uuid` - The UUID generated for the app when it was registered
+    /// * `stream` - Which output to read: "stdout" or "stderr"
+    /// * `lines` - The maximum number of lines to return
+    pub fn logs(&self, app_uuid: &str, stream: &str, lines: usize) -> Result<Vec<String>, String> {
+        if !self.entries.borrow().iter().any(|e| e.app.uuid == app_uuid) {
+            return Err(format!("App with UUID {} does not exist", app_uuid));
         }
 
-        match cmd.spawn() {
-            Ok(child) => Ok(child.id()),
-            Err(err) => Err(format!("Failed to spawn app: {:?}", err)),
+        if !logs::LOG_STREAMS.contains(&stream) {
+            return Err(format!("Unknown log stream: {}", stream));
         }
+
+        logs::tail(&logs::log_dir(&self.apps_dir, app_uuid), stream, lines)
+            .map_err(|err| format!("Failed to read logs: {}", err))
     }
 
     /// Call the active version of all registered applications with the "OnBoot" run level
@@ -0,0 +1,392 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use kubos_app::RunLevel;
+use monitor::AppMonitor;
+use registry;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use toml;
+use uuid::Uuid;
+
+/// The file the schedule is saved in, under the app registry directory
+const SCHEDULE_FILE: &str = "schedule.toml";
+/// How often the scheduler checks for runs which are due
+const CHECK_INTERVAL_SECS: u64 = 1;
+/// Runs which are more than this many seconds late are considered missed
+/// (ex. because the system was powered off when they were due)
+const MISSED_RUN_SECS: u64 = 60;
+/// How long to wait before retrying a one-time run whose app failed to start
+const RETRY_DELAY_SECS: u64 = 10;
+/// How many times a one-time run is retried before it is given up on
+const MAX_RETRIES: u32 = 3;
+
+/// Look up a run level by name ("OnBoot" or "OnCommand")
+pub fn parse_run_level(name: &str) -> Result<RunLevel, String> {
+    match name {
+        "OnBoot" => Ok(RunLevel::OnBoot),
+        "OnCommand" => Ok(RunLevel::OnCommand),
+        _ => Err(format!("Unknown run level: {}", name)),
+    }
+}
+
+/// What the scheduler should do with a run which was missed
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MissedRunPolicy {
+    /// Skip the missed run. Periodic entries wait for their next scheduled run
+    Skip,
+    /// Run the app once as soon as possible, however many runs were missed
+    RunOnce,
+}
+
+impl Default for MissedRunPolicy {
+    fn default() -> Self {
+        MissedRunPolicy::Skip
+    }
+}
+
+impl fmt::Display for MissedRunPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MissedRunPolicy::Skip => write!(f, "skip"),
+            MissedRunPolicy::RunOnce => write!(f, "run-once"),
+        }
+    }
+}
+
+impl MissedRunPolicy {
+    /// Look up a policy by name ("skip" or "run-once")
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "skip" => Ok(MissedRunPolicy::Skip),
+            "run-once" => Ok(MissedRunPolicy::RunOnce),
+            _ => Err(format!("Unknown missed run policy: {}", name)),
+        }
+    }
+}
+
+/// A scheduled run of an app
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ScheduleEntry {
+    /// The generated ID of the entry
+    pub id: String,
+    /// The UUID of the app to run
+    pub app_uuid: String,
+    /// The run level to run the app with
+    pub run_level: String,
+    /// Any additional arguments to pass to the app
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// When the app should next be run, in seconds since the Unix epoch
+    pub next_run: u64,
+    /// The number of seconds between runs, for periodic entries
+    pub period: Option<u64>,
+    /// What to do if a run is missed
+    #[serde(default)]
+    pub missed: MissedRunPolicy,
+    /// When the app was last started by this entry, in seconds since the Unix epoch
+    pub last_run: Option<u64>,
+    /// The number of times in a row the app has failed to start for this entry
+    #[serde(default)]
+    pub failures: u32,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct ScheduleFile {
+    #[serde(default)]
+    schedule: Vec<ScheduleEntry>,
+}
+
+/// Get the current time in seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
+
+/// Runs apps at absolute times or periodically.
+///
+/// The schedule is saved in the app registry directory, so it survives reboots.
+/// Runs which were missed while the system was down are either skipped or run
+/// once, depending on each entry's missed run policy.
+#[derive(Clone)]
+pub struct Scheduler {
+    apps_dir: String,
+    entries: Arc<Mutex<Vec<ScheduleEntry>>>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler {
+            apps_dir: registry::K_APPS_DIR.to_owned(),
+            entries: Arc::new(Mutex::new(vec![])),
+        }
+    }
+}
+
+impl Scheduler {
+    /// Load the schedule saved in an app registry directory
+    ///
+    /// # Arguments
+    ///
+    /// * `apps_dir` - The root directory of the app registry
+    pub fn load(apps_dir: &str) -> Self {
+        let path = Path::new(apps_dir).join(SCHEDULE_FILE);
+
+        let mut contents = String::new();
+        let entries =
+            match File::open(&path).and_then(|mut file| file.read_to_string(&mut contents)) {
+                Ok(_) => match toml::from_str::<ScheduleFile>(&contents) {
+                    Ok(file) => file.schedule,
+                    Err(err) => {
+                        eprintln!("Couldn't parse schedule {}: {}", path.display(), err);
+                        vec![]
+                    }
+                },
+                Err(_) => vec![],
+            };
+
+        Scheduler {
+            apps_dir: apps_dir.to_owned(),
+            entries: Arc::new(Mutex::new(entries)),
+        }
+    }
+
+    /// Schedule an app to be run
+    ///
+    /// # Arguments
+    ///
+    /// * `app_uuid` - The UUID of the app to run
+    /// * `time` - When to first run the app, in seconds since the Unix epoch
+    /// * `period` - The number of seconds between runs, or `None` to only run the app once
+    /// * `run_level` - Which Run Level to run the app with
+    /// * `args` - Any additional arguments to pass to the app
+    /// * `missed` - What to do if a run is missed
+    pub fn add(
+        &self,
+        app_uuid: &str,
+        time: u64,
+        period: Option<u64>,
+        run_level: RunLevel,
+        args: Vec<String>,
+        missed: MissedRunPolicy,
+    ) -> Result<ScheduleEntry, String> {
+        if period == Some(0) {
+            return Err("Period must be greater than zero".to_owned());
+        }
+
+        let entry = ScheduleEntry {
+            id: Uuid::new_v4().hyphenated().to_string(),
+            app_uuid: app_uuid.to_owned(),
+            run_level: format!("{}", run_level),
+            args,
+            next_run: time,
+            period,
+            missed,
+            last_run: None,
+            failures: 0,
+        };
+
+        let mut entries = self.lock();
+        entries.push(entry.clone());
+        if let Err(err) = self.save(&entries) {
+            entries.pop();
+            return Err(err);
+        }
+
+        Ok(entry)
+    }
+
+    /// Remove an entry from the schedule
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the schedule entry
+    pub fn remove(&self, id: &str) -> Result<(), String> {
+        let mut entries = self.lock();
+        let index = match entries.iter().position(|entry| entry.id == id) {
+            Some(index) => index,
+            None => return Err(format!("Schedule entry {} does not exist", id)),
+        };
+
+        let entry = entries.remove(index);
+        if let Err(err) = self.save(&entries) {
+            entries.insert(index, entry);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Remove all of the entries which run an app. Returns the number of entries removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `app_uuid` - The UUID of the app
+    pub fn remove_app(&self, app_uuid: &str) -> Result<usize, String> {
+        let mut entries = self.lock();
+        let before = entries.clone();
+
+        entries.retain(|entry| entry.app_uuid != app_uuid);
+        let removed = before.len() - entries.len();
+        if removed == 0 {
+            return Ok(0);
+        }
+
+        if let Err(err) = self.save(&entries) {
+            *entries = before;
+            return Err(err);
+        }
+
+        Ok(removed)
+    }
+
+    /// Get all of the schedule entries, sorted by their next run time
+    pub fn entries(&self) -> Vec<ScheduleEntry> {
+        let mut entries = self.lock().clone();
+        entries.sort_by_key(|entry| entry.next_run);
+        entries
+    }
+
+    /// Start a background thread which runs apps when they're due
+    ///
+    /// # Arguments
+    ///
+    /// * `monitor` - The monitor which should supervise the started apps
+    pub fn start(&self, monitor: AppMonitor) {
+        let scheduler = self.clone();
+
+        thread::spawn(move || loop {
+            scheduler.run_due(&monitor, now());
+            thread::sleep(Duration::from_secs(CHECK_INTERVAL_SECS));
+        });
+    }
+
+    /// Start every app whose run is due. Periodic entries are moved to their next run,
+    /// and one-time entries are removed. Returns the IDs of the entries which were run.
+    ///
+    /// If an app fails to start for a one-time entry, the run is retried a few times
+    /// before the entry is given up on.
+    ///
+    /// # Arguments
+    ///
+    /// * `monitor` - The monitor which should supervise the started apps
+    /// * `now` - The current time, in seconds since the Unix epoch
+    pub fn run_due(&self, monitor: &AppMonitor, now: u64) -> Vec<String> {
+        let mut entries = self.lock();
+        if entries.iter().all(|entry| entry.next_run > now) {
+            return vec![];
+        }
+
+        let mut ran = vec![];
+        let mut remaining = vec![];
+
+        for mut entry in entries.drain(..) {
+            if entry.next_run > now {
+                remaining.push(entry);
+                continue;
+            }
+
+            let late = now - entry.next_run;
+            if late <= MISSED_RUN_SECS || entry.missed == MissedRunPolicy::RunOnce {
+                let result = parse_run_level(&entry.run_level).and_then(|run_level| {
+                    registry::start_saved_app(
+                        &self.apps_dir,
+                        monitor,
+                        &entry.app_uuid,
+                        &run_level,
+                        entry.args.clone(),
+                    )
+                });
+
+                match result {
+                    Ok(_) => {
+                        entry.last_run = Some(now);
+                        entry.failures = 0;
+                        ran.push(entry.id.clone());
+                    }
+                    Err(err) => {
+                        eprintln!("Failed to run scheduled app {}: {}", entry.app_uuid, err);
+                        entry.failures += 1;
+
+                        if entry.period.is_none() {
+                            if entry.failures <= MAX_RETRIES {
+                                eprintln!("Retrying in {} seconds", RETRY_DELAY_SECS);
+                                entry.next_run = now + RETRY_DELAY_SECS;
+                                remaining.push(entry);
+                            } else {
+                                eprintln!(
+                                    "Giving up on scheduled run {} of app {}",
+                                    entry.id, entry.app_uuid
+                                );
+                            }
+                            continue;
+                        }
+                    }
+                }
+            } else {
+                eprintln!(
+                    "Skipping missed run of app {} (due {} seconds ago)",
+                    entry.app_uuid, late
+                );
+            }
+
+            // Move periodic entries to their next run after now
+            if let Some(period) = entry.period {
+                entry.next_run += period * (late / period + 1);
+                remaining.push(entry);
+            }
+        }
+
+        *entries = remaining;
+        if let Err(err) = self.save(&entries) {
+            eprintln!("{}", err);
+        }
+
+        ran
+    }
+
+    // Save the schedule by writing a new file and moving it into place,
+    // so that a power loss can't leave a partially written schedule behind
+    fn save(&self, entries: &[ScheduleEntry]) -> Result<(), String> {
+        let path = Path::new(&self.apps_dir).join(SCHEDULE_FILE);
+        let tmp_path: PathBuf = path.with_extension("toml.tmp");
+
+        let file = ScheduleFile {
+            schedule: entries.to_vec(),
+        };
+        let contents = toml::to_string(&file)
+            .or_else(|err| Err(format!("Couldn't save schedule: {}", err)))?;
+
+        File::create(&tmp_path)
+            .and_then(|mut file| {
+                file.write_all(contents.as_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .or_else(|err| Err(format!("Couldn't save schedule: {}", err)))
+    }
+
+    fn lock(&self) -> MutexGuard<Vec<ScheduleEntry>> {
+        self.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
@@ -13,13 +13,23 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+use integrity;
 use juniper::{FieldError, FieldResult, Value};
 use kubos_app::RunLevel;
 use kubos_service;
+use libc;
+use monitor;
 use registry::{self, AppRegistry};
+use scheduler::{self, MissedRunPolicy};
+use std::time::Duration;
 
 type Context = kubos_service::Context<AppRegistry>;
 
+/// Default number of seconds a stopped app is given to exit before it is killed
+const DEFAULT_GRACE_PERIOD: i32 = 5;
+/// Default number of lines returned by the `appLogs` query
+const DEFAULT_LOG_LINES: i32 = 50;
+
 pub struct KApp(pub registry::App);
 
 graphql_object!(KApp: () as "App" |&self| {
@@ -55,11 +65,29 @@ graphql_object!(KApp: () as "App" |&self| {
         Ok(self.0.pid as i32)
     }
 
+    field running() -> FieldResult<bool>
+        as "Whether the application is currently running"
+    {
+        Ok(self.0.pid != 0)
+    }
+
     field path() -> FieldResult<&String>
         as "Absolute Path"
     {
         Ok(&self.0.path)
     }
+
+    field restart_policy() -> FieldResult<String>
+        as "Restart Policy"
+    {
+        Ok(self.0.metadata.restart_policy.to_string())
+    }
+
+    field hash() -> FieldResult<Option<String>>
+        as "BLAKE2s hash of the application binary, taken when it was registered"
+    {
+        Ok(self.0.hash.clone())
+    }
 });
 
 pub struct KAppRegistryEntry(pub registry::AppRegistryEntry);
@@ -76,6 +104,181 @@ graphql_object!(KAppRegistryEntry: () as "AppRegistryEntry" |&self| {
     {
         Ok(self.0.active_version)
     }
+
+    field previous_version() -> FieldResult<Option<String>>
+        as "Version which was active before this one"
+    {
+        Ok(self.0.previous_version.clone())
+    }
+});
+
+pub struct KExitRecord(pub monitor::ExitRecord);
+
+graphql_object!(KExitRecord: () as "ExitRecord" |&self| {
+    description: "A record of an application process exiting"
+
+    field pid() -> FieldResult<i32>
+        as "Process ID"
+    {
+        Ok(self.0.pid as i32)
+    }
+
+    field code() -> FieldResult<Option<i32>>
+        as "Exit code, if the process exited normally"
+    {
+        Ok(self.0.code)
+    }
+
+    field signal() -> FieldResult<Option<i32>>
+        as "Signal which killed the process, if any"
+    {
+        Ok(self.0.signal)
+    }
+
+    field timestamp() -> FieldResult<i32>
+        as "Exit time, in seconds since the Unix epoch"
+    {
+        Ok(self.0.timestamp() as i32)
+    }
+
+    field runtime() -> FieldResult<f64>
+        as "How long the process ran, in seconds"
+    {
+        let runtime = self.0.runtime;
+        Ok(runtime.as_secs() as f64 + f64::from(runtime.subsec_nanos()) / 1e9)
+    }
+
+    field restarted() -> FieldResult<bool>
+        as "Whether the application was restarted"
+    {
+        Ok(self.0.restarted)
+    }
+
+    field timed_out() -> FieldResult<bool>
+        as "Whether the application was killed for exceeding its timeout limit"
+    {
+        Ok(self.0.timed_out)
+    }
+});
+
+pub struct KAppStatus(pub monitor::AppStatus);
+
+graphql_object!(KAppStatus: () as "AppStatus" |&self| {
+    description: "Supervision state of an application"
+
+    field uuid() -> FieldResult<&String>
+        as "UUID"
+    {
+        Ok(&self.0.uuid)
+    }
+
+    field pid() -> FieldResult<Option<i32>>
+        as "Process ID, if the application is running"
+    {
+        Ok(self.0.pid.map(|pid| pid as i32))
+    }
+
+    field restarts() -> FieldResult<i32>
+        as "Number of automatic restarts"
+    {
+        Ok(self.0.restarts as i32)
+    }
+
+    field exits() -> FieldResult<Vec<KExitRecord>>
+        as "Most recent exits, oldest first"
+    {
+        Ok(self.0.exits.iter().cloned().map(KExitRecord).collect())
+    }
+
+    field rollback() -> FieldResult<Option<String>>
+        as "Version the application was automatically rolled back to"
+    {
+        Ok(self.0.rollback.clone())
+    }
+});
+
+pub struct KScheduleEntry(pub scheduler::ScheduleEntry);
+
+graphql_object!(KScheduleEntry: () as "ScheduleEntry" |&self| {
+    description: "A scheduled run of an application"
+
+    field id() -> FieldResult<&String>
+        as "Schedule Entry ID"
+    {
+        Ok(&self.0.id)
+    }
+
+    field uuid() -> FieldResult<&String>
+        as "App UUID"
+    {
+        Ok(&self.0.app_uuid)
+    }
+
+    field run_level() -> FieldResult<&String>
+        as "Run Level"
+    {
+        Ok(&self.0.run_level)
+    }
+
+    field args() -> FieldResult<&Vec<String>>
+        as "Additional Arguments"
+    {
+        Ok(&self.0.args)
+    }
+
+    field next_run() -> FieldResult<i32>
+        as "Next run time, in seconds since the Unix epoch"
+    {
+        Ok(self.0.next_run as i32)
+    }
+
+    field period() -> FieldResult<Option<i32>>
+        as "Seconds between runs, for periodic entries"
+    {
+        Ok(self.0.period.map(|period| period as i32))
+    }
+
+    field missed() -> FieldResult<String>
+        as "Missed run policy"
+    {
+        Ok(self.0.missed.to_string())
+    }
+
+    field last_run() -> FieldResult<Option<i32>>
+        as "Last run time, in seconds since the Unix epoch"
+    {
+        Ok(self.0.last_run.map(|time| time as i32))
+    }
+});
+
+pub struct KIntegrityProblem(pub integrity::IntegrityProblem);
+
+graphql_object!(KIntegrityProblem: () as "IntegrityProblem" |&self| {
+    description: "A corrupt or orphaned entry in the app registry"
+
+    field path() -> FieldResult<&String>
+        as "File or directory with the problem"
+    {
+        Ok(&self.0.path)
+    }
+
+    field uuid() -> FieldResult<&String>
+        as "App UUID"
+    {
+        Ok(&self.0.uuid)
+    }
+
+    field kind() -> FieldResult<String>
+        as "Kind of problem"
+    {
+        Ok(self.0.kind.to_string())
+    }
+
+    field description() -> FieldResult<&String>
+        as "Description of the problem"
+    {
+        Ok(&self.0.description)
+    }
 });
 
 ///
@@ -91,7 +294,9 @@ graphql_object!(QueryRoot : Context as "Query" |&self| {
         -> FieldResult<Vec<KAppRegistryEntry>> as "Kubos Apps Query"
     {
         let mut result: Vec<KAppRegistryEntry> = Vec::new();
-        let entries = executor.context().subsystem().entries.borrow();
+        let registry = executor.context().subsystem();
+        registry.apply_rollbacks();
+        let entries = registry.entries.borrow();
         let mut final_iter = entries.iter().filter(|ref e| {
             if uuid.is_some() && &e.app.uuid != uuid.as_ref().unwrap() {
                 return false;
@@ -109,11 +314,60 @@ graphql_object!(QueryRoot : Context as "Query" |&self| {
         });
 
         for entry in final_iter {
-            result.push(KAppRegistryEntry(entry.clone()));
+            let mut entry = entry.clone();
+            // Report the live process state rather than the pid saved at registration
+            entry.app.pid = registry
+                .monitor
+                .running_pid(&entry.app.uuid, &entry.app.path)
+                .unwrap_or(0);
+            result.push(KAppRegistryEntry(entry));
         }
 
         Ok(result)
     }
+
+    field app_logs(&executor, uuid: String, stream: Option<String>, lines: Option<i32>)
+        -> FieldResult<Vec<String>> as "Most recent output of an app"
+    {
+        let stream = stream.unwrap_or_else(|| "stdout".to_owned());
+        let lines = lines.unwrap_or(DEFAULT_LOG_LINES).max(0) as usize;
+
+        match executor.context().subsystem().logs(&uuid, &stream, lines) {
+            Ok(lines) => Ok(lines),
+            Err(err) => Err(FieldError::new(err, Value::null()))
+        }
+    }
+
+    field schedule(&executor, uuid: Option<String>) -> FieldResult<Vec<KScheduleEntry>>
+        as "Scheduled app runs"
+    {
+        Ok(executor.context().subsystem().scheduler.entries()
+            .into_iter()
+            .filter(|entry| uuid.is_none() || uuid.as_ref() == Some(&entry.app_uuid))
+            .map(KScheduleEntry)
+            .collect())
+    }
+
+    field integrity(&executor) -> FieldResult<Vec<KIntegrityProblem>>
+        as "Corrupt or orphaned entries in the app registry"
+    {
+        Ok(executor.context().subsystem().integrity_report()
+            .into_iter()
+            .map(KIntegrityProblem)
+            .collect())
+    }
+
+    field app_status(&executor, uuid: Option<String>) -> FieldResult<Vec<KAppStatus>>
+        as "Supervision state and exit history of started apps"
+    {
+        let monitor = &executor.context().subsystem().monitor;
+        let statuses = match uuid {
+            Some(uuid) => monitor.status(&uuid).into_iter().collect(),
+            None => monitor.statuses(),
+        };
+
+        Ok(statuses.into_iter().map(KAppStatus).collect())
+    }
 });
 
 ///
@@ -159,4 +413,93 @@ graphql_object!(MutationRoot : Context as "Mutation" |&self| {
             Err(err) => Err(FieldError::new(err, Value::null()))
         }
     }
+
+    field set_version(&executor, uuid: String, version: String) -> FieldResult<KAppRegistryEntry>
+        as "Set Active App Version"
+    {
+        match executor.context().subsystem().set_version(&uuid, &version) {
+            Ok(entry) => Ok(KAppRegistryEntry(entry)),
+            Err(err) => Err(FieldError::new(err, Value::null()))
+        }
+    }
+
+    field add_schedule(&executor,
+                       uuid: String,
+                       time: Option<i32>,
+                       period: Option<i32>,
+                       run_level: Option<String>,
+                       args: Option<Vec<String>>,
+                       missed: Option<String>)
+        -> FieldResult<KScheduleEntry> as "Schedule App"
+    {
+        let time = match time {
+            Some(time) if time < 0 => {
+                return Err(FieldError::new("Time must not be negative", Value::null()))
+            }
+            Some(time) => time as u64,
+            None => scheduler::now(),
+        };
+        let period = match period {
+            Some(period) if period <= 0 => {
+                return Err(FieldError::new("Period must be greater than zero", Value::null()))
+            }
+            period => period.map(|period| period as u64),
+        };
+        let run_level = match run_level {
+            Some(level) => scheduler::parse_run_level(&level)
+                .map_err(|err| FieldError::new(err, Value::null()))?,
+            None => RunLevel::OnCommand,
+        };
+        let missed = match missed {
+            Some(missed) => MissedRunPolicy::parse(&missed)
+                .map_err(|err| FieldError::new(err, Value::null()))?,
+            None => MissedRunPolicy::default(),
+        };
+
+        match executor.context().subsystem().schedule(
+            &uuid,
+            time,
+            period,
+            run_level,
+            args.unwrap_or_default(),
+            missed,
+        ) {
+            Ok(entry) => Ok(KScheduleEntry(entry)),
+            Err(err) => Err(FieldError::new(err, Value::null()))
+        }
+    }
+
+    field remove_schedule(&executor, id: String) -> FieldResult<bool>
+        as "Remove Scheduled App Run"
+    {
+        match executor.context().subsystem().scheduler.remove(&id) {
+            Ok(()) => Ok(true),
+            Err(err) => Err(FieldError::new(err, Value::null()))
+        }
+    }
+
+    field stop_app(&executor, uuid: String, signal: Option<String>, grace_period: Option<i32>) -> FieldResult<bool>
+        as "Stop App"
+    {
+        let signal = match signal {
+            Some(name) => monitor::parse_signal(&name)
+                .map_err(|err| FieldError::new(err, Value::null()))?,
+            None => libc::SIGTERM,
+        };
+        let grace = Duration::from_secs(grace_period.unwrap_or(DEFAULT_GRACE_PERIOD).max(0) as u64);
+
+        match executor.context().subsystem().monitor.stop(&uuid, signal, grace) {
+            Ok(()) => Ok(true),
+            Err(err) => Err(FieldError::new(err, Value::null()))
+        }
+    }
+
+    field send_message(&executor, uuid: String, message: String) -> FieldResult<bool>
+        as "Send Message to Running App"
+    {
+        match executor.context().subsystem().monitor.send(&uuid, &message) {
+            Ok(()) => Ok(true),
+            Err(err) => Err(FieldError::new(err, Value::null()))
+        }
+    }
 });
@@ -0,0 +1,228 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use kubos_app::RunLevel;
+use std::fs;
+use std::path::Path;
+
+use tempfile::TempDir;
+
+use super::app_monitor::{mock_service, new_registry};
+use super::app_versions::register_version;
+use integrity::{self, ProblemKind};
+use registry::*;
+
+// Get the name of the file with each problem in the registry, along with the kind of problem
+fn problem_kinds(registry: &AppRegistry) -> Vec<(String, ProblemKind)> {
+    let mut problems: Vec<(String, ProblemKind)> = registry
+        .integrity_report()
+        .into_iter()
+        .map(|problem| {
+            let name = Path::new(&problem.path)
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .to_string();
+            (name, problem.kind)
+        })
+        .collect();
+    problems.sort_by(|a, b| a.0.cmp(&b.0));
+    problems
+}
+
+#[test]
+fn register_saves_hash() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let entry = register_version(&registry, "1.0", "exit 0", 0o755, "");
+
+    let hash = integrity::file_hash(Path::new(&entry.app.path)).unwrap();
+    assert_eq!(entry.app.hash, Some(hash.clone()));
+
+    // The hash should be saved in the app.toml file
+    let version_dir = registry_dir.path().join(&entry.app.uuid).join("1.0");
+    let saved = AppRegistryEntry::load(&version_dir).unwrap();
+    assert_eq!(saved.app.hash, Some(hash));
+
+    // Nothing should be left in the staging area
+    let mut files: Vec<String> = fs::read_dir(registry_dir.path().join(&entry.app.uuid))
+        .unwrap()
+        .map(|file| file.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    files.sort();
+    assert_eq!(files, vec!["1.0"]);
+    assert!(registry.integrity_report().is_empty());
+}
+
+#[test]
+fn register_same_version_replaces() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let first = register_version(&registry, "1.0", "exit 0", 0o755, "");
+    let second = register_version(&registry, "1.0", "exit 1", 0o755, "");
+
+    assert_eq!(first.app.uuid, second.app.uuid);
+    assert_ne!(first.app.hash, second.app.hash);
+    assert_eq!(registry.entries.borrow().len(), 1);
+    assert!(registry.entries.borrow()[0].active_version);
+    assert!(registry.integrity_report().is_empty());
+}
+
+#[test]
+fn start_corrupt_binary() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let entry = register_version(&registry, "1.0", "exit 0", 0o755, "");
+    fs::write(&entry.app.path, "#!/bin/sh\nexit 1\n").unwrap();
+
+    let err = registry
+        .start_app(&entry.app.uuid, RunLevel::OnCommand, None)
+        .unwrap_err();
+    assert!(err.starts_with(&format!("{} is corrupt", entry.app.path)));
+
+    assert_eq!(
+        problem_kinds(&registry),
+        vec![("1.0".to_owned(), ProblemKind::HashMismatch)]
+    );
+}
+
+#[test]
+fn corrupt_app_toml() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let entry = register_version(&registry, "1.0", "exit 0", 0o755, "");
+    register_version(&registry, "2.0", "exit 0", 0o755, "");
+
+    let version_dir = registry_dir.path().join(&entry.app.uuid).join("1.0");
+    fs::write(version_dir.join("app.toml"), "active_version = ").unwrap();
+
+    // The corrupt version is skipped when the registry is loaded...
+    let reloaded = AppRegistry::new_from_dir(&registry_dir.path().to_string_lossy());
+    assert_eq!(reloaded.entries.borrow().len(), 1);
+
+    // ...but reported
+    assert_eq!(
+        problem_kinds(&reloaded),
+        vec![("1.0".to_owned(), ProblemKind::CorruptMetadata)]
+    );
+}
+
+#[test]
+fn missing_app_toml_and_binary() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let entry = register_version(&registry, "1.0", "exit 0", 0o755, "");
+    register_version(&registry, "2.0", "exit 0", 0o755, "");
+
+    let app_dir = registry_dir.path().join(&entry.app.uuid);
+    fs::remove_file(app_dir.join("1.0").join("app.toml")).unwrap();
+    fs::remove_file(app_dir.join("2.0").join("tiny-app")).unwrap();
+
+    assert_eq!(
+        problem_kinds(&registry),
+        vec![
+            ("1.0".to_owned(), ProblemKind::MissingMetadata),
+            ("2.0".to_owned(), ProblemKind::MissingBinary),
+        ]
+    );
+}
+
+#[test]
+fn orphaned_active_link() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let uuid = register_version(&registry, "1.0", "exit 0", 0o755, "")
+        .app
+        .uuid;
+    fs::remove_dir_all(registry_dir.path().join(&uuid).join("1.0")).unwrap();
+
+    assert_eq!(
+        problem_kinds(&registry),
+        vec![(uuid, ProblemKind::OrphanedLink)]
+    );
+}
+
+#[test]
+fn recover_interrupted_registration() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let entry = register_version(&registry, "1.0", "exit 0", 0o755, "");
+    let app_dir = registry_dir.path().join(&entry.app.uuid);
+
+    // Power was lost while staging version 2.0...
+    fs::create_dir(app_dir.join(".2.0.staging")).unwrap();
+    fs::copy(
+        app_dir.join("1.0").join("app.toml"),
+        app_dir.join(".2.0.staging").join("app.toml"),
+    )
+    .unwrap();
+    // ...and after moving version 1.0 aside to replace it
+    fs::rename(app_dir.join("1.0"), app_dir.join(".1.0.old")).unwrap();
+
+    assert_eq!(
+        problem_kinds(&registry),
+        vec![
+            (".1.0.old".to_owned(), ProblemKind::Incomplete),
+            (".2.0.staging".to_owned(), ProblemKind::Incomplete),
+            (entry.app.uuid.clone(), ProblemKind::OrphanedLink),
+        ]
+    );
+
+    let reloaded = AppRegistry::new_from_dir(&registry_dir.path().to_string_lossy());
+    assert_eq!(reloaded.entries.borrow().len(), 1);
+    assert!(app_dir.join("1.0").join("tiny-app").exists());
+    assert!(!app_dir.join(".1.0.old").exists());
+    assert!(!app_dir.join(".2.0.staging").exists());
+    assert!(reloaded.integrity_report().is_empty());
+}
+
+#[test]
+fn integrity_query() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let entry = register_version(&registry, "1.0", "exit 0", 0o755, "");
+    let version_dir = registry_dir.path().join(&entry.app.uuid).join("1.0");
+    fs::remove_file(version_dir.join("app.toml")).unwrap();
+
+    let service = mock_service(registry);
+
+    let query = r#"{
+        integrity { path, uuid, kind, description }
+    }"#;
+
+    let expected = json!({
+        "errs": "",
+        "msg": {
+            "integrity": [{
+                "path": version_dir.to_string_lossy(),
+                "uuid": entry.app.uuid,
+                "kind": "missing-metadata",
+                "description": "No app.toml file found"
+            }]
+        }
+    })
+    .to_string();
+
+    assert_eq!(service.process(query.to_owned()), expected);
+}
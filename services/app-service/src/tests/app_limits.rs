@@ -0,0 +1,122 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use kubos_app::RunLevel;
+use libc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+use super::app_monitor::new_registry;
+use super::app_versions::register_version;
+
+// Run an app with the given limits and return the first line it prints
+fn run_with_limits(script: &str, limits: &str) -> String {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_version(
+        &registry,
+        "1.0",
+        script,
+        0o755,
+        &format!("[limits]\n{}", limits),
+    )
+    .app
+    .uuid;
+
+    registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+
+    let start = Instant::now();
+    loop {
+        if let Some(line) = registry.logs(&uuid, "stdout", 1).unwrap().pop() {
+            return line;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn limits_open_files() {
+    assert_eq!(run_with_limits("ulimit -n", "open_files = 64"), "64");
+}
+
+#[test]
+fn limits_memory() {
+    // The shell reports the limit in KiB
+    assert_eq!(run_with_limits("ulimit -v", "memory = 104857600"), "102400");
+}
+
+#[test]
+fn limits_nice() {
+    assert_eq!(run_with_limits("nice", "nice = 5"), "5");
+}
+
+#[test]
+fn limits_timeout() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_version(
+        &registry,
+        "1.0",
+        "exec sleep 30",
+        0o755,
+        "[limits]\ntimeout = 1",
+    )
+    .app
+    .uuid;
+
+    registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+
+    let start = Instant::now();
+    loop {
+        let status = registry.monitor.status(&uuid).unwrap();
+        if !status.exits.is_empty() {
+            assert_eq!(status.exits[0].signal, Some(libc::SIGKILL));
+            assert!(status.exits[0].runtime >= Duration::from_secs(1));
+            assert!(status.exits[0].timed_out);
+            assert!(!status.exits[0].failed());
+            break;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn limits_unknown_user() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_version(
+        &registry,
+        "1.0",
+        "exit 0",
+        0o755,
+        "[limits]\nuser = \"no-such-user\"",
+    )
+    .app
+    .uuid;
+
+    let err = registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap_err();
+    assert!(err.contains("Unknown user: no-such-user"));
+}
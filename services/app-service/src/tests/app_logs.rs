@@ -0,0 +1,161 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use kubos_app::RunLevel;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+use super::app_monitor::{mock_service, new_registry, register_app};
+use logs::{self, LogWriter};
+
+#[test]
+fn log_rotation() {
+    let log_dir = TempDir::new().unwrap();
+
+    let mut log = LogWriter::open(log_dir.path(), "stdout", 14).unwrap();
+    for num in 1..8 {
+        log.write_line(format!("line {}\n", num).as_bytes())
+            .unwrap();
+    }
+
+    assert!(log_dir.path().join("stdout.log").exists());
+    assert!(log_dir.path().join("stdout.log.1").exists());
+    assert!(log_dir.path().join("stdout.log.2").exists());
+    assert!(!log_dir.path().join("stdout.log.3").exists());
+
+    // The oldest file was rotated out
+    assert_eq!(
+        logs::tail(log_dir.path(), "stdout", 10).unwrap(),
+        vec!["line 3", "line 4", "line 5", "line 6", "line 7"]
+    );
+}
+
+#[test]
+fn log_reopen_appends() {
+    let log_dir = TempDir::new().unwrap();
+
+    {
+        let mut log = LogWriter::open(log_dir.path(), "stderr", 1024).unwrap();
+        log.write_line(b"first run\n").unwrap();
+    }
+    let mut log = LogWriter::open(log_dir.path(), "stderr", 1024).unwrap();
+    log.write_line(b"second run\n").unwrap();
+
+    assert_eq!(
+        logs::tail(log_dir.path(), "stderr", 1).unwrap(),
+        vec!["second run"]
+    );
+    assert_eq!(
+        logs::tail(log_dir.path(), "stderr", 10).unwrap(),
+        vec!["first run", "second run"]
+    );
+}
+
+#[test]
+fn log_tail_limit() {
+    let log_dir = TempDir::new().unwrap();
+
+    let mut log = LogWriter::open(log_dir.path(), "stdout", 1024 * 1024).unwrap();
+    for num in 0..(logs::MAX_TAIL_LINES + 10) {
+        log.write_line(format!("line {}\n", num).as_bytes())
+            .unwrap();
+    }
+
+    let lines = logs::tail(log_dir.path(), "stdout", i32::max_value() as usize).unwrap();
+    assert_eq!(lines.len(), logs::MAX_TAIL_LINES);
+    assert_eq!(lines[0], "line 10");
+}
+
+#[test]
+fn log_tail_missing() {
+    let log_dir = TempDir::new().unwrap();
+
+    assert!(logs::tail(log_dir.path(), "stdout", 10).unwrap().is_empty());
+}
+
+#[test]
+fn app_logs_query() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(
+        &registry,
+        "echo \"started $@\"\necho oops >&2\necho done",
+        "never",
+    );
+
+    registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+
+    // Wait for the output to be captured
+    let start = Instant::now();
+    while registry.logs(&uuid, "stdout", 10).unwrap().len() < 2 {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(10));
+    }
+    while registry.logs(&uuid, "stderr", 10).unwrap().is_empty() {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let service = mock_service(registry);
+
+    let query = format!(
+        r#"{{
+        stdout: appLogs(uuid: "{0}", lines: 1),
+        all: appLogs(uuid: "{0}"),
+        stderr: appLogs(uuid: "{0}", stream: "stderr")
+    }}"#,
+        uuid
+    );
+
+    let expected = json!({
+        "errs": "",
+        "msg": {
+            "stdout": ["done"],
+            "all": ["started -r OnCommand", "done"],
+            "stderr": ["oops"]
+        }
+    })
+    .to_string();
+
+    assert_eq!(service.process(query), expected);
+}
+
+#[test]
+fn app_logs_bad_stream() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "never");
+
+    assert_eq!(
+        registry.logs(&uuid, "stdin", 10),
+        Err("Unknown log stream: stdin".to_owned())
+    );
+}
+
+#[test]
+fn app_logs_bad_uuid() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    assert_eq!(
+        registry.logs("fake-uuid", "stdout", 10),
+        Err("App with UUID fake-uuid does not exist".to_owned())
+    );
+}
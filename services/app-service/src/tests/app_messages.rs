@@ -0,0 +1,112 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use kubos_app::RunLevel;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+use super::app_monitor::{mock_service, new_registry, register_app};
+use logs;
+
+fn send_query(uuid: &str, message: &str) -> String {
+    format!(
+        r#"mutation {{
+        sendMessage(uuid: "{}", message: "{}")
+    }}"#,
+        uuid, message
+    )
+}
+
+#[test]
+fn send_message_good() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(
+        &registry,
+        "while read line; do echo \"got $line\"; done",
+        "never",
+    );
+
+    registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+
+    registry.monitor.send(&uuid, "first").unwrap();
+
+    let monitor = registry.monitor.clone();
+    let log_dir = logs::log_dir(&registry.apps_dir, &uuid);
+    let service = mock_service(registry);
+
+    let expected = json!({
+        "errs": "",
+        "msg": {
+            "sendMessage": true
+        }
+    })
+    .to_string();
+    assert_eq!(service.process(send_query(&uuid, "second")), expected);
+
+    let start = Instant::now();
+    loop {
+        let lines = logs::tail(&log_dir, "stdout", 2).unwrap();
+        if lines.len() == 2 {
+            assert_eq!(lines, vec!["got first", "got second"]);
+            break;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert!(monitor.send(&uuid, "one\ntwo").is_err());
+}
+
+#[test]
+fn send_message_not_running() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "never");
+
+    let service = mock_service(registry);
+
+    let expected = format!("{{\"errs\":\"{{\\\"message\\\":\\\"App {} is not running\\\",\\\"locations\\\":[{{\\\"line\\\":2,\\\"column\\\":9}}],\\\"path\\\":[\\\"sendMessage\\\"]}}\",\"msg\":null}}", uuid);
+
+    assert_eq!(service.process(send_query(&uuid, "hello")), expected);
+}
+
+#[test]
+fn send_message_after_exit() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "read line", "never");
+
+    registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+    registry.monitor.send(&uuid, "bye").unwrap();
+
+    // Once the app has exited, messages are rejected
+    let start = Instant::now();
+    while registry.monitor.status(&uuid).unwrap().exits.is_empty() {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(
+        registry.monitor.send(&uuid, "hello"),
+        Err(format!("App {} is not running", uuid))
+    );
+}
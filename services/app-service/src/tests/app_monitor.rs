@@ -0,0 +1,416 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use kubos_app::RunLevel;
+use kubos_service::{Config, Service};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+use libc;
+use monitor::{self, AppMonitor, AppStatus};
+use registry::*;
+use schema;
+
+// Register a shell script app with the given body and restart policy
+pub fn register_app(registry: &AppRegistry, script: &str, policy: &str) -> String {
+    let app_dir = TempDir::new().unwrap();
+    let app_bin = app_dir.path().join("tiny-app");
+
+    fs::create_dir(app_bin.clone()).unwrap();
+
+    let src = format!("#!/bin/sh\n{}\n", script);
+    fs::write(app_bin.join("tiny-app"), src).unwrap();
+    fs::set_permissions(app_bin.join("tiny-app"), fs::Permissions::from_mode(0o755)).unwrap();
+
+    let manifest = format!(
+        r#"
+            name = "tiny-app"
+            version = "0.0.1"
+            author = "user"
+            restart_policy = "{}"
+            "#,
+        policy
+    );
+    fs::write(app_bin.join("manifest.toml"), manifest).unwrap();
+
+    registry
+        .register(&app_bin.to_string_lossy())
+        .unwrap()
+        .app
+        .uuid
+}
+
+pub fn new_registry(registry_dir: &TempDir) -> AppRegistry {
+    let mut registry = AppRegistry::new_from_dir(&registry_dir.path().to_string_lossy());
+    registry.monitor = AppMonitor::with_backoff(Duration::from_millis(10));
+    registry
+}
+
+// Wait for the app's supervision state to match the condition
+fn wait_for<F>(registry: &AppRegistry, uuid: &str, condition: F) -> AppStatus
+where
+    F: Fn(&AppStatus) -> bool,
+{
+    let start = Instant::now();
+    loop {
+        if let Some(status) = registry.monitor.status(uuid) {
+            if condition(&status) {
+                return status;
+            }
+        }
+
+        if start.elapsed() > Duration::from_secs(5) {
+            panic!("Timed out waiting for app {}", uuid);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn monitor_never_restart() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 3", "never");
+
+    registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+
+    let status = wait_for(&registry, &uuid, |status| !status.exits.is_empty());
+    // Give the supervisor a chance to (incorrectly) restart the app
+    thread::sleep(Duration::from_millis(100));
+    let status = registry.monitor.status(&uuid).unwrap_or(status);
+
+    assert_eq!(status.pid, None);
+    assert_eq!(status.restarts, 0);
+    assert_eq!(status.exits.len(), 1);
+    assert_eq!(status.exits[0].code, Some(3));
+    assert_eq!(status.exits[0].signal, None);
+    assert!(!status.exits[0].restarted);
+}
+
+#[test]
+fn monitor_on_failure_restart() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 1", "on-failure");
+
+    let pid = registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+
+    let status = wait_for(&registry, &uuid, |status| status.restarts >= 2);
+
+    assert_eq!(status.exits[0].pid, pid);
+    assert_eq!(status.exits[0].code, Some(1));
+    assert!(status.exits[0].restarted);
+    assert_ne!(status.exits[1].pid, pid);
+}
+
+#[test]
+fn monitor_on_failure_success() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "on-failure");
+
+    registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+
+    let status = wait_for(&registry, &uuid, |status| !status.exits.is_empty());
+    thread::sleep(Duration::from_millis(100));
+    let status = registry.monitor.status(&uuid).unwrap_or(status);
+
+    assert_eq!(status.restarts, 0);
+    assert_eq!(status.exits.len(), 1);
+    assert_eq!(status.exits[0].code, Some(0));
+    assert!(!status.exits[0].restarted);
+}
+
+#[test]
+fn monitor_always_restart() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "always");
+
+    registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+
+    let status = wait_for(&registry, &uuid, |status| status.restarts >= 1);
+
+    assert_eq!(status.exits[0].code, Some(0));
+    assert!(status.exits[0].restarted);
+}
+
+#[test]
+fn monitor_status_query() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 2", "never");
+
+    let pid = registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+    wait_for(&registry, &uuid, |status| !status.exits.is_empty());
+
+    let service = mock_service(registry);
+
+    let query = format!(
+        r#"{{
+        appStatus(uuid: "{}") {{
+            uuid, pid, restarts, exits {{ pid, code, signal, restarted }}
+        }}
+    }}"#,
+        uuid
+    );
+
+    let expected = json!({
+        "errs": "",
+        "msg": {
+            "appStatus": [{
+                "uuid": uuid,
+                "pid": null,
+                "restarts": 0,
+                "exits": [{
+                    "pid": pid,
+                    "code": 2,
+                    "signal": null,
+                    "restarted": false
+                }]
+            }]
+        }
+    })
+    .to_string();
+
+    assert_eq!(service.process(query), expected);
+}
+
+fn stop_query(uuid: &str, extra: &str) -> String {
+    format!(
+        r#"mutation {{
+        stopApp(uuid: "{}"{})
+    }}"#,
+        uuid, extra
+    )
+}
+
+fn running_query(uuid: &str) -> String {
+    format!(
+        r#"{{
+        apps(uuid: "{}") {{
+            app {{ running }}
+        }}
+    }}"#,
+        uuid
+    )
+}
+
+fn running_response(running: bool) -> String {
+    json!({
+        "errs": "",
+        "msg": {
+            "apps": [{
+                "app": {
+                    "running": running
+                }
+            }]
+        }
+    })
+    .to_string()
+}
+
+pub fn mock_service(
+    registry: AppRegistry,
+) -> Service<'static, schema::QueryRoot, schema::MutationRoot, AppRegistry> {
+    Service::new(
+        Config::new_from_str("app-service", ""),
+        registry,
+        schema::QueryRoot,
+        schema::MutationRoot,
+    )
+}
+
+#[test]
+fn stop_app_good() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exec sleep 30", "on-failure");
+
+    registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+
+    let monitor = registry.monitor.clone();
+    let service = mock_service(registry);
+
+    assert_eq!(
+        service.process(running_query(&uuid)),
+        running_response(true)
+    );
+
+    let expected = json!({
+        "errs": "",
+        "msg": {
+            "stopApp": true
+        }
+    })
+    .to_string();
+    assert_eq!(service.process(stop_query(&uuid, "")), expected);
+
+    assert_eq!(
+        service.process(running_query(&uuid)),
+        running_response(false)
+    );
+
+    // Stopped apps shouldn't be restarted, even if their restart policy allows it
+    thread::sleep(Duration::from_millis(100));
+    let status = monitor.status(&uuid).unwrap();
+    assert_eq!(status.pid, None);
+    assert_eq!(status.restarts, 0);
+    assert_eq!(status.exits[0].signal, Some(libc::SIGTERM));
+    assert!(!status.exits[0].restarted);
+}
+
+#[test]
+fn stop_app_signal() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exec sleep 30", "never");
+
+    registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+    registry
+        .monitor
+        .stop(
+            &uuid,
+            monitor::parse_signal("INT").unwrap(),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+
+    let status = registry.monitor.status(&uuid).unwrap();
+    assert_eq!(status.exits[0].signal, Some(libc::SIGINT));
+}
+
+#[test]
+fn stop_app_grace_period() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(
+        &registry,
+        "trap '' TERM\nwhile true; do sleep 0.1; done",
+        "never",
+    );
+
+    registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+    // Give the app time to start ignoring SIGTERM
+    thread::sleep(Duration::from_millis(100));
+
+    let monitor = registry.monitor.clone();
+    let service = mock_service(registry);
+
+    let expected = json!({
+        "errs": "",
+        "msg": {
+            "stopApp": true
+        }
+    })
+    .to_string();
+    assert_eq!(
+        service.process(stop_query(&uuid, ", gracePeriod: 0")),
+        expected
+    );
+
+    let status = monitor.status(&uuid).unwrap();
+    assert_eq!(status.exits[0].signal, Some(libc::SIGKILL));
+}
+
+#[test]
+fn stop_app_kills_in_background() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(
+        &registry,
+        "trap '' TERM\nwhile true; do sleep 0.1; done",
+        "never",
+    );
+
+    registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+    // Give the app time to start ignoring SIGTERM
+    thread::sleep(Duration::from_millis(100));
+
+    // The service shouldn't be held up for the whole grace period
+    let start = Instant::now();
+    registry
+        .monitor
+        .stop(&uuid, libc::SIGTERM, Duration::from_secs(2))
+        .unwrap();
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(registry.monitor.status(&uuid).unwrap().pid.is_some());
+
+    loop {
+        let status = registry.monitor.status(&uuid).unwrap();
+        if !status.exits.is_empty() {
+            assert_eq!(status.exits[0].signal, Some(libc::SIGKILL));
+            break;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn stop_app_not_running() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "never");
+
+    let service = mock_service(registry);
+
+    let expected = format!("{{\"errs\":\"{{\\\"message\\\":\\\"App {} is not running\\\",\\\"locations\\\":[{{\\\"line\\\":2,\\\"column\\\":9}}],\\\"path\\\":[\\\"stopApp\\\"]}}\",\"msg\":null}}", uuid);
+
+    assert_eq!(service.process(stop_query(&uuid, "")), expected);
+    assert_eq!(
+        service.process(running_query(&uuid)),
+        running_response(false)
+    );
+}
+
+#[test]
+fn stop_app_bad_signal() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "never");
+
+    let service = mock_service(registry);
+
+    let expected = "{\"errs\":\"{\\\"message\\\":\\\"Unsupported signal: SIGFAKE\\\",\\\"locations\\\":[{\\\"line\\\":2,\\\"column\\\":9}],\\\"path\\\":[\\\"stopApp\\\"]}\",\"msg\":null}";
+
+    assert_eq!(
+        service.process(stop_query(&uuid, ", signal: \"SIGFAKE\"")),
+        expected
+    );
+}
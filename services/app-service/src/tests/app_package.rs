@@ -0,0 +1,222 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use kubos_app::RunLevel;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+use super::app_monitor::new_registry;
+use registry::*;
+
+// Create an app package with a shell script executable, a config file and a data directory
+fn create_package(app_dir: &TempDir, script: &str, manifest: &str) -> PathBuf {
+    let package = app_dir.path().join("multi-app");
+
+    fs::create_dir_all(package.join("data")).unwrap();
+    fs::create_dir(package.join("etc")).unwrap();
+
+    fs::write(
+        package.join("multi-app"),
+        format!("#!/bin/sh\n{}\n", script),
+    )
+    .unwrap();
+    fs::set_permissions(package.join("multi-app"), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(package.join("etc/config.toml"), "mode = \"test\"\n").unwrap();
+    fs::write(package.join("data/table.bin"), "data").unwrap();
+
+    let manifest = format!(
+        r#"
+            name = "multi-app"
+            version = "1.0"
+            author = "user"
+            {}
+            "#,
+        manifest
+    );
+    fs::write(package.join("manifest.toml"), manifest).unwrap();
+
+    package
+}
+
+fn register_package(registry: &AppRegistry, package: &Path) -> Result<AppRegistryEntry, String> {
+    registry.register(&package.to_string_lossy())
+}
+
+// Wait for an app to print a line and return it
+fn first_line(registry: &AppRegistry, uuid: &str) -> String {
+    let start = Instant::now();
+    loop {
+        if let Some(line) = registry.logs(uuid, "stdout", 1).unwrap().pop() {
+            return line;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn package_multiple_files() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let app_dir = TempDir::new().unwrap();
+    let package = create_package(
+        &app_dir,
+        "cat \"$KUBOS_APP_CONFIG\"",
+        "executable = \"multi-app\"\nconfig = \"etc/config.toml\"",
+    );
+
+    let entry = register_package(&registry, &package).unwrap();
+
+    let version_dir = registry_dir.path().join(&entry.app.uuid).join("1.0");
+    assert_eq!(
+        entry.app.path,
+        version_dir.join("multi-app").to_string_lossy()
+    );
+    assert_eq!(
+        fs::read_to_string(version_dir.join("data/table.bin")).unwrap(),
+        "data"
+    );
+    assert_eq!(
+        entry.app.config_path(),
+        Some(
+            version_dir
+                .join("etc/config.toml")
+                .to_string_lossy()
+                .to_string()
+        )
+    );
+
+    // The app should be able to find its config file
+    registry
+        .start_app(&entry.app.uuid, RunLevel::OnCommand, None)
+        .unwrap();
+    assert_eq!(first_line(&registry, &entry.app.uuid), "mode = \"test\"");
+}
+
+#[test]
+fn package_no_executable() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let app_dir = TempDir::new().unwrap();
+    let package = create_package(&app_dir, "exit 0", "");
+
+    assert_eq!(
+        register_package(&registry, &package).unwrap_err(),
+        "The manifest must name the app executable"
+    );
+}
+
+#[test]
+fn package_missing_executable() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let app_dir = TempDir::new().unwrap();
+    let package = create_package(&app_dir, "exit 0", "executable = \"other-app\"");
+
+    assert_eq!(
+        register_package(&registry, &package).unwrap_err(),
+        format!(
+            "Failed to find app file {}",
+            package.join("other-app").display()
+        )
+    );
+}
+
+#[test]
+fn package_bad_config_path() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let app_dir = TempDir::new().unwrap();
+    let package = create_package(
+        &app_dir,
+        "exit 0",
+        "executable = \"multi-app\"\nconfig = \"../config.toml\"",
+    );
+
+    assert_eq!(
+        register_package(&registry, &package).unwrap_err(),
+        "Invalid app file ../config.toml: must be relative to the app directory"
+    );
+}
+
+#[test]
+fn package_boot_args() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let app_dir = TempDir::new().unwrap();
+    let package = create_package(
+        &app_dir,
+        "echo \"$@\"",
+        "executable = \"multi-app\"\nboot_args = [\"--safe\", \"--verbose\"]",
+    );
+
+    let uuid = register_package(&registry, &package).unwrap().app.uuid;
+
+    registry.run_onboot().unwrap();
+    assert_eq!(first_line(&registry, &uuid), "-r OnBoot --safe --verbose");
+}
+
+#[test]
+fn package_required_services() {
+    let registry_dir = TempDir::new().unwrap();
+    let mut registry = new_registry(&registry_dir);
+    let app_dir = TempDir::new().unwrap();
+    let package = create_package(
+        &app_dir,
+        "exit 0",
+        "executable = \"multi-app\"\nrequires = [\"telemetry-service\"]",
+    );
+
+    let config_dir = TempDir::new().unwrap();
+    let config_path = config_dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        "[telemetry-service.addr]\nip = \"127.0.0.1\"\nport = 8020\n",
+    )
+    .unwrap();
+    registry.config_path = config_path.to_string_lossy().to_string();
+
+    let entry = register_package(&registry, &package).unwrap();
+    assert_eq!(entry.app.metadata.requires, vec!["telemetry-service"]);
+}
+
+#[test]
+fn package_missing_service() {
+    let registry_dir = TempDir::new().unwrap();
+    let mut registry = new_registry(&registry_dir);
+    let app_dir = TempDir::new().unwrap();
+    let package = create_package(
+        &app_dir,
+        "exit 0",
+        "executable = \"multi-app\"\nrequires = [\"payload-service\"]",
+    );
+
+    let config_dir = TempDir::new().unwrap();
+    let config_path = config_dir.path().join("config.toml");
+    fs::write(&config_path, "[telemetry-service]\n").unwrap();
+    registry.config_path = config_path.to_string_lossy().to_string();
+
+    assert_eq!(
+        register_package(&registry, &package).unwrap_err(),
+        "Required service payload-service is not available: No [payload-service] section found in config"
+    );
+    assert!(registry.entries.borrow().is_empty());
+}
@@ -0,0 +1,330 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use kubos_app::RunLevel;
+use libc;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+use super::app_monitor::{mock_service, new_registry};
+use registry::*;
+
+const ROLLBACK: &str = r#"
+    restart_policy = "on-failure"
+
+    [rollback]
+    crashes = 2
+    window = 60
+    "#;
+
+// Register a version of a shell script app
+pub fn register_version(
+    registry: &AppRegistry,
+    version: &str,
+    script: &str,
+    mode: u32,
+    extra: &str,
+) -> AppRegistryEntry {
+    let app_dir = TempDir::new().unwrap();
+    let app_bin = app_dir.path().join("tiny-app");
+
+    fs::create_dir(app_bin.clone()).unwrap();
+
+    fs::write(app_bin.join("tiny-app"), format!("#!/bin/sh\n{}\n", script)).unwrap();
+    fs::set_permissions(app_bin.join("tiny-app"), fs::Permissions::from_mode(mode)).unwrap();
+
+    let manifest = format!(
+        r#"
+            name = "tiny-app"
+            version = "{}"
+            author = "user"
+            {}
+            "#,
+        version, extra
+    );
+    fs::write(app_bin.join("manifest.toml"), manifest).unwrap();
+
+    registry.register(&app_bin.to_string_lossy()).unwrap()
+}
+
+fn active_versions(registry: &AppRegistry) -> Vec<(String, bool)> {
+    let mut versions: Vec<(String, bool)> = registry
+        .entries
+        .borrow()
+        .iter()
+        .map(|e| (e.app.metadata.version.clone(), e.active_version))
+        .collect();
+    versions.sort();
+    versions
+}
+
+fn active_link(registry_dir: &TempDir, uuid: &str) -> String {
+    fs::read_link(registry_dir.path().join("active").join(uuid))
+        .unwrap()
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .to_string()
+}
+
+#[test]
+fn set_version_good() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let uuid = register_version(&registry, "1.0", "exit 0", 0o755, "")
+        .app
+        .uuid;
+    let entry = register_version(&registry, "2.0", "exit 0", 0o755, "");
+    assert_eq!(entry.previous_version, Some("1.0".to_owned()));
+    assert_eq!(active_link(&registry_dir, &uuid), "2.0");
+
+    let entry = registry.set_version(&uuid, "1.0").unwrap();
+    assert!(entry.active_version);
+    assert_eq!(entry.previous_version, Some("2.0".to_owned()));
+
+    assert_eq!(
+        active_versions(&registry),
+        vec![("1.0".to_owned(), true), ("2.0".to_owned(), false)]
+    );
+    assert_eq!(active_link(&registry_dir, &uuid), "1.0");
+
+    // The change should be saved in the registry
+    let reloaded = AppRegistry::new_from_dir(&registry_dir.path().to_string_lossy());
+    assert_eq!(
+        active_versions(&reloaded),
+        vec![("1.0".to_owned(), true), ("2.0".to_owned(), false)]
+    );
+}
+
+#[test]
+fn set_version_query() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let uuid = register_version(&registry, "1.0", "exit 0", 0o755, "")
+        .app
+        .uuid;
+    register_version(&registry, "2.0", "exit 0", 0o755, "");
+
+    let service = mock_service(registry);
+
+    let query = format!(
+        r#"mutation {{
+        setVersion(uuid: "{}", version: "1.0") {{
+            active, previousVersion, app {{ version }}
+        }}
+    }}"#,
+        uuid
+    );
+
+    let expected = json!({
+        "errs": "",
+        "msg": {
+            "setVersion": {
+                "active": true,
+                "previousVersion": "2.0",
+                "app": {
+                    "version": "1.0"
+                }
+            }
+        }
+    })
+    .to_string();
+
+    assert_eq!(service.process(query), expected);
+}
+
+#[test]
+fn set_version_missing() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let uuid = register_version(&registry, "1.0", "exit 0", 0o755, "")
+        .app
+        .uuid;
+
+    assert_eq!(
+        registry.set_version(&uuid, "3.0").unwrap_err(),
+        format!("App with UUID {} and version 3.0 does not exist", uuid)
+    );
+    assert_eq!(active_versions(&registry), vec![("1.0".to_owned(), true)]);
+}
+
+#[test]
+fn rollback_on_crash() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let uuid = register_version(&registry, "1.0", "exec sleep 30", 0o755, "")
+        .app
+        .uuid;
+    register_version(&registry, "2.0", "exit 1", 0o755, ROLLBACK);
+
+    registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .unwrap();
+
+    // Wait for the new version to crash twice and the old version to be started
+    let start = Instant::now();
+    loop {
+        let status = registry.monitor.status(&uuid).unwrap();
+        if status.pid.is_some() && status.path.ends_with("/1.0/tiny-app") {
+            assert_eq!(status.rollback, Some("1.0".to_owned()));
+            break;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    registry.apply_rollbacks();
+    assert_eq!(
+        active_versions(&registry),
+        vec![("1.0".to_owned(), true), ("2.0".to_owned(), false)]
+    );
+    assert_eq!(active_link(&registry_dir, &uuid), "1.0");
+
+    registry
+        .monitor
+        .stop(&uuid, libc::SIGKILL, Duration::from_secs(1))
+        .unwrap();
+}
+
+#[test]
+fn rollback_across_manual_starts() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let uuid = register_version(&registry, "1.0", "exec sleep 30", 0o755, "")
+        .app
+        .uuid;
+    register_version(
+        &registry,
+        "2.0",
+        "exit 1",
+        0o755,
+        "[rollback]\ncrashes = 2\nwindow = 60",
+    );
+
+    // The app isn't restarted automatically, so each crash follows a manual start
+    for exits in 1..3 {
+        registry
+            .start_app(&uuid, RunLevel::OnCommand, None)
+            .unwrap();
+
+        let start = Instant::now();
+        while registry.monitor.status(&uuid).unwrap().exits.len() < exits {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    let start = Instant::now();
+    loop {
+        let status = registry.monitor.status(&uuid).unwrap();
+        if status.pid.is_some() && status.path.ends_with("/1.0/tiny-app") {
+            assert_eq!(status.rollback, Some("1.0".to_owned()));
+            break;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    // The registry only changes once it's next used
+    assert_eq!(active_link(&registry_dir, &uuid), "2.0");
+    registry.apply_rollbacks();
+    assert_eq!(active_link(&registry_dir, &uuid), "1.0");
+
+    registry
+        .monitor
+        .stop(&uuid, libc::SIGKILL, Duration::from_secs(1))
+        .unwrap();
+}
+
+#[test]
+fn rollback_on_start_failure() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let uuid = register_version(&registry, "1.0", "exit 0", 0o755, "")
+        .app
+        .uuid;
+    // The new version isn't executable
+    register_version(&registry, "2.0", "exit 0", 0o644, ROLLBACK);
+
+    assert!(registry.start_app(&uuid, RunLevel::OnCommand, None).is_ok());
+
+    assert_eq!(
+        active_versions(&registry),
+        vec![("1.0".to_owned(), true), ("2.0".to_owned(), false)]
+    );
+    assert_eq!(active_link(&registry_dir, &uuid), "1.0");
+}
+
+#[test]
+fn rollback_on_saved_start_failure() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let uuid = register_version(&registry, "1.0", "exit 0", 0o755, "")
+        .app
+        .uuid;
+    register_version(&registry, "2.0", "exit 0", 0o644, ROLLBACK);
+
+    // Apps started from outside the main thread run the previous version,
+    // but leave the registry's files for the main thread to update
+    assert!(start_saved_app(
+        &registry.apps_dir,
+        &registry.monitor,
+        &uuid,
+        &RunLevel::OnCommand,
+        vec![]
+    )
+    .is_ok());
+    assert_eq!(active_link(&registry_dir, &uuid), "2.0");
+
+    registry.apply_rollbacks();
+
+    assert_eq!(
+        active_versions(&registry),
+        vec![("1.0".to_owned(), true), ("2.0".to_owned(), false)]
+    );
+    assert_eq!(active_link(&registry_dir, &uuid), "1.0");
+}
+
+#[test]
+fn no_rollback_without_policy() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    let uuid = register_version(&registry, "1.0", "exit 0", 0o755, "")
+        .app
+        .uuid;
+    register_version(&registry, "2.0", "exit 0", 0o644, "");
+
+    assert!(registry
+        .start_app(&uuid, RunLevel::OnCommand, None)
+        .is_err());
+
+    assert_eq!(
+        active_versions(&registry),
+        vec![("1.0".to_owned(), false), ("2.0".to_owned(), true)]
+    );
+}
@@ -14,6 +14,14 @@
  * limitations under the License.
  */
 
+mod app_integrity;
+mod app_limits;
+mod app_logs;
+mod app_messages;
+mod app_monitor;
+mod app_package;
+mod app_versions;
 mod register_app;
 mod registry_test;
 mod registry_onboot;
+mod scheduler;
@@ -21,6 +21,8 @@ use std::env;
 use std::fs;
 use std::path::PathBuf;
 
+use limits::ResourceLimits;
+use monitor::{RestartPolicy, RollbackPolicy};
 use registry::*;
 
 fn setup_registry() -> PathBuf {
@@ -70,21 +72,53 @@ fn serialize_entry() {
                 name: String::from("dummy"),
                 version: String::from("0.0.1"),
                 author: String::from("noone"),
+                restart_policy: RestartPolicy::OnFailure,
+                executable: Some(String::from("dummy")),
+                config: Some(String::from("config.toml")),
+                requires: vec![String::from("telemetry-service")],
+                boot_args: vec![String::from("--safe")],
+                rollback: Some(RollbackPolicy {
+                    crashes: 3,
+                    window: 60,
+                }),
+                limits: Some(ResourceLimits {
+                    memory: Some(1048576),
+                    user: Some(String::from("kubos")),
+                    timeout: Some(30),
+                    ..Default::default()
+                }),
             },
             pid: 101,
             path: String::from("/fake/path"),
+            hash: Some(String::from("0123456789abcdef0123456789abcdef")),
         },
         active_version: true,
+        previous_version: Some(String::from("0.0.0")),
     };
 
     let str = toml::to_string(&dummy).unwrap();
     let parsed: AppRegistryEntry = toml::from_str(&str).unwrap();
 
     assert_eq!(parsed.active_version, dummy.active_version);
+    assert_eq!(parsed.previous_version, dummy.previous_version);
     assert_eq!(parsed.app.uuid, dummy.app.uuid);
     assert_eq!(parsed.app.pid, dummy.app.pid);
     assert_eq!(parsed.app.path, dummy.app.path);
+    assert_eq!(parsed.app.hash, dummy.app.hash);
     assert_eq!(parsed.app.metadata.name, dummy.app.metadata.name);
     assert_eq!(parsed.app.metadata.version, dummy.app.metadata.version);
     assert_eq!(parsed.app.metadata.author, dummy.app.metadata.author);
+    assert_eq!(
+        parsed.app.metadata.restart_policy,
+        dummy.app.metadata.restart_policy
+    );
+    assert_eq!(parsed.app.metadata.rollback, dummy.app.metadata.rollback);
+    assert_eq!(parsed.app.metadata.limits, dummy.app.metadata.limits);
+    assert_eq!(
+        parsed.app.metadata.executable,
+        dummy.app.metadata.executable
+    );
+    assert_eq!(parsed.app.metadata.config, dummy.app.metadata.config);
+    assert_eq!(parsed.app.metadata.requires, dummy.app.metadata.requires);
+    assert_eq!(parsed.app.metadata.boot_args, dummy.app.metadata.boot_args);
 }
@@ -0,0 +1,327 @@
+/*
+ * Copyright (C) 2018 Kubos Corporation
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use kubos_app::RunLevel;
+use std::fs;
+
+use tempfile::TempDir;
+
+use super::app_monitor::{mock_service, new_registry, register_app};
+use super::app_versions::register_version;
+use registry::*;
+use scheduler::{MissedRunPolicy, Scheduler};
+
+fn schedule(
+    registry: &AppRegistry,
+    uuid: &str,
+    time: u64,
+    period: Option<u64>,
+    missed: MissedRunPolicy,
+) -> String {
+    registry
+        .schedule(uuid, time, period, RunLevel::OnCommand, vec![], missed)
+        .unwrap()
+        .id
+}
+
+#[test]
+fn schedule_persists() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "never");
+
+    let id = schedule(&registry, &uuid, 1000, Some(600), MissedRunPolicy::Skip);
+
+    let loaded = Scheduler::load(&registry_dir.path().to_string_lossy());
+    let entries = loaded.entries();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id, id);
+    assert_eq!(entries[0].app_uuid, uuid);
+    assert_eq!(entries[0].next_run, 1000);
+    assert_eq!(entries[0].period, Some(600));
+
+    registry.scheduler.remove(&id).unwrap();
+
+    let loaded = Scheduler::load(&registry_dir.path().to_string_lossy());
+    assert!(loaded.entries().is_empty());
+}
+
+#[test]
+fn schedule_bad_app() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    assert_eq!(
+        registry.schedule(
+            "fake-uuid",
+            1000,
+            None,
+            RunLevel::OnCommand,
+            vec![],
+            MissedRunPolicy::Skip
+        ),
+        Err("App with UUID fake-uuid does not exist".to_owned())
+    );
+}
+
+#[test]
+fn run_once() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "never");
+
+    let id = schedule(&registry, &uuid, 1000, None, MissedRunPolicy::Skip);
+
+    assert!(registry
+        .scheduler
+        .run_due(&registry.monitor, 999)
+        .is_empty());
+    assert_eq!(
+        registry.scheduler.run_due(&registry.monitor, 1000),
+        vec![id]
+    );
+    assert!(registry.monitor.status(&uuid).is_some());
+
+    // One-time entries are removed once they've run
+    assert!(registry.scheduler.entries().is_empty());
+    assert!(registry
+        .scheduler
+        .run_due(&registry.monitor, 2000)
+        .is_empty());
+}
+
+#[test]
+fn run_periodic() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "never");
+
+    let id = schedule(&registry, &uuid, 1000, Some(600), MissedRunPolicy::Skip);
+
+    assert_eq!(
+        registry.scheduler.run_due(&registry.monitor, 1005),
+        vec![id.clone()]
+    );
+    let entry = registry.scheduler.entries()[0].clone();
+    assert_eq!(entry.next_run, 1600);
+    assert_eq!(entry.last_run, Some(1005));
+
+    assert!(registry
+        .scheduler
+        .run_due(&registry.monitor, 1599)
+        .is_empty());
+    assert_eq!(
+        registry.scheduler.run_due(&registry.monitor, 1600),
+        vec![id]
+    );
+    assert_eq!(registry.scheduler.entries()[0].next_run, 2200);
+}
+
+#[test]
+fn missed_run_skip() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "never");
+
+    schedule(&registry, &uuid, 1000, Some(600), MissedRunPolicy::Skip);
+    schedule(&registry, &uuid, 1000, None, MissedRunPolicy::Skip);
+
+    // Simulate the system coming back up long after the runs were due
+    assert!(registry
+        .scheduler
+        .run_due(&registry.monitor, 5000)
+        .is_empty());
+    assert!(registry.monitor.status(&uuid).is_none());
+
+    let entries = registry.scheduler.entries();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].next_run, 5200);
+    assert_eq!(entries[0].last_run, None);
+}
+
+#[test]
+fn missed_run_once() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "never");
+
+    let id = schedule(&registry, &uuid, 1000, Some(600), MissedRunPolicy::RunOnce);
+
+    assert_eq!(
+        registry.scheduler.run_due(&registry.monitor, 5000),
+        vec![id]
+    );
+
+    let entries = registry.scheduler.entries();
+    assert_eq!(entries[0].next_run, 5200);
+    assert_eq!(entries[0].last_run, Some(5000));
+}
+
+#[test]
+fn run_once_retries() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "never");
+
+    schedule(&registry, &uuid, 1000, None, MissedRunPolicy::Skip);
+
+    // Make the app impossible to start
+    let app_path = registry.entries.borrow()[0].app.path.clone();
+    fs::remove_file(&app_path).unwrap();
+
+    let mut now = 1000;
+    for failures in 1..4 {
+        assert!(registry.scheduler.run_due(&registry.monitor, now).is_empty());
+
+        let entries = registry.scheduler.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].failures, failures);
+        now = entries[0].next_run;
+        assert!(now > 1000);
+    }
+
+    // The entry is given up on after the last retry
+    assert!(registry.scheduler.run_due(&registry.monitor, now).is_empty());
+    assert!(registry.scheduler.entries().is_empty());
+    assert!(registry.monitor.status(&uuid).is_none());
+}
+
+#[test]
+fn uninstall_removes_schedule() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_version(&registry, "1.0", "exit 0", 0o755, "")
+        .app
+        .uuid;
+    register_version(&registry, "2.0", "exit 0", 0o755, "");
+
+    schedule(&registry, &uuid, 1000, Some(600), MissedRunPolicy::Skip);
+    schedule(&registry, &uuid, 2000, None, MissedRunPolicy::Skip);
+
+    // The entries are kept while a version of the app is still installed
+    registry.uninstall(&uuid, "1.0").unwrap();
+    assert_eq!(registry.scheduler.entries().len(), 2);
+
+    registry.uninstall(&uuid, "2.0").unwrap();
+    assert!(registry.scheduler.entries().is_empty());
+
+    let loaded = Scheduler::load(&registry_dir.path().to_string_lossy());
+    assert!(loaded.entries().is_empty());
+}
+
+#[test]
+fn schedule_bad_run_level() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "never");
+
+    let service = mock_service(registry);
+
+    let mutation = format!(
+        r#"mutation {{
+        addSchedule(uuid: "{}", runLevel: "OnFriday") {{ id }}
+    }}"#,
+        uuid
+    );
+
+    let expected = "{\"errs\":\"{\\\"message\\\":\\\"Unknown run level: OnFriday\\\",\\\"locations\\\":[{\\\"line\\\":2,\\\"column\\\":9}],\\\"path\\\":[\\\"addSchedule\\\"]}\",\"msg\":null}";
+
+    assert_eq!(service.process(mutation), expected);
+}
+
+#[test]
+fn schedule_query() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "never");
+
+    let service = mock_service(registry);
+
+    let mutation = format!(
+        r#"mutation {{
+        addSchedule(uuid: "{}", time: 1000, period: 600, args: ["--fast"], missed: "run-once") {{
+            uuid, nextRun, period, runLevel, args, missed, lastRun
+        }}
+    }}"#,
+        uuid
+    );
+
+    let expected = json!({
+        "errs": "",
+        "msg": {
+            "addSchedule": {
+                "uuid": uuid,
+                "nextRun": 1000,
+                "period": 600,
+                "runLevel": "OnCommand",
+                "args": ["--fast"],
+                "missed": "run-once",
+                "lastRun": null
+            }
+        }
+    })
+    .to_string();
+
+    assert_eq!(service.process(mutation), expected);
+
+    let query = r#"{
+        schedule { uuid, nextRun }
+    }"#;
+
+    let expected = json!({
+        "errs": "",
+        "msg": {
+            "schedule": [{
+                "uuid": uuid,
+                "nextRun": 1000
+            }]
+        }
+    })
+    .to_string();
+
+    assert_eq!(service.process(query.to_owned()), expected);
+}
+
+#[test]
+fn schedule_bad_period() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+    let uuid = register_app(&registry, "exit 0", "never");
+
+    let service = mock_service(registry);
+
+    let mutation = format!(
+        r#"mutation {{
+        addSchedule(uuid: "{}", period: 0) {{ id }}
+    }}"#,
+        uuid
+    );
+
+    let expected = "{\"errs\":\"{\\\"message\\\":\\\"Period must be greater than zero\\\",\\\"locations\\\":[{\\\"line\\\":2,\\\"column\\\":9}],\\\"path\\\":[\\\"addSchedule\\\"]}\",\"msg\":null}";
+
+    assert_eq!(service.process(mutation), expected);
+}
+
+#[test]
+fn remove_missing() {
+    let registry_dir = TempDir::new().unwrap();
+    let registry = new_registry(&registry_dir);
+
+    assert_eq!(
+        registry.scheduler.remove("fake-id"),
+        Err("Schedule entry fake-id does not exist".to_owned())
+    );
+}
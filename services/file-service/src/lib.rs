@@ -19,15 +19,51 @@ extern crate file_protocol;
 extern crate kubos_system;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_derive;
 extern crate simplelog;
 
 use file_protocol::{FileProtocol, State};
-use kubos_system::Config as ServiceConfig;
+use kubos_system::{Config as ServiceConfig, ConfigChange};
+use std::sync::mpsc::Receiver;
 use std::thread;
 use std::time::Duration;
 
+/// Settings read from the `[file-transfer-service]` config section
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FileServiceConfig {
+    /// Directory prefix used for temporary/intermediate storage
+    #[serde(default)]
+    pub storage_dir: Option<String>,
+    /// Number of seconds to wait for the next message of a transaction
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+fn default_timeout() -> u64 {
+    2
+}
+
 // We need this in this lib.rs file so we can build integration tests
 pub fn recv_loop(config: ServiceConfig) -> Result<(), String> {
+    run(config, None)
+}
+
+/// Same as `recv_loop`, but also applies any config changes received on `updates`.
+///
+/// New settings are used by transactions started after the change arrives.
+/// Transactions which are already in progress keep using the settings they started with.
+pub fn recv_loop_with_updates(
+    config: ServiceConfig,
+    updates: Receiver<ConfigChange>,
+) -> Result<(), String> {
+    run(config, Some(updates))
+}
+
+fn run(config: ServiceConfig, updates: Option<Receiver<ConfigChange>>) -> Result<(), String> {
+    let mut settings: FileServiceConfig = config.deserialize().map_err(|err| err.to_string())?;
+
     // Get and bind our UDP listening socket
     let host = config.hosturl();
     let c_protocol = cbor_protocol::Protocol::new(host.clone());
@@ -36,28 +72,21 @@ pub fn recv_loop(config: ServiceConfig) -> Result<(), String> {
     let mut host_parts = host.split(':').map(|val| val.to_owned());
     let host_ip = host_parts.next().unwrap();
 
-    // Get the storage directory prefix that we'll be using for our
-    // temporary/intermediate storage location
-    let prefix = match config.get("storage_dir") {
-        Some(val) => val.as_str().and_then(|str| Some(str.to_owned())),
-        None => None,
-    };
-
-    let timeout = config
-        .get("timeout")
-        .and_then(|val| {
-            val.as_integer()
-                .and_then(|num| Some(Duration::from_secs(num as u64)))
-        })
-        .unwrap_or(Duration::from_secs(2));
-
     loop {
         // Listen on UDP port
         let (source, first_message) = c_protocol.recv_message_peer()?;
 
-        let prefix_ref = prefix.clone();
+        if let Some(ref updates) = updates {
+            for change in updates.try_iter() {
+                apply_change(&mut settings, change);
+            }
+        }
+
+        // Get the storage directory prefix that we'll be using for our
+        // temporary/intermediate storage location
+        let prefix_ref = settings.storage_dir.clone();
         let host_ref = host_ip.clone();
-        let timeout_ref = timeout.clone();
+        let timeout_ref = Duration::from_secs(settings.timeout);
 
         // Break the processing work off into its own thread so we can
         // listen for requests from other clients
@@ -86,3 +115,20 @@ pub fn recv_loop(config: ServiceConfig) -> Result<(), String> {
         });
     }
 }
+
+fn apply_change(settings: &mut FileServiceConfig, change: ConfigChange) {
+    for key in change.restart_required.iter() {
+        warn!(
+            "Config value '{}' changed. Restart the service to apply it",
+            key
+        );
+    }
+
+    match change.config.deserialize() {
+        Ok(new_settings) => {
+            info!("Applying updated config: {:?}", new_settings);
+            *settings = new_settings;
+        }
+        Err(err) => warn!("Ignoring updated config: {}", err),
+    }
+}
@@ -25,9 +25,11 @@ extern crate log;
 extern crate simplelog;
 
 use file_service::*;
-use kubos_system::Config as ServiceConfig;
+use kubos_system::{Config as ServiceConfig, ConfigWatcher};
 use simplelog::*;
 use std::fs::File;
+use std::process;
+use std::thread;
 
 fn main() {
     let mut loggers: Vec<Box<SharedLogger>> = vec![];
@@ -49,11 +51,29 @@ fn main() {
         _ => {}
     }
 
-    let config = ServiceConfig::new("file-transfer-service");
+    let config = match ServiceConfig::try_new("file-transfer-service") {
+        Ok(config) => config,
+        Err(err) => {
+            error!("Failed to load service config: {}", err);
+            process::exit(1);
+        }
+    };
 
     info!("Starting file transfer service");
+    info!("Effective config:\n{}", config);
 
-    match recv_loop(config) {
+    // Pick up changes to the timeout and storage directory without restarting
+    let watcher = ConfigWatcher::new("file-transfer-service");
+    let updates = watcher.subscribe();
+    let errors = watcher.errors();
+    watcher.start();
+    thread::spawn(move || {
+        for err in errors {
+            warn!("Ignoring changes to the config file: {}", err);
+        }
+    });
+
+    match recv_loop_with_updates(config, updates) {
         Ok(()) => warn!("Service listener loop exited successfully?"),
         Err(err) => error!("Service listener exited early: {}", err),
     }
@@ -59,6 +59,106 @@
 //! Note - the `service-name` used in the sections must match the name used when creating
 //! the `Config` instance inside your service.
 //!
+//! ### Large Responses
+//!
+//! Requests may be up to the maximum UDP payload size (65507 bytes). By default, each
+//! response is sent back to the client in a single UDP datagram. If the communication
+//! link can't handle datagrams of that size, the optional `chunk_size` value may be
+//! specified in the `[service-name]` section:
+//!
+//! ```toml,ignore
+//! [service-name]
+//! chunk_size = 1024
+//! ```
+//!
+//! Any response larger than `chunk_size` bytes will then be split into multiple datagrams.
+//! Each fragment starts with a five byte header: the `FRAGMENT_MARKER` byte (`0xFF`),
+//! followed by the fragment's sequence number and the total number of fragments, each as
+//! a big-endian `u16`. The remaining bytes are the next piece of the JSON response.
+//! Responses are split and reassembled by the functions shared through `kubos_system`, so the
+//! `kubos_app::query` function reassembles these fragments automatically. A response which
+//! would need more than 65535 fragments is replaced with an error.
+//!
+//! ### Service Metrics
+//!
+//! Every service's query schema automatically includes the reserved `_service` field,
+//! which reports the health and request metrics of the service process:
+//!
+//! ```graphql
+//! {
+//!     _service {
+//!         uptime,
+//!         requestCount,
+//!         errorCount,
+//!         lastError,
+//!         averageLatency,
+//!         version
+//!     }
+//! }
+//! ```
+//!
+//! ### Request Logging
+//!
+//! Each request is logged through the `log` crate with the address of the client which sent it,
+//! the query text and variables, how long it took to process and any errors it produced.
+//! If the optional `[service-name.log]` section is present, the service will set up a logger
+//! which sends these messages to either the system log or a rotating log file:
+//!
+//! ```toml,ignore
+//! [service-name.log]
+//! # Either "syslog" (the default) or "file"
+//! target = "file"
+//! # The most verbose level of messages to record
+//! level = "info"
+//! # Hide string values in logged queries and variables, and the messages of logged errors
+//! redact = true
+//! # Options only used by the "file" target
+//! path = "/var/log/kubos/service-name.log"
+//! max_size = 1048576
+//! max_files = 5
+//! ```
+//!
+//! Services which set up their own logger before creating the `Service` will keep using it.
+//!
+//! ### Persistent Storage
+//!
+//! Values saved with `Context::set` are kept in memory and lost when the service restarts.
+//! Values which need to survive a restart can be saved with `Context::set_persistent` instead.
+//! If the optional `storage_dir` value is given, these are also saved to a file in that
+//! directory and loaded again the next time the service starts:
+//!
+//! ```toml,ignore
+//! [service-name]
+//! storage_dir = "/home/system/var/service-name"
+//! ```
+//!
+//! Every change to a persistent value is written atomically, so the file always holds a
+//! complete set of values, even if the system loses power partway through a write. Since each
+//! change rewrites the file, persistent values should be reserved for state which changes
+//! rarely. `set_persistent` returns an error if the value couldn't be saved, in which case the
+//! previous value is kept.
+//!
+//! ### Concurrent Requests
+//!
+//! By default, `Service::start` processes requests one at a time. Services with long-running
+//! queries or mutations may instead use `Service::start_concurrent`, which processes requests
+//! on a fixed pool of worker threads. In this mode, the subsystem must be safe to share between
+//! threads (wrapping it in a `Mutex` is the simplest way to do so).
+//!
+//! The optional `workers` value sets how many requests are processed at once (4, by default).
+//! A request which arrives while every worker is busy is answered with an error.
+//!
+//! The optional `request_timeout` value sets the number of seconds a request may take before
+//! the service gives up waiting on it and sends an error response. The request still runs to
+//! completion, keeping its worker busy until it does. Both values only apply to services
+//! started with `start_concurrent`:
+//!
+//! ```toml,ignore
+//! [service-name]
+//! workers = 4
+//! request_timeout = 10
+//! ```
+//!
 //! ### Examples
 //!
 //! # Creating and starting a simple service.
@@ -108,15 +208,27 @@
 #[cfg(test)]
 #[macro_use]
 extern crate failure;
+#[macro_use]
 extern crate juniper;
 extern crate serde;
 #[macro_use]
+extern crate serde_derive;
+#[macro_use]
 extern crate serde_json;
 
 extern crate kubos_system;
+#[macro_use]
+extern crate log;
+#[cfg(test)]
+extern crate tempfile;
 
+mod logging;
 mod macros;
+mod metrics;
 mod service;
+mod storage;
 
-pub use kubos_system::Config;
-pub use service::{Context, Service};
+pub use kubos_system::{
+    Config, ConfigChange, ConfigWatcher, FRAGMENT_HEADER_SIZE, FRAGMENT_MARKER, MAX_DATAGRAM_SIZE,
+};
+pub use service::{Context, Service, SERVICE_CONFIG_KEYS};
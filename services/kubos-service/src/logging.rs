@@ -0,0 +1,499 @@
+//
+// Copyright (C) 2018 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use kubos_system::Config;
+use log::{self, Level, LevelFilter, Log, Metadata, Record};
+use serde_json::{Deserializer, Value};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The default location of the system log socket
+const SYSLOG_PATH: &str = "/dev/log";
+/// The syslog facility used for all messages (LOG_DAEMON)
+const SYSLOG_FACILITY: u8 = 3;
+/// The default maximum size of a log file before it is rotated
+const DEFAULT_MAX_SIZE: u64 = 1024 * 1024;
+/// The default number of rotated log files to keep
+const DEFAULT_MAX_FILES: u32 = 5;
+
+/// Request logging options, read from the optional `[service-name.log]` config section
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogConfig {
+    /// Whether string values in logged queries should be hidden
+    pub redact: bool,
+}
+
+/// Sets up the global logger for a service, if the `[service-name.log]` config section is present.
+/// Messages are identified with the name of the service's executable.
+///
+/// Returns the request logging options which the service should use.
+pub fn init(config: &Config) -> LogConfig {
+    let settings = match config.get("log") {
+        Some(settings) => settings,
+        None => return LogConfig { redact: false },
+    };
+
+    let name = program_name();
+    let name = name.as_str();
+
+    let log_config = LogConfig {
+        redact: settings
+            .get("redact")
+            .and_then(|val| val.as_bool())
+            .unwrap_or(false),
+    };
+
+    let level = settings
+        .get("level")
+        .and_then(|val| val.as_str().map(|level| level.to_owned()))
+        .unwrap_or("info".to_owned());
+    let level = match LevelFilter::from_str(&level) {
+        Ok(level) => level,
+        Err(_) => {
+            eprintln!("Unknown log level '{}', defaulting to 'info'", level);
+            LevelFilter::Info
+        }
+    };
+
+    let target = settings
+        .get("target")
+        .and_then(|val| val.as_str().map(|target| target.to_owned()))
+        .unwrap_or("syslog".to_owned());
+
+    let logger: Box<Log> = match target.as_ref() {
+        "syslog" => Box::new(SyslogLogger::new(name, SYSLOG_PATH, level)),
+        "file" => {
+            let path = settings
+                .get("path")
+                .and_then(|val| val.as_str().map(|path| path.to_owned()))
+                .unwrap_or(format!("/var/log/kubos/{}.log", name));
+            let max_size = settings
+                .get("max_size")
+                .and_then(|val| val.as_integer())
+                .map(|size| size as u64)
+                .unwrap_or(DEFAULT_MAX_SIZE);
+            let max_files = settings
+                .get("max_files")
+                .and_then(|val| val.as_integer())
+                .map(|count| count as u32)
+                .unwrap_or(DEFAULT_MAX_FILES);
+
+            match RotatingFileLogger::new(&path, max_size, max_files, level) {
+                Ok(logger) => Box::new(logger),
+                Err(err) => {
+                    eprintln!("Failed to open log file {}: {}", path, err);
+                    return log_config;
+                }
+            }
+        }
+        other => {
+            eprintln!("Unknown log target '{}'. Expected 'syslog' or 'file'", other);
+            return log_config;
+        }
+    };
+
+    // Services which have already set up their own logger keep it
+    if log::set_boxed_logger(logger).is_ok() {
+        log::set_max_level(level);
+    }
+
+    log_config
+}
+
+/// Hides the contents of all string literals in a GraphQL query, so that
+/// the structure of the request can be logged without exposing its values
+pub fn redact(query: &str) -> String {
+    let mut result = String::with_capacity(query.len());
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in query.chars() {
+        if in_string {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => {
+                    in_string = false;
+                    result.push_str("***\"");
+                }
+                _ => escaped = false,
+            }
+        } else {
+            if c == '"' {
+                in_string = true;
+            }
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+/// Hides the contents of all string values in a request's variables, like `redact`
+/// does for a query. Object keys are kept, so the structure of the variables can still be seen
+pub fn redact_value(value: &Value) -> Value {
+    match value {
+        Value::String(_) => Value::String("***".to_owned()),
+        Value::Array(items) => Value::Array(items.iter().map(redact_value).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), redact_value(value)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Hides the messages of the errors returned for a request, which may echo its values.
+///
+/// The errors are a sequence of JSON values, as produced by the service. The `locations`
+/// and `path` of each error are kept, so it can still be seen which part of the request failed.
+/// Errors which can't be parsed are redacted like a query.
+pub fn redact_errors(errors: &str) -> String {
+    let parsed: Result<Vec<Value>, _> = Deserializer::from_str(errors).into_iter().collect();
+    match parsed {
+        Ok(values) => values
+            .iter()
+            .map(|value| redact_error(value).to_string())
+            .collect(),
+        Err(_) => redact(errors),
+    }
+}
+
+fn redact_error(value: &Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.iter().map(redact_error).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| match key.as_str() {
+                    "locations" | "path" => (key.clone(), value.clone()),
+                    _ => (key.clone(), redact_value(value)),
+                })
+                .collect(),
+        ),
+        other => redact_value(other),
+    }
+}
+
+fn program_name() -> String {
+    env::args()
+        .next()
+        .and_then(|arg| {
+            Path::new(&arg)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .unwrap_or("kubos-service".to_owned())
+}
+
+fn timestamp() -> String {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(time) => format!("{}.{:03}", time.as_secs(), time.subsec_nanos() / 1_000_000),
+        Err(_) => String::from("0.000"),
+    }
+}
+
+/// Logger which sends messages to the system log daemon
+pub struct SyslogLogger {
+    name: String,
+    path: String,
+    level: LevelFilter,
+    socket: Mutex<Option<UnixDatagram>>,
+}
+
+impl SyslogLogger {
+    pub fn new(name: &str, path: &str, level: LevelFilter) -> Self {
+        SyslogLogger {
+            name: name.to_owned(),
+            path: path.to_owned(),
+            level,
+            socket: Mutex::new(None),
+        }
+    }
+
+    /// Formats a message in the traditional BSD syslog format
+    fn format(&self, level: Level, message: &str) -> String {
+        let severity = match level {
+            Level::Error => 3,
+            Level::Warn => 4,
+            Level::Info => 6,
+            Level::Debug | Level::Trace => 7,
+        };
+
+        format!(
+            "<{}>{}[{}]: {}",
+            SYSLOG_FACILITY * 8 + severity,
+            self.name,
+            process::id(),
+            message
+        )
+    }
+
+    fn send(&self, message: &str) -> io::Result<()> {
+        let mut socket = self.socket.lock().unwrap_or_else(|err| err.into_inner());
+
+        if socket.is_none() {
+            let new_socket = UnixDatagram::unbound()?;
+            new_socket.connect(&self.path)?;
+            *socket = Some(new_socket);
+        }
+
+        let result = match socket.as_ref() {
+            Some(sock) => sock.send(message.as_bytes()).map(|_| ()),
+            None => Ok(()),
+        };
+
+        // Reconnect on the next message if the log daemon has been restarted
+        if result.is_err() {
+            *socket = None;
+        }
+
+        result
+    }
+}
+
+impl Log for SyslogLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = self.format(record.level(), &format!("{}", record.args()));
+        if let Err(err) = self.send(&message) {
+            eprintln!("Failed to write to syslog: {}", err);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Logger which writes messages to a file, rotating it once it grows too large.
+///
+/// When the log file exceeds `max_size` bytes, it is renamed to `<path>.1` (with older files
+/// being shifted to `<path>.2`, `<path>.3`, etc) and a new log file is started.
+/// At most `max_files` old log files are kept.
+pub struct RotatingFileLogger {
+    path: String,
+    max_size: u64,
+    max_files: u32,
+    level: LevelFilter,
+    file: Mutex<(File, u64)>,
+}
+
+impl RotatingFileLogger {
+    pub fn new(path: &str, max_size: u64, max_files: u32, level: LevelFilter) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+
+        Ok(RotatingFileLogger {
+            path: path.to_owned(),
+            max_size,
+            max_files,
+            level,
+            file: Mutex::new((file, size)),
+        })
+    }
+
+    fn rotate(&self) -> io::Result<File> {
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                let from = format!("{}.{}", self.path, index);
+                if fs::metadata(&from).is_ok() {
+                    fs::rename(&from, format!("{}.{}", self.path, index + 1))?;
+                }
+            }
+            fs::rename(&self.path, format!("{}.1", self.path))?;
+        }
+
+        OpenOptions::new().create(true).append(true).open(&self.path)
+    }
+
+    fn write(&self, line: &str) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+
+        if file.1 + line.len() as u64 > self.max_size && file.1 > 0 {
+            file.0 = self.rotate()?;
+            file.1 = 0;
+        }
+
+        file.0.write_all(line.as_bytes())?;
+        file.1 += line.len() as u64;
+        Ok(())
+    }
+}
+
+impl Log for RotatingFileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            timestamp(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        if let Err(err) = self.write(&line) {
+            eprintln!("Failed to write to log file {}: {}", self.path, err);
+        }
+    }
+
+    fn flush(&self) {
+        let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+        let _ = file.0.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn redact_strings() {
+        assert_eq!(
+            redact(r#"mutation { issueRawCommand(command: "41", rxLen: 2) { success } }"#),
+            r#"mutation { issueRawCommand(command: "***", rxLen: 2) { success } }"#
+        );
+    }
+
+    #[test]
+    fn redact_escaped_quotes() {
+        assert_eq!(
+            redact(r#"{ echo(value: "a \"quoted\" value") }"#),
+            r#"{ echo(value: "***") }"#
+        );
+    }
+
+    #[test]
+    fn redact_variables() {
+        assert_eq!(
+            redact_value(&json!({"command": "41", "rxLen": 2, "args": ["a", {"key": "b"}]})),
+            json!({"command": "***", "rxLen": 2, "args": ["***", {"key": "***"}]})
+        );
+    }
+
+    #[test]
+    fn redact_error_keeps_location() {
+        let error = json!({
+            "message": "Invalid command: \"41\"",
+            "locations": [{"line": 1, "column": 12}],
+            "path": ["issueRawCommand"]
+        });
+
+        assert_eq!(
+            redact_errors(&format!("{}{}", error, error)),
+            format!(
+                "{}{}",
+                json!({
+                    "message": "***",
+                    "locations": [{"line": 1, "column": 12}],
+                    "path": ["issueRawCommand"]
+                }),
+                json!({
+                    "message": "***",
+                    "locations": [{"line": 1, "column": 12}],
+                    "path": ["issueRawCommand"]
+                })
+            )
+        );
+    }
+
+    #[test]
+    fn redact_validation_errors() {
+        let errors = json!([{
+            "message": "Unknown argument \"value\"",
+            "locations": [{"line": 1, "column": 8}]
+        }]);
+
+        assert_eq!(
+            redact_errors(&errors.to_string()),
+            json!([{"message": "***", "locations": [{"line": 1, "column": 8}]}]).to_string()
+        );
+    }
+
+    #[test]
+    fn redact_no_strings() {
+        assert_eq!(redact("{ ping }"), "{ ping }");
+    }
+
+    #[test]
+    fn syslog_format() {
+        let logger = SyslogLogger::new("test-service", "/fake/path", LevelFilter::Info);
+
+        assert_eq!(
+            logger.format(Level::Error, "message"),
+            format!("<27>test-service[{}]: message", process::id())
+        );
+        assert_eq!(
+            logger.format(Level::Info, "message"),
+            format!("<30>test-service[{}]: message", process::id())
+        );
+    }
+
+    #[test]
+    fn rotating_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("service.log");
+        let path_str = path.to_string_lossy().to_string();
+
+        let logger = RotatingFileLogger::new(&path_str, 20, 2, LevelFilter::Info).unwrap();
+
+        logger.write("0123456789\n").unwrap();
+        logger.write("abcdefghij\n").unwrap();
+        logger.write("ABCDEFGHIJ\n").unwrap();
+        logger.write("9876543210\n").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "9876543210\n");
+        assert_eq!(
+            fs::read_to_string(format!("{}.1", path_str)).unwrap(),
+            "ABCDEFGHIJ\n"
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{}.2", path_str)).unwrap(),
+            "abcdefghij\n"
+        );
+        assert!(fs::metadata(format!("{}.3", path_str)).is_err());
+    }
+
+    #[test]
+    fn init_no_section() {
+        let config = Config::new_from_str("test-service", "");
+
+        assert_eq!(init(&config), LogConfig { redact: false });
+    }
+}
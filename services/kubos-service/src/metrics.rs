@@ -0,0 +1,159 @@
+//
+// Copyright (C) 2018 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use juniper::meta::MetaType;
+use juniper::{Arguments, ExecutionResult, Executor, FieldResult, GraphQLType, Registry};
+use service::Context;
+use std::time::{Duration, Instant};
+
+/// The name of the field which is automatically added to every service's query schema
+pub const SERVICE_FIELD: &str = "_service";
+
+/// Request statistics gathered by a service while it runs
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    started: Instant,
+    request_count: u64,
+    error_count: u64,
+    last_error: Option<String>,
+    total_latency: Duration,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics {
+            started: Instant::now(),
+            request_count: 0,
+            error_count: 0,
+            last_error: None,
+            total_latency: Duration::from_secs(0),
+        }
+    }
+
+    /// Records a completed request, along with the error it produced (if any)
+    pub fn record(&mut self, latency: Duration, error: Option<String>) {
+        self.request_count += 1;
+        self.total_latency += latency;
+
+        if error.is_some() {
+            self.record_error(error);
+        }
+    }
+
+    /// Records an error which wasn't produced by a completed request (ex. a request timeout)
+    pub fn record_error(&mut self, error: Option<String>) {
+        self.error_count += 1;
+        self.last_error = error;
+    }
+}
+
+/// Snapshot of a service's health and metrics, returned by the `_service` query
+pub struct ServiceInfo(Metrics);
+
+graphql_object!(ServiceInfo: () as "ServiceInfo" |&self| {
+    description: "Health and request metrics of the service process"
+
+    field uptime() -> FieldResult<i32>
+        as "Number of seconds since the service started"
+    {
+        Ok(self.0.started.elapsed().as_secs() as i32)
+    }
+
+    field request_count() -> FieldResult<i32>
+        as "Number of requests processed since the service started"
+    {
+        Ok(self.0.request_count as i32)
+    }
+
+    field error_count() -> FieldResult<i32>
+        as "Number of requests which have produced errors since the service started"
+    {
+        Ok(self.0.error_count as i32)
+    }
+
+    field last_error() -> FieldResult<Option<String>>
+        as "The most recent error produced by a request"
+    {
+        Ok(self.0.last_error.clone())
+    }
+
+    field average_latency() -> FieldResult<f64>
+        as "Average time taken to process a request, in milliseconds"
+    {
+        if self.0.request_count == 0 {
+            return Ok(0.0);
+        }
+
+        let total = self.0.total_latency;
+        let total_ms = total.as_secs() as f64 * 1000.0 + total.subsec_nanos() as f64 / 1_000_000.0;
+        Ok(total_ms / self.0.request_count as f64)
+    }
+
+    field version() -> FieldResult<String>
+        as "Version of the kubos-service library the service was built with"
+    {
+        Ok(env!("CARGO_PKG_VERSION").to_owned())
+    }
+});
+
+/// Wrapper around a service's root query object which adds the reserved `_service`
+/// field to the schema, so that every service can be monitored in the same way
+pub struct ServiceQuery<Q>(pub Q);
+
+impl<Q, S> GraphQLType for ServiceQuery<Q>
+where
+    Q: GraphQLType<Context = Context<S>>,
+{
+    type Context = Context<S>;
+    type TypeInfo = Q::TypeInfo;
+
+    fn name(info: &Self::TypeInfo) -> Option<&str> {
+        Q::name(info)
+    }
+
+    fn meta<'r>(info: &Self::TypeInfo, registry: &mut Registry<'r>) -> MetaType<'r> {
+        let service_field = registry
+            .field::<ServiceInfo>(SERVICE_FIELD, &())
+            .description("Health and request metrics of the service process");
+
+        match Q::meta(info, registry) {
+            MetaType::Object(mut meta) => {
+                meta.fields.push(service_field);
+                MetaType::Object(meta)
+            }
+            meta => meta,
+        }
+    }
+
+    fn resolve_field(
+        &self,
+        info: &Self::TypeInfo,
+        field_name: &str,
+        arguments: &Arguments,
+        executor: &Executor<Self::Context>,
+    ) -> ExecutionResult {
+        if field_name == SERVICE_FIELD {
+            let info = ServiceInfo(executor.context().metrics());
+            return executor.resolve_with_ctx(&(), &info);
+        }
+
+        self.0.resolve_field(info, field_name, arguments, executor)
+    }
+
+    fn concrete_type_name(&self, context: &Self::Context) -> String {
+        self.0.concrete_type_name(context)
+    }
+}
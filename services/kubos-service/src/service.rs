@@ -14,18 +14,97 @@
 // limitations under the License.
 //
 
-use juniper::{execute, Context as JuniperContext, GraphQLType, RootNode, Variables};
-use kubos_system::Config;
+use juniper::{execute, Context as JuniperContext, GraphQLType, InputValue, RootNode, Variables};
+use kubos_system::{fragment_response, Config, FRAGMENT_HEADER_SIZE, MAX_DATAGRAM_SIZE};
+use logging::{self, LogConfig};
+use metrics::{Metrics, ServiceQuery};
 use serde_json;
-use std::cell::RefCell;
-use std::collections::HashMap;
 use std::net::{SocketAddr, UdpSocket};
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use storage::Storage;
+
+/// Config keys read by `Service::new`. Changes to these (or to `addr`) only take
+/// effect once the service restarts, so they should be passed to
+/// `ConfigWatcher::restart_required` by services which watch their config.
+pub const SERVICE_CONFIG_KEYS: &[&str] = &[
+    "chunk_size",
+    "request_timeout",
+    "workers",
+    "storage_dir",
+    "log",
+];
+
+/// Number of requests a concurrent service processes at once, unless its config sets `workers`
+const DEFAULT_WORKERS: usize = 4;
+
+/// A GraphQL request sent as a JSON envelope, rather than as a plain query string.
+///
+/// This allows clients to supply query variables and to select which of the
+/// operations in the query document should be executed:
+///
+/// ```json
+/// {
+///     "query": "query Power($id: Int!) { power(id: $id) { state } }",
+///     "variables": { "id": 1 },
+///     "operationName": "Power",
+///     "id": "1234-5"
+/// }
+/// ```
+///
+/// The optional `id` is echoed back in the response, so that clients can tell
+/// which request a response belongs to.
+#[derive(Debug, Deserialize)]
+struct Request {
+    query: String,
+    #[serde(default)]
+    variables: Option<InputValue>,
+    #[serde(rename = "operationName", default)]
+    operation_name: Option<String>,
+    #[serde(default)]
+    id: Option<serde_json::Value>,
+}
+
+impl Request {
+    /// Parses an incoming request. Anything which isn't a valid JSON envelope
+    /// is treated as a plain GraphQL query string.
+    fn parse(request: String) -> Request {
+        match serde_json::from_str::<Request>(&request) {
+            Ok(envelope) => envelope,
+            Err(_) => Request {
+                query: request,
+                variables: None,
+                operation_name: None,
+                id: None,
+            },
+        }
+    }
+
+    fn variables(&self) -> Variables {
+        self.variables
+            .as_ref()
+            .and_then(|vars| {
+                vars.to_object_value().map(|obj| {
+                    obj.into_iter()
+                        .map(|(key, val)| (key.to_owned(), val.clone()))
+                        .collect()
+                })
+            })
+            .unwrap_or_default()
+    }
+}
 
 /// Context struct used by a service to provide Juniper context,
 /// subsystem access and persistent storage.
 pub struct Context<T> {
     subsystem: T,
-    storage: RefCell<HashMap<String, String>>,
+    storage: Storage,
+    metrics: Mutex<Metrics>,
 }
 
 impl<T> JuniperContext for Context<T> {}
@@ -42,22 +121,43 @@ impl<T> Context<T> {
     ///
     /// `name` - Key to search for in storage
     pub fn get(&self, name: &str) -> String {
-        let stor = self.storage.borrow();
-        match stor.get(&name.to_string()) {
-            Some(s) => s.clone(),
-            None => "".to_string(),
-        }
+        self.storage.get(name).unwrap_or_default()
     }
 
     /// Sets a value in the context's storage
     ///
+    /// The value is only kept in memory, and is lost when the service restarts.
+    /// Values which need to survive a restart should be stored with `set_persistent` instead.
+    ///
     /// # Arguments
     ///
     /// `key` - Key to store value under
     /// `value` - Value to store
     pub fn set(&self, key: &str, value: &str) {
-        let mut stor = self.storage.borrow_mut();
-        stor.insert(key.to_string(), value.to_string());
+        if let Err(err) = self.storage.set(key, value) {
+            error!(
+                "Failed to remove '{}' from persistent storage: {}",
+                key, err
+            );
+        }
+    }
+
+    /// Sets a value in the context's storage, saving it to disk if the service
+    /// has a `storage_dir` configured
+    ///
+    /// Every call rewrites the storage file, so this should only be used for values which
+    /// change rarely. If the value can't be saved, the error is returned and the
+    /// previous value is kept.
+    ///
+    /// # Arguments
+    ///
+    /// `key` - Key to store value under
+    /// `value` - Value to store
+    pub fn set_persistent(&self, key: &str, value: &str) -> io::Result<()> {
+        self.storage.set_persistent(key, value).map_err(|err| {
+            error!("Failed to save '{}' to persistent storage: {}", key, err);
+            err
+        })
     }
 
     /// Clears a single key/value from storage
@@ -66,13 +166,46 @@ impl<T> Context<T> {
     ///
     /// `key` - Key to clear (along with corresponding value)
     pub fn clear(&self, name: &String) {
-        let mut storage = self.storage.borrow_mut();
-        storage.remove(name);
+        if let Err(err) = self.storage.remove(name) {
+            error!(
+                "Failed to remove '{}' from persistent storage: {}",
+                name, err
+            );
+        }
     }
 
     /// Clears all key/value pairs from storage
     pub fn clear_all(&self) {
-        self.storage.borrow_mut().clear();
+        if let Err(err) = self.storage.clear() {
+            error!("Failed to clear persistent storage: {}", err);
+        }
+    }
+
+    /// Returns a snapshot of the service's request metrics
+    pub(crate) fn metrics(&self) -> Metrics {
+        self.metrics
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    fn update_metrics<F>(&self, update: F)
+    where
+        F: FnOnce(&mut Metrics),
+    {
+        update(&mut self.metrics.lock().unwrap_or_else(|err| err.into_inner()));
+    }
+}
+
+impl<T> Context<Mutex<T>> {
+    /// Locks the context's subsystem for use by the current request
+    ///
+    /// Services running in concurrent mode (see `Service::start_concurrent`) should wrap their
+    /// subsystem in a `Mutex` so that hardware calls from simultaneous requests can't
+    /// interleave. Requests which don't touch the subsystem, like `ping`, never need to
+    /// wait for the lock.
+    pub fn lock_subsystem(&self) -> MutexGuard<T> {
+        self.subsystem.lock().unwrap_or_else(|err| err.into_inner())
     }
 }
 
@@ -103,8 +236,10 @@ where
     Mutation: GraphQLType<Context = Context<S>> + Send + Sync + 'static,
 {
     config: Config,
-    root_node: RootNode<'a, Query, Mutation>,
+    root_node: RootNode<'a, ServiceQuery<Query>, Mutation>,
     context: Context<S>,
+    chunk_size: Option<usize>,
+    log_config: LogConfig,
 }
 
 impl<'a, Query, Mutation, S> Service<'a, Query, Mutation, S>
@@ -121,19 +256,29 @@ where
     /// `query` - The root query struct holding all other GraphQL queries.
     /// `mutation` - The root mutation struct holding all other GraphQL mutations.
     pub fn new(config: Config, subsystem: S, query: Query, mutation: Mutation) -> Self {
+        let chunk_size = get_chunk_size(&config);
+        let log_config = logging::init(&config);
+        let storage = get_storage(&config);
+
         Service {
             config: config,
-            root_node: RootNode::new(query, mutation),
+            root_node: RootNode::new(ServiceQuery(query), mutation),
             context: Context {
                 subsystem: subsystem,
-                storage: RefCell::new(HashMap::new()),
+                storage,
+                metrics: Mutex::new(Metrics::new()),
             },
+            chunk_size,
+            log_config,
         }
     }
 
     /// Starts the service's GraphQL/UDP server. This function runs
     /// without return.
     ///
+    /// Requests are processed one at a time, each running to completion.
+    /// The `request_timeout` and `workers` config values only apply to `start_concurrent`.
+    ///
     /// # Panics
     ///
     /// The UDP interface will panic if the ip address and port provided
@@ -145,37 +290,108 @@ where
         let socket = UdpSocket::bind(&addr).unwrap();
         println!("Listening on: {}", socket.local_addr().unwrap());
 
-        let mut buf = [0; 4096];
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
         loop {
             // Wait for an incoming message
             let (size, peer) = socket
                 .recv_from(&mut buf)
                 .expect("Failed to receive a message");
             if let Ok(query_string) = String::from_utf8(buf[0..(size)].to_vec()) {
-                //println!(
-                //  "[{}] <- [{}] {}",
-                //  peer,
-                //  socket.local_addr().unwrap(),
-                //  &query_string
-                //);
-
                 // Go process the request
-                let res = self.process(query_string);
+                let res = self.handle(Request::parse(query_string), &peer);
 
                 // And then send the response back
-                let _amt = socket.send_to(&res.as_bytes(), &peer);
-                //println!("[{}] -> [{}] {}", socket.local_addr().unwrap(), peer, &res);
+                self.respond(&socket, &peer, &res);
+            }
+        }
+    }
+
+    // Send a response back to the client, split into fragments
+    // if it won't fit within the configured chunk size
+    fn respond(&self, socket: &UdpSocket, peer: &SocketAddr, response: &str) {
+        let datagrams = match fragment_response(response.as_bytes(), self.chunk_size) {
+            Ok(datagrams) => datagrams,
+            Err(err) => {
+                warn!("peer={} status=error errors={:?}", peer, err.to_string());
+                let response = json!({
+                    "msg": null,
+                    "errs": json!({ "message": err.to_string() }).to_string()
+                });
+                vec![response.to_string().into_bytes()]
             }
+        };
+
+        for datagram in datagrams {
+            if socket.send_to(&datagram, peer).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Processes a GraphQL request
+    ///
+    /// The request may either be a plain GraphQL query string, or a JSON object
+    /// containing the `query` along with optional `variables`, `operationName` and `id` fields
+    ///
+    /// Every service's query schema automatically includes a `_service` field, which returns
+    /// the service's uptime and request metrics
+    pub fn process(&self, request: String) -> String {
+        self.process_request(&Request::parse(request)).0
+    }
+
+    // Process a request from a client, logging who sent it, what was requested,
+    // how long it took and whether it succeeded
+    fn handle(&self, request: Request, peer: &SocketAddr) -> String {
+        let start = Instant::now();
+
+        let (response, error) = self.process_request(&request);
+
+        let elapsed = start.elapsed();
+        let duration_ms = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_nanos() / 1_000_000);
+        let variables = request
+            .variables
+            .as_ref()
+            .map(|vars| serde_json::to_value(vars).unwrap_or(serde_json::Value::Null));
+
+        // Values in the query, its variables and any errors which echo them are hidden
+        // when redaction is enabled
+        let (query, variables, error) = match self.log_config.redact {
+            true => (
+                logging::redact(&request.query),
+                variables.map(|vars| logging::redact_value(&vars)),
+                error.map(|err| logging::redact_errors(&err)),
+            ),
+            false => (request.query.clone(), variables, error),
+        };
+        let variables = match variables {
+            Some(vars) => format!(" variables={}", vars),
+            None => String::new(),
+        };
+
+        match error {
+            Some(err) => warn!(
+                "peer={} duration_ms={} status=error query={:?}{} errors={:?}",
+                peer, duration_ms, query, variables, err
+            ),
+            None => info!(
+                "peer={} duration_ms={} status=ok query={:?}{}",
+                peer, duration_ms, query, variables
+            ),
         }
+
+        response
     }
 
-    /// Processes a GraphQL query
-    pub fn process(&self, query: String) -> String {
-        match execute(
-            &query,
-            None,
+    // Execute a parsed request, returning the response along with
+    // the errors it produced (if any)
+    fn process_request(&self, request: &Request) -> (String, Option<String>) {
+        let start = Instant::now();
+
+        let (response, error) = match execute(
+            &request.query,
+            request.operation_name.as_ref().map(|name| name.as_str()),
             &self.root_node,
-            &Variables::new(),
+            &request.variables(),
             &self.context,
         ) {
             Ok((val, errs)) => {
@@ -183,12 +399,679 @@ where
                     .map(|x| serde_json::to_string(&x).unwrap())
                     .collect();
 
-                json!({
-                    "msg": val,
-                    "errs": errs_msg})
-                    .to_string()
+                let error = match errs_msg.is_empty() {
+                    true => None,
+                    false => Some(errs_msg.clone()),
+                };
+
+                let response = with_id(
+                    json!({
+                        "msg": val,
+                        "errs": errs_msg}),
+                    request.id.as_ref(),
+                );
+
+                (response, error)
+            }
+            Err(e) => {
+                let response = serde_json::to_string(&e).unwrap();
+                (response.clone(), Some(response))
+            }
+        };
+
+        let recorded = error.clone();
+        self.context
+            .update_metrics(|metrics| metrics.record(start.elapsed(), recorded));
+
+        (response, error)
+    }
+}
+
+impl<Query, Mutation, S> Service<'static, Query, Mutation, S>
+where
+    Query: GraphQLType<Context = Context<S>, TypeInfo = ()> + Send + Sync + 'static,
+    Mutation: GraphQLType<Context = Context<S>, TypeInfo = ()> + Send + Sync + 'static,
+    S: Send + Sync + 'static,
+{
+    /// Starts the service's GraphQL/UDP server in concurrent mode. This function runs
+    /// without return.
+    ///
+    /// Requests are processed by a fixed pool of worker threads, so a long-running mutation
+    /// won't prevent the service from answering other queries. Because of this, the subsystem
+    /// must be safe to share between threads. Subsystems which aren't can be wrapped in a
+    /// `Mutex` and accessed with `Context::lock_subsystem`.
+    ///
+    /// The optional `workers` config value sets the number of requests processed at once
+    /// (4, by default). Requests which arrive while every worker is busy are answered
+    /// with an error straight away.
+    ///
+    /// If the optional `request_timeout` value (in seconds) is present in the service's config,
+    /// any request which hasn't completed within that time is answered with an error. The
+    /// request itself continues to run to completion, and keeps its worker busy until it does.
+    ///
+    /// # Panics
+    ///
+    /// The UDP interface will panic if the ip address and port provided
+    /// cannot be bound (like if they are already in use), or if for some reason the socket fails
+    /// to receive a message.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust,ignore
+    /// use kubos_service::{Config, Service};
+    /// use std::sync::Mutex;
+    ///
+    /// Service::new(
+    ///     Config::new("example-service"),
+    ///     Mutex::new(model::Subsystem::new()),
+    ///     schema::QueryRoot,
+    ///     schema::MutationRoot,
+    /// ).start_concurrent();
+    /// ```
+    pub fn start_concurrent(self) {
+        let addr = self.config.hosturl().parse::<SocketAddr>().unwrap();
+
+        let socket = UdpSocket::bind(&addr).unwrap();
+        println!("Listening on: {}", socket.local_addr().unwrap());
+
+        let workers = get_workers(&self.config);
+        let timeout = get_request_timeout(&self.config);
+        self.serve_concurrent(socket, workers, timeout);
+    }
+
+    // Receive requests on the socket, handing each one to the pool of workers
+    fn serve_concurrent(self, socket: UdpSocket, workers: usize, timeout: Option<Duration>) {
+        let service = Arc::new(self);
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let (job_sender, job_receiver) = mpsc::channel::<Job>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        // Requests which take too long are answered by the timer instead of their worker
+        let timer = timeout.map(|timeout| {
+            let (sender, receiver) = mpsc::channel();
+            let service = service.clone();
+            let socket = socket.try_clone().expect("Failed to create reply socket");
+            thread::spawn(move || service.expire_requests(&socket, &receiver, timeout));
+            sender
+        });
+
+        for _ in 0..workers {
+            let service = service.clone();
+            let socket = socket.try_clone().expect("Failed to create reply socket");
+            let job_receiver = job_receiver.clone();
+            let in_flight = in_flight.clone();
+            thread::spawn(move || loop {
+                // Only one idle worker waits on the queue at a time
+                let job = match job_receiver.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => return,
+                };
+
+                let res = service.handle(job.request, &job.reply.peer);
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                if job.reply.claim() {
+                    service.respond(&socket, &job.reply.peer, &res);
+                }
+            });
+        }
+
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+        loop {
+            // Wait for an incoming message
+            let (size, peer) = socket
+                .recv_from(&mut buf)
+                .expect("Failed to receive a message");
+            let request = match String::from_utf8(buf[0..(size)].to_vec()) {
+                Ok(query_string) => Request::parse(query_string),
+                Err(_) => continue,
+            };
+
+            // Requests which arrive while every worker is busy are turned away, rather than
+            // queueing up behind requests which may never complete
+            if in_flight.fetch_add(1, Ordering::SeqCst) >= workers {
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                let res = error_response(
+                    &service.context,
+                    "Service busy: too many requests in progress".to_owned(),
+                    request.id.as_ref(),
+                );
+                warn!("peer={} status=busy workers={}", peer, workers);
+                service.respond(&socket, &peer, &res);
+                continue;
+            }
+
+            let reply = Arc::new(Reply {
+                id: request.id.clone(),
+                peer,
+                answered: AtomicBool::new(false),
+            });
+            if let (Some(timer), Some(timeout)) = (timer.as_ref(), timeout) {
+                let _ = timer.send((Instant::now() + timeout, reply.clone()));
+            }
+            let _ = job_sender.send(Job { request, reply });
+        }
+    }
+
+    // Answer each request with an error once its deadline passes, unless its worker
+    // has already answered it. The request itself keeps running on its worker until
+    // it completes, since a thread can't be interrupted.
+    fn expire_requests(
+        &self,
+        socket: &UdpSocket,
+        receiver: &mpsc::Receiver<(Instant, Arc<Reply>)>,
+        timeout: Duration,
+    ) {
+        // Every request gets the same timeout, so deadlines arrive in order
+        let mut pending: VecDeque<(Instant, Arc<Reply>)> = VecDeque::new();
+
+        loop {
+            while pending
+                .front()
+                .map_or(false, |&(_, ref reply)| reply.answered.load(Ordering::SeqCst))
+            {
+                pending.pop_front();
+            }
+
+            let received = match pending.front() {
+                Some(&(deadline, _)) => {
+                    let now = Instant::now();
+                    if deadline <= now {
+                        Err(mpsc::RecvTimeoutError::Timeout)
+                    } else {
+                        receiver.recv_timeout(deadline - now)
+                    }
+                }
+                None => receiver
+                    .recv()
+                    .map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+            };
+
+            match received {
+                Ok(entry) => pending.push_back(entry),
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    let (_, reply) = pending.pop_front().unwrap();
+                    if reply.claim() {
+                        warn!(
+                            "peer={} status=timeout timeout_s={}",
+                            reply.peer,
+                            timeout.as_secs()
+                        );
+                        let res = error_response(
+                            &self.context,
+                            format!("Request timed out after {} seconds", timeout.as_secs()),
+                            reply.id.as_ref(),
+                        );
+                        self.respond(socket, &reply.peer, &res);
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
             }
-            Err(e) => serde_json::to_string(&e).unwrap(),
         }
     }
 }
+
+// A request waiting for one of the workers of a concurrent service
+struct Job {
+    request: Request,
+    reply: Arc<Reply>,
+}
+
+// Where to send the response to a request. A request is answered exactly once,
+// either by its worker or, if it takes too long, by the timer
+struct Reply {
+    id: Option<serde_json::Value>,
+    peer: SocketAddr,
+    answered: AtomicBool,
+}
+
+impl Reply {
+    // Returns true if the caller should send the response
+    fn claim(&self) -> bool {
+        !self.answered.swap(true, Ordering::SeqCst)
+    }
+}
+
+/// Builds the response to a request which couldn't be processed, recording the error
+/// in the service's metrics
+fn error_response<S>(
+    context: &Context<S>,
+    message: String,
+    id: Option<&serde_json::Value>,
+) -> String {
+    let errs = json!({ "message": message }).to_string();
+    context.update_metrics(|metrics| metrics.record_error(Some(errs.clone())));
+
+    with_id(
+        json!({
+            "msg": null,
+            "errs": errs
+        }),
+        id,
+    )
+}
+
+/// Adds the ID of a request (if it has one) to its response
+fn with_id(mut response: serde_json::Value, id: Option<&serde_json::Value>) -> String {
+    if let Some(id) = id {
+        response["id"] = id.clone();
+    }
+    response.to_string()
+}
+
+/// Fetches the optional `workers` value from the service's config: the number of
+/// requests `start_concurrent` processes at once
+fn get_workers(config: &Config) -> usize {
+    match config.get("workers").and_then(|val| val.as_integer()) {
+        Some(workers) if workers > 0 => workers as usize,
+        Some(workers) => {
+            eprintln!(
+                "Ignoring workers {}: must be at least 1. Using {} workers",
+                workers, DEFAULT_WORKERS
+            );
+            DEFAULT_WORKERS
+        }
+        None => DEFAULT_WORKERS,
+    }
+}
+
+/// Fetches the optional `request_timeout` value (in seconds) from the service's config.
+/// This only applies to services started with `start_concurrent`
+fn get_request_timeout(config: &Config) -> Option<Duration> {
+    config
+        .get("request_timeout")
+        .and_then(|val| val.as_integer())
+        .and_then(|secs| match secs {
+            secs if secs > 0 => Some(Duration::from_secs(secs as u64)),
+            _ => None,
+        })
+}
+
+/// Creates the context's storage, persisting it under the optional `storage_dir`
+/// config value if one is given.
+///
+/// If the directory can't be used, the service falls back to in-memory storage
+/// rather than refusing to start.
+fn get_storage(config: &Config) -> Storage {
+    let dir = match config.get("storage_dir") {
+        Some(dir) => match dir.as_str() {
+            Some(dir) => dir.to_owned(),
+            None => {
+                eprintln!("Ignoring storage_dir: must be a string");
+                return Storage::new();
+            }
+        },
+        None => return Storage::new(),
+    };
+
+    match Storage::persistent(&dir) {
+        Ok(storage) => storage,
+        Err(err) => {
+            eprintln!(
+                "Failed to load persistent storage from {}: {}. Using in-memory storage",
+                dir, err
+            );
+            Storage::new()
+        }
+    }
+}
+
+/// Fetches the optional `chunk_size` value from the service's config.
+///
+/// Chunk sizes which can't hold at least one byte of response data after
+/// the fragment header are ignored.
+fn get_chunk_size(config: &Config) -> Option<usize> {
+    let size = config
+        .get("chunk_size")
+        .and_then(|val| val.as_integer())?;
+
+    if size <= FRAGMENT_HEADER_SIZE as i64 {
+        eprintln!(
+            "Ignoring chunk_size {}: must be larger than {} bytes",
+            size, FRAGMENT_HEADER_SIZE
+        );
+        return None;
+    }
+
+    Some(size as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use juniper::FieldResult;
+    use tempfile::TempDir;
+
+    struct QueryRoot;
+
+    graphql_object!(QueryRoot: Context<()> as "Query" |&self| {
+        field ping() -> FieldResult<String> {
+            Ok(String::from("pong"))
+        }
+
+        field echo(value: String) -> FieldResult<String> {
+            Ok(value)
+        }
+
+        field slow() -> FieldResult<bool> {
+            thread::sleep(Duration::from_millis(500));
+            Ok(true)
+        }
+    });
+
+    struct MutationRoot;
+
+    graphql_object!(MutationRoot: Context<()> as "Mutation" |&self| {
+        field noop() -> FieldResult<bool> {
+            Ok(true)
+        }
+    });
+
+    fn test_service() -> Service<'static, QueryRoot, MutationRoot, ()> {
+        Service::new(
+            Config::new_from_str("test-service", ""),
+            (),
+            QueryRoot,
+            MutationRoot,
+        )
+    }
+
+    #[test]
+    fn process_plain_query() {
+        let service = test_service();
+
+        assert_eq!(
+            service.process("{ ping }".to_owned()),
+            json!({"errs": "", "msg": {"ping": "pong"}}).to_string()
+        );
+    }
+
+    #[test]
+    fn process_variables() {
+        let service = test_service();
+        let request = json!({
+            "query": "query Echo($value: String!) { echo(value: $value) }",
+            "variables": { "value": "hello" }
+        });
+
+        assert_eq!(
+            service.process(request.to_string()),
+            json!({"errs": "", "msg": {"echo": "hello"}}).to_string()
+        );
+    }
+
+    #[test]
+    fn process_operation_name() {
+        let service = test_service();
+        let request = json!({
+            "query": "query Ping { ping } mutation Noop { noop }",
+            "operationName": "Noop"
+        });
+
+        assert_eq!(
+            service.process(request.to_string()),
+            json!({"errs": "", "msg": {"noop": true}}).to_string()
+        );
+    }
+
+    #[test]
+    fn process_request_id() {
+        let service = test_service();
+        let request = json!({
+            "query": "{ ping }",
+            "id": "1234-5"
+        });
+
+        assert_eq!(
+            service.process(request.to_string()),
+            json!({"errs": "", "id": "1234-5", "msg": {"ping": "pong"}}).to_string()
+        );
+    }
+
+    #[test]
+    fn process_missing_variable() {
+        let service = test_service();
+        let request = json!({
+            "query": "query Echo($value: String!) { echo(value: $value) }"
+        });
+
+        let response: serde_json::Value =
+            serde_json::from_str(&service.process(request.to_string())).unwrap();
+
+        assert!(response[0]["message"].as_str().unwrap().contains("$value"));
+    }
+
+    // Serve requests concurrently on a local socket, returning a socket to send them from
+    fn concurrent_service(workers: usize, timeout: Option<Duration>) -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.connect(socket.local_addr().unwrap()).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+
+        thread::spawn(move || test_service().serve_concurrent(socket, workers, timeout));
+        client
+    }
+
+    fn recv_json(client: &UdpSocket) -> serde_json::Value {
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+        let size = client.recv(&mut buf).unwrap();
+        serde_json::from_slice(&buf[0..size]).unwrap()
+    }
+
+    #[test]
+    fn concurrent_request() {
+        let client = concurrent_service(2, Some(Duration::from_secs(1)));
+
+        client.send(b"{ ping }").unwrap();
+
+        assert_eq!(
+            recv_json(&client),
+            json!({"errs": "", "msg": {"ping": "pong"}})
+        );
+    }
+
+    #[test]
+    fn concurrent_slow_request() {
+        let client = concurrent_service(2, None);
+
+        // The quick query is answered while the slow one is still running
+        client.send(b"{ slow }").unwrap();
+        client.send(b"{ ping }").unwrap();
+
+        assert_eq!(recv_json(&client)["msg"], json!({"ping": "pong"}));
+        assert_eq!(recv_json(&client)["msg"], json!({"slow": true}));
+    }
+
+    #[test]
+    fn concurrent_timeout_expired() {
+        let client = concurrent_service(1, Some(Duration::from_millis(100)));
+        let request = json!({
+            "query": "{ slow }",
+            "id": 7
+        });
+
+        client.send(request.to_string().as_bytes()).unwrap();
+
+        assert_eq!(
+            recv_json(&client),
+            json!({
+                "id": 7,
+                "msg": null,
+                "errs": "{\"message\":\"Request timed out after 0 seconds\"}"
+            })
+        );
+    }
+
+    #[test]
+    fn concurrent_busy() {
+        let client = concurrent_service(1, Some(Duration::from_millis(100)));
+
+        // The timed out request keeps its worker busy until it completes
+        client.send(b"{ slow }").unwrap();
+        assert!(recv_json(&client)["errs"]
+            .as_str()
+            .unwrap()
+            .contains("timed out"));
+
+        let request = json!({
+            "query": "{ ping }",
+            "id": 8
+        });
+        client.send(request.to_string().as_bytes()).unwrap();
+        let response = recv_json(&client);
+        assert_eq!(response["id"], json!(8));
+        assert!(response["errs"].as_str().unwrap().contains("Service busy"));
+
+        // Once the slow request finishes, the worker is free again
+        thread::sleep(Duration::from_millis(600));
+        client.send(b"{ ping }").unwrap();
+        assert_eq!(recv_json(&client)["msg"], json!({"ping": "pong"}));
+    }
+
+    #[test]
+    fn workers_config() {
+        let config = Config::new_from_str(
+            "test-service",
+            r#"
+            [test-service]
+            workers = 2
+            "#,
+        );
+        assert_eq!(get_workers(&config), 2);
+
+        let config = Config::new_from_str(
+            "test-service",
+            r#"
+            [test-service]
+            workers = 0
+            "#,
+        );
+        assert_eq!(get_workers(&config), DEFAULT_WORKERS);
+
+        let config = Config::new_from_str("test-service", "");
+        assert_eq!(get_workers(&config), DEFAULT_WORKERS);
+    }
+
+    #[test]
+    fn service_metrics() {
+        let service = test_service();
+
+        service.process("{ ping }".to_owned());
+        service.process("{ unknown }".to_owned());
+
+        let response: serde_json::Value = serde_json::from_str(&service.process(
+            "{ _service { uptime, requestCount, errorCount, lastError, version } }".to_owned(),
+        )).unwrap();
+
+        let info = &response["msg"]["_service"];
+        assert_eq!(info["uptime"], json!(0));
+        assert_eq!(info["requestCount"], json!(2));
+        assert_eq!(info["errorCount"], json!(1));
+        assert!(info["lastError"].as_str().unwrap().contains("unknown"));
+        assert_eq!(info["version"], json!(env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn service_metrics_introspection() {
+        let service = test_service();
+
+        let response: serde_json::Value = serde_json::from_str(&service.process(
+            "{ __type(name: \"Query\") { fields { name } } }".to_owned(),
+        )).unwrap();
+
+        let fields: Vec<&str> = response["msg"]["__type"]["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field["name"].as_str().unwrap())
+            .collect();
+
+        assert!(fields.contains(&"ping"));
+        assert!(fields.contains(&"_service"));
+    }
+
+    #[test]
+    fn request_timeout_config() {
+        let config = Config::new_from_str(
+            "test-service",
+            r#"
+            [test-service]
+            request_timeout = 5
+            "#,
+        );
+        assert_eq!(get_request_timeout(&config), Some(Duration::from_secs(5)));
+
+        let config = Config::new_from_str("test-service", "");
+        assert_eq!(get_request_timeout(&config), None);
+    }
+
+    #[test]
+    fn context_lock_subsystem() {
+        let context = Context {
+            subsystem: Mutex::new(1),
+            storage: Storage::new(),
+            metrics: Mutex::new(Metrics::new()),
+        };
+
+        *context.lock_subsystem() += 1;
+        assert_eq!(*context.lock_subsystem(), 2);
+    }
+
+    #[test]
+    fn context_storage_persists() {
+        let dir = TempDir::new().unwrap();
+        let config = format!(
+            r#"
+            [test-service]
+            storage_dir = "{}"
+            "#,
+            dir.path().display()
+        );
+
+        {
+            let context = Context {
+                subsystem: (),
+                storage: get_storage(&Config::new_from_str("test-service", &config)),
+                metrics: Mutex::new(Metrics::new()),
+            };
+            context.set_persistent("deployed", "true").unwrap();
+            context.set_persistent("attempts", "1").unwrap();
+            context.clear(&"attempts".to_owned());
+            context.set("errors", "timeout");
+        }
+
+        let context = Context {
+            subsystem: (),
+            storage: get_storage(&Config::new_from_str("test-service", &config)),
+            metrics: Mutex::new(Metrics::new()),
+        };
+        assert_eq!(context.get("deployed"), "true");
+        assert_eq!(context.get("attempts"), "");
+        assert_eq!(context.get("errors"), "");
+    }
+
+    #[test]
+    fn chunk_size_config() {
+        let config = Config::new_from_str(
+            "test-service",
+            r#"
+            [test-service]
+            chunk_size = 512
+            "#,
+        );
+        assert_eq!(get_chunk_size(&config), Some(512));
+
+        let config = Config::new_from_str(
+            "test-service",
+            r#"
+            [test-service]
+            chunk_size = 5
+            "#,
+        );
+        assert_eq!(get_chunk_size(&config), None);
+
+        let config = Config::new_from_str("test-service", "");
+        assert_eq!(get_chunk_size(&config), None);
+    }
+}
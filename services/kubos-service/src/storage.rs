@@ -0,0 +1,292 @@
+//
+// Copyright (C) 2018 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use serde_json;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Name of the file which persistent storage is saved to
+const STORAGE_FILE: &str = "context.json";
+
+/// Key/value storage backing a service's `Context`.
+///
+/// Values are kept in memory. When created with a storage directory, values stored with
+/// `set_persistent` are also written to disk, so that they survive service restarts.
+pub struct Storage {
+    values: RwLock<HashMap<String, Entry>>,
+    path: Option<PathBuf>,
+}
+
+// A stored value, and whether it is saved to disk
+#[derive(Clone)]
+struct Entry {
+    value: String,
+    persistent: bool,
+}
+
+impl Storage {
+    /// Creates in-memory storage
+    pub fn new() -> Self {
+        Storage {
+            values: RwLock::new(HashMap::new()),
+            path: None,
+        }
+    }
+
+    /// Creates storage which is persisted to a file in the given directory,
+    /// loading any values which were previously saved there
+    pub fn persistent(dir: &str) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        let path = Path::new(dir).join(STORAGE_FILE);
+        let saved: HashMap<String, String> = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err),
+        };
+
+        let values = saved
+            .into_iter()
+            .map(|(key, value)| {
+                (
+                    key,
+                    Entry {
+                        value,
+                        persistent: true,
+                    },
+                )
+            })
+            .collect();
+
+        Ok(Storage {
+            values: RwLock::new(values),
+            path: Some(path),
+        })
+    }
+
+    /// Fetches the value stored under `key`
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.values
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .get(key)
+            .map(|entry| entry.value.clone())
+    }
+
+    /// Stores `value` under `key` in memory only, replacing any existing value.
+    /// If the key's previous value was saved to disk, it is removed from there
+    pub fn set(&self, key: &str, value: &str) -> io::Result<()> {
+        self.update(|values| {
+            let entry = Entry {
+                value: value.to_owned(),
+                persistent: false,
+            };
+            values
+                .insert(key.to_owned(), entry)
+                .map_or(false, |old| old.persistent)
+        })
+    }
+
+    /// Stores `value` under `key` and saves it to disk, replacing any existing value
+    pub fn set_persistent(&self, key: &str, value: &str) -> io::Result<()> {
+        self.update(|values| {
+            let entry = Entry {
+                value: value.to_owned(),
+                persistent: true,
+            };
+            values.insert(key.to_owned(), entry);
+            true
+        })
+    }
+
+    /// Removes `key` and its value
+    pub fn remove(&self, key: &str) -> io::Result<()> {
+        self.update(|values| values.remove(key).map_or(false, |old| old.persistent))
+    }
+
+    /// Removes all stored values
+    pub fn clear(&self) -> io::Result<()> {
+        self.update(|values| {
+            let saved = values.values().any(|entry| entry.persistent);
+            values.clear();
+            saved
+        })
+    }
+
+    // Apply a change to the stored values and then save them, if the change affected any
+    // saved values. If saving fails, the change is undone, so that the values in memory
+    // never differ from those on disk.
+    // The lock is held while saving so that concurrent changes are written in order.
+    fn update<F>(&self, change: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut HashMap<String, Entry>) -> bool,
+    {
+        let mut values = self.values.write().unwrap_or_else(|err| err.into_inner());
+
+        let path = match self.path {
+            Some(ref path) => path,
+            None => {
+                change(&mut values);
+                return Ok(());
+            }
+        };
+
+        let previous = values.clone();
+        if !change(&mut values) {
+            return Ok(());
+        }
+
+        save(path, &values).map_err(|err| {
+            *values = previous;
+            err
+        })
+    }
+}
+
+// Atomically replace the storage file, so that a power loss mid-write
+// leaves either the old or the new values on disk, never a partial file
+fn save(path: &Path, values: &HashMap<String, Entry>) -> io::Result<()> {
+    let saved: HashMap<&str, &str> = values
+        .iter()
+        .filter(|&(_, entry)| entry.persistent)
+        .map(|(key, entry)| (key.as_str(), entry.value.as_str()))
+        .collect();
+    let contents = serde_json::to_string(&saved)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    let tmp_path = path.with_extension("json.tmp");
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)?;
+
+    // Make sure the rename itself has made it to disk
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn memory_storage() {
+        let storage = Storage::new();
+
+        storage.set("key", "value").unwrap();
+        assert_eq!(storage.get("key"), Some("value".to_owned()));
+
+        storage.remove("key").unwrap();
+        assert_eq!(storage.get("key"), None);
+    }
+
+    #[test]
+    fn persistent_storage_reload() {
+        let dir = TempDir::new().unwrap();
+        let dir_str = dir.path().to_string_lossy().to_string();
+
+        {
+            let storage = Storage::persistent(&dir_str).unwrap();
+            storage.set_persistent("deploy_attempted", "true").unwrap();
+            storage.set_persistent("boot_count", "3").unwrap();
+            storage.remove("boot_count").unwrap();
+            storage.set("last_error", "timeout").unwrap();
+        }
+
+        let storage = Storage::persistent(&dir_str).unwrap();
+        assert_eq!(storage.get("deploy_attempted"), Some("true".to_owned()));
+        assert_eq!(storage.get("boot_count"), None);
+        assert_eq!(storage.get("last_error"), None);
+        assert!(!dir.path().join("context.json.tmp").exists());
+    }
+
+    #[test]
+    fn persistent_storage_clear() {
+        let dir = TempDir::new().unwrap();
+        let dir_str = dir.path().to_string_lossy().to_string();
+
+        let storage = Storage::persistent(&dir_str).unwrap();
+        storage.set_persistent("key", "value").unwrap();
+        storage.clear().unwrap();
+
+        let storage = Storage::persistent(&dir_str).unwrap();
+        assert_eq!(storage.get("key"), None);
+    }
+
+    #[test]
+    fn persistent_storage_new_dir() {
+        let dir = TempDir::new().unwrap();
+        let sub_dir = dir.path().join("service").join("context");
+
+        let storage = Storage::persistent(&sub_dir.to_string_lossy()).unwrap();
+        storage.set_persistent("key", "value").unwrap();
+
+        assert!(sub_dir.join("context.json").exists());
+    }
+
+    #[test]
+    fn persistent_storage_transient_values() {
+        let dir = TempDir::new().unwrap();
+        let dir_str = dir.path().to_string_lossy().to_string();
+
+        // Values which aren't persistent never touch the disk
+        let storage = Storage::persistent(&dir_str).unwrap();
+        storage.set("errors", "[]").unwrap();
+        assert!(!dir.path().join("context.json").exists());
+
+        // A persistent value which is then set normally is removed from the disk
+        storage.set_persistent("mode", "safe").unwrap();
+        storage.set("mode", "normal").unwrap();
+        assert_eq!(storage.get("mode"), Some("normal".to_owned()));
+
+        let storage = Storage::persistent(&dir_str).unwrap();
+        assert_eq!(storage.get("mode"), None);
+    }
+
+    #[test]
+    fn persistent_storage_save_failed() {
+        let dir = TempDir::new().unwrap();
+        let sub_dir = dir.path().join("context");
+
+        let storage = Storage::persistent(&sub_dir.to_string_lossy()).unwrap();
+        storage.set_persistent("key", "old").unwrap();
+        fs::remove_dir_all(&sub_dir).unwrap();
+
+        // The value in memory is left matching the one last saved
+        assert!(storage.set_persistent("key", "new").is_err());
+        assert_eq!(storage.get("key"), Some("old".to_owned()));
+        assert!(storage.remove("key").is_err());
+        assert_eq!(storage.get("key"), Some("old".to_owned()));
+    }
+
+    #[test]
+    fn persistent_storage_corrupt() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("context.json"), "not json").unwrap();
+
+        assert!(Storage::persistent(&dir.path().to_string_lossy()).is_err());
+    }
+}
@@ -0,0 +1,386 @@
+//
+// Copyright (C) 2018 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use failure;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+
+use process::root_dir;
+
+/// Time spent by a CPU in each state, in clock ticks, as reported by /proc/stat
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CpuTimes {
+    name: String,
+    user: u64,
+    nice: u64,
+    system: u64,
+    idle: u64,
+    iowait: u64,
+    irq: u64,
+    softirq: u64,
+    steal: u64,
+}
+
+/// Percentage of time spent by a CPU in each state between two samples
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CpuUsage {
+    pub name: String,
+    pub user: f64,
+    pub nice: f64,
+    pub system: f64,
+    pub idle: f64,
+    pub iowait: f64,
+    pub irq: f64,
+    pub softirq: f64,
+    pub steal: f64,
+}
+
+impl CpuUsage {
+    /// Percentage of time the CPU was busy (not idle or waiting for I/O)
+    pub fn busy(&self) -> f64 {
+        self.user + self.nice + self.system + self.irq + self.softirq + self.steal
+    }
+}
+
+impl CpuTimes {
+    /// Parse a `cpu` line of /proc/stat
+    fn parse_line(line: &str) -> Option<CpuTimes> {
+        let mut iter = line.split_whitespace();
+        let name = iter.next()?;
+        if !name.starts_with("cpu") {
+            return None;
+        }
+
+        let mut next = || {
+            iter.next()
+                .and_then(|val| u64::from_str(val).ok())
+                .unwrap_or(0)
+        };
+
+        // The guest times which follow are already counted in user and nice
+        Some(CpuTimes {
+            name: name.to_owned(),
+            user: next(),
+            nice: next(),
+            system: next(),
+            idle: next(),
+            iowait: next(),
+            irq: next(),
+            softirq: next(),
+            steal: next(),
+        })
+    }
+
+    /// The name of the CPU, such as `cpu0`. The combined times of all CPUs are named `cpu`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Total clock ticks spent in all states
+    pub fn total(&self) -> u64 {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait
+            + self.irq
+            + self.softirq
+            + self.steal
+    }
+
+    /// Percentage of time spent in each state between an earlier sample and this one
+    pub fn usage_since(&self, earlier: &CpuTimes) -> CpuUsage {
+        // Some counters (like iowait) may go backwards, so never let a delta go negative
+        let elapsed = self.total().saturating_sub(earlier.total());
+        let percent = |now: u64, then: u64| match elapsed {
+            0 => 0.0,
+            _ => now.saturating_sub(then) as f64 * 100.0 / elapsed as f64,
+        };
+
+        CpuUsage {
+            name: self.name.clone(),
+            user: percent(self.user, earlier.user),
+            nice: percent(self.nice, earlier.nice),
+            system: percent(self.system, earlier.system),
+            idle: percent(self.idle, earlier.idle),
+            iowait: percent(self.iowait, earlier.iowait),
+            irq: percent(self.irq, earlier.irq),
+            softirq: percent(self.softirq, earlier.softirq),
+            steal: percent(self.steal, earlier.steal),
+        }
+    }
+}
+
+/// CPU times provided by the Linux /proc/stat file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CpuStat {
+    total: CpuTimes,
+    cpus: Vec<CpuTimes>,
+}
+
+impl CpuStat {
+    /// Parse a String with the format of the /proc/stat file
+    pub fn parse<R>(stat: R) -> Result<CpuStat, failure::Error>
+    where
+        R: BufRead,
+    {
+        let mut cpu_stat = CpuStat::default();
+        let mut found = false;
+
+        for line in stat.lines() {
+            let line = line?;
+            match CpuTimes::parse_line(&line) {
+                Some(times) if times.name == "cpu" => {
+                    cpu_stat.total = times;
+                    found = true;
+                }
+                Some(times) => cpu_stat.cpus.push(times),
+                None => {}
+            }
+        }
+
+        match found {
+            true => Ok(cpu_stat),
+            false => Err(format_err!("No cpu line found in stat")),
+        }
+    }
+
+    pub fn from_proc() -> Result<CpuStat, failure::Error> {
+        let file = File::open(root_path!("proc", "stat"))?;
+        Self::parse(BufReader::new(file))
+    }
+
+    /// The combined times of all CPUs
+    pub fn total(&self) -> &CpuTimes {
+        &self.total
+    }
+
+    /// The times of each CPU
+    pub fn cpus(&self) -> &[CpuTimes] {
+        &self.cpus
+    }
+
+    /// Percentage of one CPU used by a process which used the given number of clock ticks
+    /// between an earlier sample and this one
+    pub fn process_usage(&self, earlier: &CpuStat, ticks: u64) -> f64 {
+        let elapsed = self.total.total().saturating_sub(earlier.total.total());
+        let num_cpus = self.cpus.len().max(1) as u64;
+
+        match elapsed {
+            0 => 0.0,
+            _ => (ticks * num_cpus) as f64 * 100.0 / elapsed as f64,
+        }
+    }
+}
+
+/// System load averages provided by the Linux /proc/loadavg file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LoadAvg {
+    one: f64,
+    five: f64,
+    fifteen: f64,
+    running: u32,
+    total: u32,
+}
+
+impl LoadAvg {
+    /// Parse a String with the format of the /proc/loadavg file
+    pub fn parse<R>(mut loadavg: R) -> Result<LoadAvg, failure::Error>
+    where
+        R: BufRead,
+    {
+        let mut line = String::new();
+        loadavg.read_line(&mut line)?;
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 {
+            bail!("Invalid loadavg format");
+        }
+
+        let (running, total) = match fields[3].find('/') {
+            Some(index) => (&fields[3][..index], &fields[3][index + 1..]),
+            None => bail!("Invalid loadavg format"),
+        };
+
+        Ok(LoadAvg {
+            one: f64::from_str(fields[0])?,
+            five: f64::from_str(fields[1])?,
+            fifteen: f64::from_str(fields[2])?,
+            running: u32::from_str(running)?,
+            total: u32::from_str(total)?,
+        })
+    }
+
+    pub fn from_proc() -> Result<LoadAvg, failure::Error> {
+        let file = File::open(root_path!("proc", "loadavg"))?;
+        Self::parse(BufReader::new(file))
+    }
+
+    /// Load average over the last minute
+    pub fn one(&self) -> f64 {
+        self.one
+    }
+
+    /// Load average over the last five minutes
+    pub fn five(&self) -> f64 {
+        self.five
+    }
+
+    /// Load average over the last fifteen minutes
+    pub fn fifteen(&self) -> f64 {
+        self.fifteen
+    }
+
+    /// Number of currently runnable processes and threads
+    pub fn running(&self) -> u32 {
+        self.running
+    }
+
+    /// Number of processes and threads which currently exist
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAT_BEFORE: &[u8] = b"cpu  1000 100 500 8000 200 10 40 0 0 0\n\
+                                 cpu0 500 50 250 4000 100 5 20 0 0 0\n\
+                                 cpu1 500 50 250 4000 100 5 20 0 0 0\n\
+                                 intr 1234567 0 0 0\n\
+                                 ctxt 2345678\n\
+                                 btime 1537216542\n\
+                                 processes 4242\n";
+
+    const STAT_AFTER: &[u8] = b"cpu  1100 100 550 8300 250 10 40 0 0 0\n\
+                                cpu0 580 50 290 4090 140 5 20 0 0 0\n\
+                                cpu1 520 50 260 4210 110 5 20 0 0 0\n\
+                                intr 1234789 0 0 0\n";
+
+    #[test]
+    fn cpustat_parse() {
+        let stat = CpuStat::parse(STAT_BEFORE).unwrap();
+        assert_eq!(
+            stat.total(),
+            &CpuTimes {
+                name: "cpu".into(),
+                user: 1000,
+                nice: 100,
+                system: 500,
+                idle: 8000,
+                iowait: 200,
+                irq: 10,
+                softirq: 40,
+                steal: 0,
+            }
+        );
+        assert_eq!(stat.cpus().len(), 2);
+        assert_eq!(stat.cpus()[1].name(), "cpu1");
+        assert_eq!(stat.total().total(), 9850);
+    }
+
+    #[test]
+    fn cpustat_old_kernel() {
+        // Kernels before 2.6.11 don't report steal time
+        let stat = CpuStat::parse(&b"cpu  1000 100 500 8000 200 10 40\n"[..]).unwrap();
+        assert_eq!(stat.total().steal, 0);
+        assert_eq!(stat.total().softirq, 40);
+        assert!(stat.cpus().is_empty());
+    }
+
+    #[test]
+    fn cpustat_invalid() {
+        assert!(CpuStat::parse(&b"intr 1234567 0 0 0\n"[..]).is_err());
+    }
+
+    #[test]
+    fn cpustat_usage() {
+        let before = CpuStat::parse(STAT_BEFORE).unwrap();
+        let after = CpuStat::parse(STAT_AFTER).unwrap();
+
+        // 500 ticks passed in total
+        let usage = after.total().usage_since(before.total());
+        assert_eq!(usage.name, "cpu");
+        assert_eq!(usage.user, 20.0);
+        assert_eq!(usage.system, 10.0);
+        assert_eq!(usage.idle, 60.0);
+        assert_eq!(usage.iowait, 10.0);
+        assert_eq!(usage.busy(), 30.0);
+
+        let usage = after.cpus()[1].usage_since(&before.cpus()[1]);
+        assert_eq!(usage.idle, 84.0);
+        assert_eq!(usage.busy(), 12.0);
+    }
+
+    #[test]
+    fn cpustat_usage_no_time() {
+        let stat = CpuStat::parse(STAT_BEFORE).unwrap();
+        assert_eq!(stat.total().usage_since(stat.total()).busy(), 0.0);
+    }
+
+    #[test]
+    fn cpustat_process_usage() {
+        let before = CpuStat::parse(STAT_BEFORE).unwrap();
+        let after = CpuStat::parse(STAT_AFTER).unwrap();
+
+        // Each CPU ran for 250 ticks, so 125 ticks is half of one CPU
+        assert_eq!(after.process_usage(&before, 125), 50.0);
+        assert_eq!(after.process_usage(&before, 500), 200.0);
+    }
+
+    #[test]
+    fn cpustat_from_proc() {
+        let stat = CpuStat::from_proc().unwrap();
+        assert_eq!(stat.total().name(), "cpu");
+        assert_eq!(stat.total().total(), 4125);
+        assert_eq!(stat.cpus().len(), 1);
+    }
+
+    #[test]
+    fn loadavg_parse() {
+        let loadavg = LoadAvg::parse(&b"0.20 0.18 0.12 1/80 11206\n"[..]).unwrap();
+        assert_eq!(
+            loadavg,
+            LoadAvg {
+                one: 0.20,
+                five: 0.18,
+                fifteen: 0.12,
+                running: 1,
+                total: 80,
+            }
+        );
+    }
+
+    #[test]
+    fn loadavg_invalid() {
+        assert!(LoadAvg::parse(&b"0.20 0.18\n"[..]).is_err());
+        assert!(LoadAvg::parse(&b"0.20 0.18 0.12 80 11206\n"[..]).is_err());
+    }
+
+    #[test]
+    fn loadavg_from_proc() {
+        let loadavg = LoadAvg::from_proc().unwrap();
+        assert_eq!(loadavg.one(), 1.5);
+        assert_eq!(loadavg.five(), 0.75);
+        assert_eq!(loadavg.fifteen(), 0.25);
+        assert_eq!(loadavg.running(), 2);
+        assert_eq!(loadavg.total(), 97);
+    }
+}
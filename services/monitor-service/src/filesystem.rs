@@ -0,0 +1,377 @@
+//
+// Copyright (C) 2018 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use failure;
+use libc;
+
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use process::root_dir;
+
+/// The deepest directory level `PathUsage` descends to
+pub const MAX_USAGE_DEPTH: u32 = 16;
+/// The most files and directories `PathUsage` looks at for each path
+pub const MAX_USAGE_ENTRIES: u64 = 20000;
+
+/// A mounted filesystem, as listed in /proc/mounts
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mount {
+    device: String,
+    mount_point: String,
+    fs_type: String,
+    options: String,
+}
+
+impl Mount {
+    /// Parse a String with the format of the /proc/mounts file
+    pub fn parse<R>(mounts: R) -> Result<Vec<Mount>, failure::Error>
+    where
+        R: BufRead,
+    {
+        let mut list = vec![];
+
+        for line in mounts.lines() {
+            let line = line?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 4 {
+                continue;
+            }
+
+            list.push(Mount {
+                device: unescape(fields[0]),
+                mount_point: unescape(fields[1]),
+                fs_type: fields[2].to_owned(),
+                options: fields[3].to_owned(),
+            });
+        }
+
+        Ok(list)
+    }
+
+    pub fn from_proc() -> Result<Vec<Mount>, failure::Error> {
+        let file = File::open(root_path!("proc", "mounts"))?;
+        Self::parse(BufReader::new(file))
+    }
+
+    /// The device or pseudo-filesystem which is mounted
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// The directory the filesystem is mounted on
+    pub fn mount_point(&self) -> &str {
+        &self.mount_point
+    }
+
+    /// The type of the filesystem, such as `ext4` or `tmpfs`
+    pub fn fs_type(&self) -> &str {
+        &self.fs_type
+    }
+
+    /// Whether the filesystem is mounted read-only
+    pub fn read_only(&self) -> bool {
+        self.options.split(',').any(|opt| opt == "ro")
+    }
+}
+
+// The kernel escapes spaces, tabs, newlines and backslashes in /proc/mounts as octal sequences
+fn unescape(field: &str) -> String {
+    let mut result = String::with_capacity(field.len());
+    let mut rest = field;
+
+    while let Some(index) = rest.find('\\') {
+        result.push_str(&rest[..index]);
+        let escape = rest.get(index + 1..index + 4);
+        match escape.and_then(|digits| u8::from_str_radix(digits, 8).ok()) {
+            Some(byte) => {
+                result.push(byte as char);
+                rest = &rest[index + 4..];
+            }
+            None => {
+                result.push('\\');
+                rest = &rest[index + 1..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Space and inode usage of a filesystem, as reported by statvfs
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FsUsage {
+    total_bytes: u64,
+    free_bytes: u64,
+    available_bytes: u64,
+    total_inodes: u64,
+    free_inodes: u64,
+}
+
+impl FsUsage {
+    /// Get the usage of the filesystem containing the given path
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<FsUsage, failure::Error> {
+        let c_path = CString::new(path.as_ref().as_os_str().as_bytes())?;
+        let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+
+        if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let block_size = stat.f_frsize as u64;
+        Ok(FsUsage {
+            total_bytes: stat.f_blocks as u64 * block_size,
+            free_bytes: stat.f_bfree as u64 * block_size,
+            available_bytes: stat.f_bavail as u64 * block_size,
+            total_inodes: stat.f_files as u64,
+            free_inodes: stat.f_ffree as u64,
+        })
+    }
+
+    /// Size of the filesystem in bytes
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// Bytes in use
+    pub fn used_bytes(&self) -> u64 {
+        self.total_bytes.saturating_sub(self.free_bytes)
+    }
+
+    /// Bytes which aren't in use, including those reserved for the root user
+    pub fn free_bytes(&self) -> u64 {
+        self.free_bytes
+    }
+
+    /// Bytes available to unprivileged users
+    pub fn available_bytes(&self) -> u64 {
+        self.available_bytes
+    }
+
+    /// Number of inodes in the filesystem
+    pub fn total_inodes(&self) -> u64 {
+        self.total_inodes
+    }
+
+    /// Inodes in use
+    pub fn used_inodes(&self) -> u64 {
+        self.total_inodes.saturating_sub(self.free_inodes)
+    }
+
+    /// Inodes which aren't in use
+    pub fn free_inodes(&self) -> u64 {
+        self.free_inodes
+    }
+}
+
+/// Space used by a file or directory tree
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PathUsage {
+    bytes: u64,
+    files: u64,
+    entries: u64,
+    complete: bool,
+}
+
+impl PathUsage {
+    /// Add up the sizes of a file, or of all the files under a directory.
+    ///
+    /// Symlinks aren't followed and other filesystems mounted under the directory aren't
+    /// counted. Files which can't be read, such as those removed while the directory is being
+    /// walked, are skipped.
+    ///
+    /// The walk stops after `MAX_USAGE_ENTRIES` files and directories, and doesn't descend more
+    /// than `MAX_USAGE_DEPTH` levels, so that a huge directory can't hold up the service.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<PathUsage, failure::Error> {
+        Self::from_path_limited(path, MAX_USAGE_DEPTH, MAX_USAGE_ENTRIES)
+    }
+
+    /// Add up the sizes of a file, or of the files under a directory, looking at no more than
+    /// `max_entries` files and directories up to `max_depth` levels deep
+    pub fn from_path_limited<P: AsRef<Path>>(
+        path: P,
+        max_depth: u32,
+        max_entries: u64,
+    ) -> Result<PathUsage, failure::Error> {
+        let metadata = fs::symlink_metadata(path.as_ref())?;
+        let mut usage = PathUsage {
+            complete: true,
+            ..Default::default()
+        };
+        usage.add(
+            path.as_ref(),
+            &metadata,
+            metadata.dev(),
+            max_depth,
+            max_entries,
+        );
+        Ok(usage)
+    }
+
+    fn add(&mut self, path: &Path, metadata: &fs::Metadata, dev: u64, depth: u32, max: u64) {
+        if self.entries >= max {
+            self.complete = false;
+            return;
+        }
+        self.entries += 1;
+
+        if !metadata.is_dir() {
+            self.bytes += metadata.len();
+            self.files += 1;
+            return;
+        }
+
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            if self.entries >= max {
+                self.complete = false;
+                return;
+            }
+            if let Ok(child) = entry.metadata() {
+                if child.dev() != dev {
+                    continue;
+                }
+                if child.is_dir() && depth == 0 {
+                    self.complete = false;
+                    continue;
+                }
+                self.add(&entry.path(), &child, dev, depth.saturating_sub(1), max);
+            }
+        }
+    }
+
+    /// Total size of the files in bytes
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Number of files, not counting directories
+    pub fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// Whether every file was counted, or the walk was cut short by one of its limits
+    pub fn complete(&self) -> bool {
+        self.complete
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTS: &[u8] = b"/dev/root / ext4 ro,relatime,data=ordered 0 0\n\
+                            devtmpfs /dev devtmpfs rw,relatime,size=250360k,mode=755 0 0\n\
+                            proc /proc proc rw,relatime 0 0\n\
+                            /dev/mmcblk0p7 /home ext4 rw,relatime,data=ordered 0 0\n\
+                            /dev/sda1 /mnt/flight\\040data vfat rw,relatime 0 0\n";
+
+    #[test]
+    fn mounts_parse() {
+        let mounts = Mount::parse(MOUNTS).unwrap();
+        assert_eq!(mounts.len(), 5);
+        assert_eq!(
+            mounts[3],
+            Mount {
+                device: "/dev/mmcblk0p7".into(),
+                mount_point: "/home".into(),
+                fs_type: "ext4".into(),
+                options: "rw,relatime,data=ordered".into(),
+            }
+        );
+        assert!(mounts[0].read_only());
+        assert!(!mounts[3].read_only());
+    }
+
+    #[test]
+    fn mounts_unescape() {
+        let mounts = Mount::parse(MOUNTS).unwrap();
+        assert_eq!(mounts[4].mount_point(), "/mnt/flight data");
+        assert_eq!(unescape("a\\011b\\134c\\"), "a\tb\\c\\");
+    }
+
+    #[test]
+    fn mounts_from_proc() {
+        let mounts = Mount::from_proc().unwrap();
+        let points: Vec<&str> = mounts.iter().map(|m| m.mount_point()).collect();
+        assert_eq!(points, vec!["/", "/proc", "/tmp", "/home"]);
+        assert_eq!(mounts[2].fs_type(), "tmpfs");
+    }
+
+    #[test]
+    fn fsusage_from_path() {
+        let usage = FsUsage::from_path(root_dir()).unwrap();
+        assert!(usage.total_bytes() > 0);
+        assert!(usage.free_bytes() <= usage.total_bytes());
+        assert!(usage.available_bytes() <= usage.free_bytes());
+        assert_eq!(usage.used_bytes() + usage.free_bytes(), usage.total_bytes());
+    }
+
+    #[test]
+    fn fsusage_missing_path() {
+        assert!(FsUsage::from_path(root_path!("does-not-exist")).is_err());
+    }
+
+    #[test]
+    fn pathusage_dir() {
+        let usage = PathUsage::from_path(root_path!("home", "storage")).unwrap();
+        assert_eq!(usage.bytes(), 30);
+        assert_eq!(usage.files(), 2);
+        assert!(usage.complete());
+    }
+
+    #[test]
+    fn pathusage_file() {
+        let usage = PathUsage::from_path(root_path!("home", "storage", "sub", "b.txt")).unwrap();
+        assert_eq!(usage.bytes(), 20);
+        assert_eq!(usage.files(), 1);
+    }
+
+    #[test]
+    fn pathusage_entry_limit() {
+        // The directories count as entries too
+        let usage = PathUsage::from_path_limited(root_path!("home", "storage"), 16, 3).unwrap();
+        assert!(usage.files() < 2);
+        assert!(!usage.complete());
+
+        let usage = PathUsage::from_path_limited(root_path!("home", "storage"), 16, 4).unwrap();
+        assert_eq!(usage.files(), 2);
+        assert!(usage.complete());
+    }
+
+    #[test]
+    fn pathusage_depth_limit() {
+        let usage = PathUsage::from_path_limited(root_path!("home", "storage"), 0, 100).unwrap();
+        assert_eq!(usage.bytes(), 10);
+        assert_eq!(usage.files(), 1);
+        assert!(!usage.complete());
+    }
+
+    #[test]
+    fn pathusage_missing() {
+        assert!(PathUsage::from_path(root_path!("home", "missing")).is_err());
+    }
+}
@@ -14,7 +14,27 @@
 // limitations under the License.
 //
 
-//! Service for monitoring KubOS Linux processes, memory, and CPU usage
+//! Service for monitoring KubOS Linux processes, memory, CPU, disk and network usage
+//!
+//! # Configuration
+//!
+//! The service can be configured in the `/home/system/etc/config.toml` with the following fields:
+//!
+//! ```toml,ignore
+//! [monitor-service]
+//! usage_paths = ["/var/lib/telemetry.db", "/home/kubos/storage"]
+//! ```
+//!
+//! Where `usage_paths` lists the files and directories whose sizes are reported by the
+//! `pathUsage` query, such as the telemetry database and the file service's `storage_dir`.
+//! Each path is walked for at most 20000 files and directories, up to 16 levels deep.
+//! `complete` is false if a path's usage was cut short by these limits.
+//!
+//! # CPU Usage
+//!
+//! The `cpu` and `ps` queries measure CPU usage by sampling the CPU times twice, `sampleMs`
+//! milliseconds apart. The service handles one request at a time, so `sampleMs` is limited to
+//! 5000 milliseconds.
 //!
 //! # GraphQL Schema
 //!
@@ -26,7 +46,90 @@
 //! type Query {
 //!     ping: String!
 //!     memInfo: MemInfo!
-//!     ps(pids: [Int!] = null): [ProcInfo!]!
+//!     cpu(sampleMs: Int = 100): Cpu!
+//!     loadAvg: LoadAvg!
+//!     filesystems(all: Boolean = false): [Filesystem!]!
+//!     pathUsage: [PathUsage!]!
+//!     net: Net!
+//!     ps(pids: [Int!] = null, sampleMs: Int = 0): [ProcInfo!]!
+//! }
+//!
+//! type Cpu {
+//!     total: CpuUsage!
+//!     cpus: [CpuUsage!]!
+//! }
+//!
+//! type CpuUsage {
+//!     name: String!
+//!     usage: Float!
+//!     user: Float!
+//!     nice: Float!
+//!     system: Float!
+//!     idle: Float!
+//!     iowait: Float!
+//!     irq: Float!
+//!     softirq: Float!
+//!     steal: Float!
+//! }
+//!
+//! type Filesystem {
+//!     device: String!
+//!     mountPoint: String!
+//!     fsType: String!
+//!     readOnly: Boolean!
+//!     totalBytes: Float
+//!     usedBytes: Float
+//!     freeBytes: Float
+//!     availableBytes: Float
+//!     totalInodes: Float
+//!     usedInodes: Float
+//!     freeInodes: Float
+//! }
+//!
+//! type PathUsage {
+//!     path: String!
+//!     bytes: Float
+//!     files: Int
+//!     complete: Boolean
+//!     error: String
+//! }
+//!
+//! type Net {
+//!     interfaces: [NetInterface!]!
+//!     udp: [UdpSocket!]!
+//! }
+//!
+//! type NetInterface {
+//!     name: String!
+//!     rxBytes: Float!
+//!     rxPackets: Float!
+//!     rxErrors: Float!
+//!     rxDrops: Float!
+//!     txBytes: Float!
+//!     txPackets: Float!
+//!     txErrors: Float!
+//!     txDrops: Float!
+//! }
+//!
+//! type UdpSocket {
+//!     localAddress: String!
+//!     localPort: Int!
+//!     remoteAddress: String!
+//!     remotePort: Int!
+//!     txQueue: Int!
+//!     rxQueue: Int!
+//!     drops: Float!
+//!     uid: Int!
+//!     pid: Int
+//!     cmd: String
+//! }
+//!
+//! type LoadAvg {
+//!     one: Float!
+//!     five: Float!
+//!     fifteen: Float!
+//!     running: Int!
+//!     total: Int!
 //! }
 //!
 //! type MemInfo {
@@ -47,6 +150,9 @@
 //!     mem: Int
 //!     rss: Int
 //!     threads: Int
+//!     utime: Int
+//!     stime: Int
+//!     cpu: Float
 //!     cmd: String
 //! }
 //! ```
@@ -56,6 +162,7 @@ extern crate failure;
 #[macro_use]
 extern crate juniper;
 extern crate kubos_service;
+extern crate libc;
 extern crate regex;
 
 #[cfg(test)]
@@ -63,21 +170,36 @@ extern crate regex;
 extern crate lazy_static;
 
 use kubos_service::{Config, Service};
-use schema::{MutationRoot, QueryRoot};
+use schema::{MutationRoot, QueryRoot, Subsystem};
 
 mod meminfo;
 mod objects;
 #[macro_use]
 mod process;
+mod cpu;
+mod filesystem;
+mod net;
 mod schema;
 mod userinfo;
 
 fn main() {
     let config = Config::new("monitor-service");
 
+    let usage_paths = config
+        .get("usage_paths")
+        .and_then(|paths| {
+            paths.as_array().map(|paths| {
+                paths
+                    .iter()
+                    .filter_map(|path| path.as_str().map(|path| path.to_owned()))
+                    .collect()
+            })
+        })
+        .unwrap_or_default();
+
     Service::new(
         config,
-        (),
+        Subsystem { usage_paths },
         QueryRoot,
         MutationRoot,
     ).start();
@@ -0,0 +1,383 @@
+//
+// Copyright (C) 2018 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use failure;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+use process::{self, root_dir};
+
+/// Traffic counters of a network interface, as reported by /proc/net/dev
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InterfaceStats {
+    name: String,
+    rx_bytes: u64,
+    rx_packets: u64,
+    rx_errors: u64,
+    rx_drops: u64,
+    tx_bytes: u64,
+    tx_packets: u64,
+    tx_errors: u64,
+    tx_drops: u64,
+}
+
+impl InterfaceStats {
+    /// Parse a String with the format of the /proc/net/dev file
+    pub fn parse<R>(dev: R) -> Result<Vec<InterfaceStats>, failure::Error>
+    where
+        R: BufRead,
+    {
+        let mut list = vec![];
+
+        // The first two lines are column headers
+        for line in dev.lines().skip(2) {
+            let line = line?;
+
+            // Older kernels don't leave a space between the name and the first counter
+            let (name, counters) = match line.find(':') {
+                Some(index) => (line[..index].trim(), &line[index + 1..]),
+                None => bail!("Invalid net/dev format"),
+            };
+
+            let counters = counters
+                .split_whitespace()
+                .map(u64::from_str)
+                .collect::<Result<Vec<u64>, _>>()?;
+            if counters.len() < 12 {
+                bail!("Invalid net/dev format");
+            }
+
+            // The receive columns are bytes, packets, errs, drop, fifo, frame, compressed and
+            // multicast, followed by the transmit columns starting with the same four
+            list.push(InterfaceStats {
+                name: name.to_owned(),
+                rx_bytes: counters[0],
+                rx_packets: counters[1],
+                rx_errors: counters[2],
+                rx_drops: counters[3],
+                tx_bytes: counters[8],
+                tx_packets: counters[9],
+                tx_errors: counters[10],
+                tx_drops: counters[11],
+            });
+        }
+
+        Ok(list)
+    }
+
+    pub fn from_proc() -> Result<Vec<InterfaceStats>, failure::Error> {
+        let file = File::open(root_path!("proc", "net", "dev"))?;
+        Self::parse(BufReader::new(file))
+    }
+
+    /// The name of the interface, such as `eth0`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Bytes received
+    pub fn rx_bytes(&self) -> u64 {
+        self.rx_bytes
+    }
+
+    /// Packets received
+    pub fn rx_packets(&self) -> u64 {
+        self.rx_packets
+    }
+
+    /// Receive errors detected by the driver
+    pub fn rx_errors(&self) -> u64 {
+        self.rx_errors
+    }
+
+    /// Received packets which were dropped
+    pub fn rx_drops(&self) -> u64 {
+        self.rx_drops
+    }
+
+    /// Bytes transmitted
+    pub fn tx_bytes(&self) -> u64 {
+        self.tx_bytes
+    }
+
+    /// Packets transmitted
+    pub fn tx_packets(&self) -> u64 {
+        self.tx_packets
+    }
+
+    /// Transmit errors detected by the driver
+    pub fn tx_errors(&self) -> u64 {
+        self.tx_errors
+    }
+
+    /// Outgoing packets which were dropped
+    pub fn tx_drops(&self) -> u64 {
+        self.tx_drops
+    }
+}
+
+/// An IPv4 UDP socket, as listed in /proc/net/udp
+#[derive(Clone, Debug, PartialEq)]
+pub struct UdpSocket {
+    local_address: Ipv4Addr,
+    local_port: u16,
+    remote_address: Ipv4Addr,
+    remote_port: u16,
+    tx_queue: u64,
+    rx_queue: u64,
+    uid: u32,
+    inode: u64,
+    drops: u64,
+}
+
+// Addresses are written as the hex value of the address in network byte order, as read by this
+// host, followed by the port in host byte order. For example, 127.0.0.1:8080 is `0100007F:1F90`
+// on a little-endian system.
+fn parse_address(field: &str) -> Result<(Ipv4Addr, u16), failure::Error> {
+    let mut iter = field.split(':');
+    match (iter.next(), iter.next()) {
+        (Some(addr), Some(port)) => Ok((
+            Ipv4Addr::from(u32::from_be(u32::from_str_radix(addr, 16)?)),
+            u16::from_str_radix(port, 16)?,
+        )),
+        _ => bail!("Invalid socket address: {}", field),
+    }
+}
+
+impl UdpSocket {
+    /// Parse a String with the format of the /proc/net/udp file
+    pub fn parse<R>(udp: R) -> Result<Vec<UdpSocket>, failure::Error>
+    where
+        R: BufRead,
+    {
+        let mut list = vec![];
+
+        // The first line holds the column headers
+        for line in udp.lines().skip(1) {
+            let line = line?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 {
+                bail!("Invalid net/udp format");
+            }
+
+            let (local_address, local_port) = parse_address(fields[1])?;
+            let (remote_address, remote_port) = parse_address(fields[2])?;
+            let (tx_queue, rx_queue) = match fields[4].find(':') {
+                Some(index) => (
+                    u64::from_str_radix(&fields[4][..index], 16)?,
+                    u64::from_str_radix(&fields[4][index + 1..], 16)?,
+                ),
+                None => bail!("Invalid net/udp format"),
+            };
+
+            list.push(UdpSocket {
+                local_address,
+                local_port,
+                remote_address,
+                remote_port,
+                tx_queue,
+                rx_queue,
+                uid: u32::from_str(fields[7])?,
+                inode: u64::from_str(fields[9])?,
+                // The drops column was added in Linux 2.6.27
+                drops: fields
+                    .get(12)
+                    .and_then(|drops| u64::from_str(drops).ok())
+                    .unwrap_or(0),
+            });
+        }
+
+        Ok(list)
+    }
+
+    pub fn from_proc() -> Result<Vec<UdpSocket>, failure::Error> {
+        let file = File::open(root_path!("proc", "net", "udp"))?;
+        Self::parse(BufReader::new(file))
+    }
+
+    /// The address the socket is bound to. `0.0.0.0` means any address
+    pub fn local_address(&self) -> Ipv4Addr {
+        self.local_address
+    }
+
+    /// The port the socket is bound to
+    pub fn local_port(&self) -> u16 {
+        self.local_port
+    }
+
+    /// The address the socket is connected to, or `0.0.0.0` if it isn't connected
+    pub fn remote_address(&self) -> Ipv4Addr {
+        self.remote_address
+    }
+
+    /// The port the socket is connected to, or `0` if it isn't connected
+    pub fn remote_port(&self) -> u16 {
+        self.remote_port
+    }
+
+    /// Bytes waiting to be sent
+    pub fn tx_queue(&self) -> u64 {
+        self.tx_queue
+    }
+
+    /// Bytes received which haven't been read yet
+    pub fn rx_queue(&self) -> u64 {
+        self.rx_queue
+    }
+
+    /// The user ID which owns the socket
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// The inode of the socket, which appears in the /proc/<pid>/fd links of the processes
+    /// using it
+    pub fn inode(&self) -> u64 {
+        self.inode
+    }
+
+    /// Received datagrams which were dropped, usually because the receive queue was full
+    pub fn drops(&self) -> u64 {
+        self.drops
+    }
+}
+
+/// Map the inode of each open socket to the PID of the process which has it open.
+///
+/// Processes which exit, or whose file descriptors can't be read, are skipped.
+pub fn socket_owners() -> Result<HashMap<u64, i32>, failure::Error> {
+    let mut owners = HashMap::new();
+
+    for pid in process::running_pids()? {
+        if let Ok(inodes) = process::socket_inodes(pid) {
+            for inode in inodes {
+                owners.insert(inode, pid);
+            }
+        }
+    }
+
+    Ok(owners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEV: &[u8] = b"Inter-|   Receive    |  Transmit\n\
+                         face |bytes    packets errs drop fifo frame compressed multicast|bytes \
+                         packets errs drop fifo colls carrier compressed\n\
+                         lo:  123456 789 0 0 0 0 0 0 123456 789 0 0 0 0 0 0\n\
+                         eth0: 9876543 12345 2 5 0 0 0 17 1234567 6789 1 3 0 0 0 0\n\
+                         slip0:100 10 0 0 0 0 0 0 200 20 0 0 0 0 0 0\n";
+
+    const UDP: &[u8] = b"sl local_address rem_address st tx_queue rx_queue tr tm->when retrnsmt \
+                         uid timeout inode ref pointer drops\n\
+                         37: 0100007F:1F90 00000000:0000 07 00000000:00000000 00:00000000 \
+                         00000000 0 0 10764 2 ffff88003d3af3c0 0\n\
+                         52: 00000000:0044 00000000:0000 07 00000000:00000A00 00:00000000 \
+                         00000000 0 0 10971 2 ffff88003d3af780 12\n";
+
+    #[test]
+    fn interfaces_parse() {
+        let interfaces = InterfaceStats::parse(DEV).unwrap();
+        assert_eq!(interfaces.len(), 3);
+        assert_eq!(
+            interfaces[1],
+            InterfaceStats {
+                name: "eth0".into(),
+                rx_bytes: 9876543,
+                rx_packets: 12345,
+                rx_errors: 2,
+                rx_drops: 5,
+                tx_bytes: 1234567,
+                tx_packets: 6789,
+                tx_errors: 1,
+                tx_drops: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn interfaces_no_space() {
+        let interfaces = InterfaceStats::parse(DEV).unwrap();
+        assert_eq!(interfaces[2].name(), "slip0");
+        assert_eq!(interfaces[2].rx_bytes(), 100);
+        assert_eq!(interfaces[2].tx_packets(), 20);
+    }
+
+    #[test]
+    fn interfaces_invalid() {
+        let dev = b"header\nheader\n  eth0: 1 2 3\n";
+        assert!(InterfaceStats::parse(&dev[..]).is_err());
+    }
+
+    #[test]
+    fn interfaces_from_proc() {
+        let interfaces = InterfaceStats::from_proc().unwrap();
+        let names: Vec<&str> = interfaces.iter().map(|i| i.name()).collect();
+        assert_eq!(names, vec!["lo", "eth0"]);
+        assert_eq!(interfaces[1].rx_drops(), 4);
+    }
+
+    #[test]
+    fn udp_parse() {
+        let sockets = UdpSocket::parse(UDP).unwrap();
+        assert_eq!(sockets.len(), 2);
+        assert_eq!(
+            sockets[0],
+            UdpSocket {
+                local_address: Ipv4Addr::new(127, 0, 0, 1),
+                local_port: 8080,
+                remote_address: Ipv4Addr::new(0, 0, 0, 0),
+                remote_port: 0,
+                tx_queue: 0,
+                rx_queue: 0,
+                uid: 0,
+                inode: 10764,
+                drops: 0,
+            }
+        );
+        assert_eq!(sockets[1].local_port(), 68);
+        assert_eq!(sockets[1].rx_queue(), 2560);
+        assert_eq!(sockets[1].drops(), 12);
+    }
+
+    #[test]
+    fn udp_invalid() {
+        let udp = b"header\n   0: 0100007F 00000000:0000 07 00000000:00000000 00:00000000 00000000 0 0 1\n";
+        assert!(UdpSocket::parse(&udp[..]).is_err());
+    }
+
+    #[test]
+    fn udp_from_proc() {
+        let sockets = UdpSocket::from_proc().unwrap();
+        let ports: Vec<u16> = sockets.iter().map(|s| s.local_port()).collect();
+        assert_eq!(ports, vec![8006, 8089, 5353]);
+    }
+
+    #[test]
+    fn udp_owners() {
+        let owners = socket_owners().unwrap();
+        assert_eq!(owners.len(), 3);
+        assert_eq!(owners.get(&10764), Some(&761));
+        assert_eq!(owners.get(&10766), Some(&761));
+        assert_eq!(owners.get(&10971), Some(&1149));
+    }
+}
@@ -13,7 +13,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 //
+use cpu::{CpuUsage, LoadAvg};
+use filesystem::{FsUsage, Mount, PathUsage};
 use meminfo::MemInfo;
+use net::{InterfaceStats, UdpSocket};
 use process::ProcStat;
 use userinfo::UserInfo;
 
@@ -39,10 +42,279 @@ graphql_object!(MemInfoResponse: () |&self| {
     }
 });
 
+pub struct CpuResponse {
+    pub total: CpuUsage,
+    pub cpus: Vec<CpuUsage>,
+}
+
+graphql_object!(CpuResponse: () as "Cpu" |&self| {
+    field total() -> CpuUsageResponse {
+        CpuUsageResponse(self.total.clone())
+    }
+
+    field cpus() -> Vec<CpuUsageResponse> {
+        self.cpus.iter().cloned().map(CpuUsageResponse).collect()
+    }
+});
+
+pub struct CpuUsageResponse(pub CpuUsage);
+
+graphql_object!(CpuUsageResponse: () as "CpuUsage" |&self| {
+    field name() -> String {
+        self.0.name.clone()
+    }
+
+    field usage() -> f64 {
+        self.0.busy()
+    }
+
+    field user() -> f64 {
+        self.0.user
+    }
+
+    field nice() -> f64 {
+        self.0.nice
+    }
+
+    field system() -> f64 {
+        self.0.system
+    }
+
+    field idle() -> f64 {
+        self.0.idle
+    }
+
+    field iowait() -> f64 {
+        self.0.iowait
+    }
+
+    field irq() -> f64 {
+        self.0.irq
+    }
+
+    field softirq() -> f64 {
+        self.0.softirq
+    }
+
+    field steal() -> f64 {
+        self.0.steal
+    }
+});
+
+pub struct LoadAvgResponse {
+    pub load: LoadAvg,
+}
+
+graphql_object!(LoadAvgResponse: () as "LoadAvg" |&self| {
+    field one() -> f64 {
+        self.load.one()
+    }
+
+    field five() -> f64 {
+        self.load.five()
+    }
+
+    field fifteen() -> f64 {
+        self.load.fifteen()
+    }
+
+    field running() -> i32 {
+        self.load.running() as i32
+    }
+
+    field total() -> i32 {
+        self.load.total() as i32
+    }
+});
+
+pub struct FilesystemResponse {
+    pub mount: Mount,
+    pub usage: Option<FsUsage>,
+}
+
+graphql_object!(FilesystemResponse: () as "Filesystem" |&self| {
+    field device() -> String {
+        self.mount.device().to_owned()
+    }
+
+    field mount_point() -> String {
+        self.mount.mount_point().to_owned()
+    }
+
+    field fs_type() -> String {
+        self.mount.fs_type().to_owned()
+    }
+
+    field read_only() -> bool {
+        self.mount.read_only()
+    }
+
+    field total_bytes() -> Option<f64> {
+        self.usage.as_ref().map(|u| u.total_bytes() as f64)
+    }
+
+    field used_bytes() -> Option<f64> {
+        self.usage.as_ref().map(|u| u.used_bytes() as f64)
+    }
+
+    field free_bytes() -> Option<f64> {
+        self.usage.as_ref().map(|u| u.free_bytes() as f64)
+    }
+
+    field available_bytes() -> Option<f64> {
+        self.usage.as_ref().map(|u| u.available_bytes() as f64)
+    }
+
+    field total_inodes() -> Option<f64> {
+        self.usage.as_ref().map(|u| u.total_inodes() as f64)
+    }
+
+    field used_inodes() -> Option<f64> {
+        self.usage.as_ref().map(|u| u.used_inodes() as f64)
+    }
+
+    field free_inodes() -> Option<f64> {
+        self.usage.as_ref().map(|u| u.free_inodes() as f64)
+    }
+});
+
+pub struct PathUsageResponse {
+    pub path: String,
+    pub usage: Result<PathUsage, String>,
+}
+
+graphql_object!(PathUsageResponse: () as "PathUsage" |&self| {
+    field path() -> String {
+        self.path.clone()
+    }
+
+    field bytes() -> Option<f64> {
+        self.usage.as_ref().ok().map(|u| u.bytes() as f64)
+    }
+
+    field files() -> Option<i32> {
+        self.usage.as_ref().ok().map(|u| u.files() as i32)
+    }
+
+    field complete() -> Option<bool> {
+        self.usage.as_ref().ok().map(|u| u.complete())
+    }
+
+    field error() -> Option<String> {
+        self.usage.as_ref().err().cloned()
+    }
+});
+
+pub struct NetResponse {
+    pub interfaces: Vec<InterfaceStats>,
+    pub udp: Vec<UdpSocketResponse>,
+}
+
+graphql_object!(NetResponse: () as "Net" |&self| {
+    field interfaces() -> Vec<InterfaceResponse> {
+        self.interfaces.iter().cloned().map(InterfaceResponse).collect()
+    }
+
+    field udp() -> Vec<UdpSocketResponse> {
+        self.udp.clone()
+    }
+});
+
+pub struct InterfaceResponse(pub InterfaceStats);
+
+graphql_object!(InterfaceResponse: () as "NetInterface" |&self| {
+    field name() -> String {
+        self.0.name().to_owned()
+    }
+
+    field rx_bytes() -> f64 {
+        self.0.rx_bytes() as f64
+    }
+
+    field rx_packets() -> f64 {
+        self.0.rx_packets() as f64
+    }
+
+    field rx_errors() -> f64 {
+        self.0.rx_errors() as f64
+    }
+
+    field rx_drops() -> f64 {
+        self.0.rx_drops() as f64
+    }
+
+    field tx_bytes() -> f64 {
+        self.0.tx_bytes() as f64
+    }
+
+    field tx_packets() -> f64 {
+        self.0.tx_packets() as f64
+    }
+
+    field tx_errors() -> f64 {
+        self.0.tx_errors() as f64
+    }
+
+    field tx_drops() -> f64 {
+        self.0.tx_drops() as f64
+    }
+});
+
+#[derive(Clone)]
+pub struct UdpSocketResponse {
+    pub socket: UdpSocket,
+    pub pid: Option<i32>,
+}
+
+graphql_object!(UdpSocketResponse: () as "UdpSocket" |&self| {
+    field local_address() -> String {
+        self.socket.local_address().to_string()
+    }
+
+    field local_port() -> i32 {
+        self.socket.local_port() as i32
+    }
+
+    field remote_address() -> String {
+        self.socket.remote_address().to_string()
+    }
+
+    field remote_port() -> i32 {
+        self.socket.remote_port() as i32
+    }
+
+    field tx_queue() -> i32 {
+        self.socket.tx_queue() as i32
+    }
+
+    field rx_queue() -> i32 {
+        self.socket.rx_queue() as i32
+    }
+
+    field drops() -> f64 {
+        self.socket.drops() as f64
+    }
+
+    field uid() -> i32 {
+        self.socket.uid() as i32
+    }
+
+    field pid() -> Option<i32> {
+        self.pid
+    }
+
+    field cmd() -> Option<String> {
+        self.pid
+            .and_then(|pid| ProcStat::from_pid(pid).ok())
+            .and_then(|stat| stat.cmd().ok().map(|argv| argv.join(" ")))
+    }
+});
+
 pub struct PSResponse {
     pub pid: i32,
     pub user: Option<UserInfo>,
     pub stat: Option<ProcStat>,
+    pub cpu: Option<f64>,
 }
 
 impl PSResponse {
@@ -51,6 +323,7 @@ impl PSResponse {
             pid,
             user: UserInfo::from_pid(pid).ok(),
             stat: ProcStat::from_pid(pid).ok(),
+            cpu: None,
         }
     }
 }
@@ -96,6 +369,18 @@ graphql_object!(PSResponse: () |&self| {
         self.stat.as_ref().map(|stat| stat.num_threads() as i32)
     }
 
+    field utime(&executor) -> Option<i32> {
+        self.stat.as_ref().map(|stat| stat.utime() as i32)
+    }
+
+    field stime(&executor) -> Option<i32> {
+        self.stat.as_ref().map(|stat| stat.stime() as i32)
+    }
+
+    field cpu(&executor) -> Option<f64> {
+        self.cpu
+    }
+
     field cmd(&executor) -> Option<String> {
         self.stat.as_ref().and_then(|stat| {
             stat.cmd().ok().map(|argv| argv.join(" "))
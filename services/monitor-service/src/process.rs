@@ -221,6 +221,16 @@ impl ProcStat {
         self.rss as i32
     }
 
+    /// Number of clock ticks this process has spent in user mode
+    pub fn utime(&self) -> u64 {
+        self.utime
+    }
+
+    /// Number of clock ticks this process has spent in kernel mode
+    pub fn stime(&self) -> u64 {
+        self.stime
+    }
+
     /// Number of threads in this process
     pub fn num_threads(&self) -> i32 {
         self.num_threads as i32
@@ -257,6 +267,24 @@ pub fn running_pids() -> Result<Vec<i32>, failure::Error> {
     Ok(info)
 }
 
+/// Finds the inodes of the sockets a process has open, by reading the links in /proc/<pid>/fd
+pub fn socket_inodes(pid: i32) -> Result<Vec<u64>, failure::Error> {
+    let mut inodes: Vec<u64> = Vec::new();
+    let entries = fs::read_dir(root_path!("proc", pid, "fd"))?;
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        if let Ok(target) = fs::read_link(entry.path()) {
+            let target = target.to_string_lossy();
+            if target.starts_with("socket:[") && target.ends_with(']') {
+                if let Ok(inode) = u64::from_str(&target[8..target.len() - 1]) {
+                    inodes.push(inode);
+                }
+            }
+        }
+    }
+    Ok(inodes)
+}
+
 
 
 // Unit tests
@@ -330,6 +358,8 @@ mod tests {
         assert_eq!(stat.mem_usage(), 2981888);
         assert_eq!(stat.rss(), 458);
         assert_eq!(stat.num_threads(), 1);
+        assert_eq!(stat.utime(), 1);
+        assert_eq!(stat.stime(), 2);
     }
 
 
@@ -386,4 +416,13 @@ mod tests {
 
         assert_eq!(pids, vec![232, 380, 720, 761, 1149, 1492]);
     }
+
+    #[test]
+    fn socket_inodes() {
+        let mut inodes = super::socket_inodes(761).unwrap();
+        inodes.sort_unstable();
+        assert_eq!(inodes, vec![10764, 10766]);
+
+        assert_eq!(super::socket_inodes(1149).unwrap(), vec![10971]);
+    }
 }
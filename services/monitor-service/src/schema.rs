@@ -17,11 +17,38 @@
 use juniper::{self, FieldResult, FieldError};
 use kubos_service;
 
+use cpu;
+use filesystem;
 use meminfo;
+use net;
 use objects::*;
 use process;
+use std::thread;
+use std::time::Duration;
 
-type Context = kubos_service::Context<()>;
+/// Settings read from the service's section of the config file
+pub struct Subsystem {
+    /// Files and directories to report the size of in the `pathUsage` query
+    pub usage_paths: Vec<String>,
+}
+
+type Context = kubos_service::Context<Subsystem>;
+
+/// The longest time a query may spend sampling CPU usage. The service handles one
+/// request at a time, so a long sample holds up every other request.
+const MAX_SAMPLE_MS: i32 = 5000;
+
+// Check a requested sampling time, and convert it into the time to sleep for
+fn sample_duration(sample_ms: i32) -> FieldResult<Duration> {
+    if sample_ms < 0 || sample_ms > MAX_SAMPLE_MS {
+        return Err(FieldError::new(
+            format!("sampleMs must be between 0 and {}", MAX_SAMPLE_MS),
+            juniper::Value::null(),
+        ));
+    }
+
+    Ok(Duration::from_millis(sample_ms as u64))
+}
 
 pub struct QueryRoot;
 
@@ -38,14 +65,111 @@ graphql_object!(QueryRoot: Context as "Query" |&self| {
             .map_err(|err| FieldError::new(err, juniper::Value::null()))
     }
 
-    field ps(&executor, pids: Option<Vec<i32>>) -> FieldResult<Vec<PSResponse>>
+    field cpu(&executor, sample_ms = 100: i32) -> FieldResult<CpuResponse> {
+        let sample = sample_duration(sample_ms)?;
+        let before = cpu::CpuStat::from_proc()
+            .map_err(|err| FieldError::new(err, juniper::Value::null()))?;
+        thread::sleep(sample);
+        let after = cpu::CpuStat::from_proc()
+            .map_err(|err| FieldError::new(err, juniper::Value::null()))?;
+
+        let cpus = after.cpus().iter()
+            .filter_map(|cpu| {
+                before.cpus().iter()
+                    .find(|earlier| earlier.name() == cpu.name())
+                    .map(|earlier| cpu.usage_since(earlier))
+            })
+            .collect();
+
+        Ok(CpuResponse {
+            total: after.total().usage_since(before.total()),
+            cpus,
+        })
+    }
+
+    field load_avg(&executor) -> FieldResult<LoadAvgResponse> {
+        cpu::LoadAvg::from_proc()
+            .map(|load| LoadAvgResponse { load })
+            .map_err(|err| FieldError::new(err, juniper::Value::null()))
+    }
+
+    field filesystems(&executor, all = false: bool) -> FieldResult<Vec<FilesystemResponse>> {
+        let mounts = filesystem::Mount::from_proc()
+            .map_err(|err| FieldError::new(err, juniper::Value::null()))?;
+
+        // Pseudo-filesystems like proc and sysfs have no blocks, so skip them unless asked
+        Ok(mounts.into_iter()
+            .map(|mount| {
+                let usage = filesystem::FsUsage::from_path(mount.mount_point()).ok();
+                FilesystemResponse { mount, usage }
+            })
+            .filter(|fs| all || fs.usage.as_ref().map_or(false, |u| u.total_bytes() > 0))
+            .collect())
+    }
+
+    field path_usage(&executor) -> Vec<PathUsageResponse> {
+        executor.context().subsystem().usage_paths.iter()
+            .map(|path| PathUsageResponse {
+                path: path.clone(),
+                usage: filesystem::PathUsage::from_path(path).map_err(|err| err.to_string()),
+            })
+            .collect()
+    }
+
+    field net(&executor) -> FieldResult<NetResponse> {
+        let interfaces = net::InterfaceStats::from_proc()
+            .map_err(|err| FieldError::new(err, juniper::Value::null()))?;
+        let sockets = net::UdpSocket::from_proc()
+            .map_err(|err| FieldError::new(err, juniper::Value::null()))?;
+        let owners = net::socket_owners()
+            .map_err(|err| FieldError::new(err, juniper::Value::null()))?;
+
+        let udp = sockets.into_iter()
+            .map(|socket| {
+                let pid = owners.get(&socket.inode()).cloned();
+                UdpSocketResponse { socket, pid }
+            })
+            .collect();
+
+        Ok(NetResponse { interfaces, udp })
+    }
+
+    field ps(&executor, pids: Option<Vec<i32>>, sample_ms = 0: i32) -> FieldResult<Vec<PSResponse>>
     {
         let pids_vec: Vec<i32> = match pids {
             Some(vec) => vec,
             None => process::running_pids()?
         };
 
-        Ok(pids_vec.into_iter().map(|pid| PSResponse::new(pid)).collect())
+        let sample = sample_duration(sample_ms)?;
+        if sample_ms == 0 {
+            return Ok(pids_vec.into_iter().map(|pid| PSResponse::new(pid)).collect());
+        }
+
+        // Sample the processes' CPU times twice, so their usage in between can be calculated
+        let cpu_before = cpu::CpuStat::from_proc()
+            .map_err(|err| FieldError::new(err, juniper::Value::null()))?;
+        let before: Vec<Option<process::ProcStat>> = pids_vec.iter()
+            .map(|pid| process::ProcStat::from_pid(*pid).ok())
+            .collect();
+
+        thread::sleep(sample);
+
+        let cpu_after = cpu::CpuStat::from_proc()
+            .map_err(|err| FieldError::new(err, juniper::Value::null()))?;
+
+        Ok(pids_vec.into_iter().zip(before.into_iter()).map(|(pid, before)| {
+            let mut response = PSResponse::new(pid);
+            response.cpu = match (before, response.stat.as_ref()) {
+                (Some(before), Some(after)) => {
+                    let ticks = (after.utime() + after.stime())
+                        .saturating_sub(before.utime() + before.stime());
+                    Some(cpu_after.process_usage(&cpu_before, ticks))
+                }
+                _ => None,
+            };
+            response
+        }).collect())
     }
 });
 
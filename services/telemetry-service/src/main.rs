@@ -47,7 +47,7 @@
 //!
 //! # Panics
 //!
-//! Attempts to grab database path from Configuration and will `panic!` if not found.
+//! Exits with an error if the configuration file can't be loaded or has no `database` path.
 //! Attempts to connect to database at provided path and will `panic!` if connection fails.
 //! Attempts to create telemetry table and will `panic!` if table creation fails.
 //!
@@ -166,23 +166,67 @@ extern crate diesel;
 extern crate juniper;
 extern crate kubos_service;
 extern crate kubos_telemetry_db;
+#[macro_use]
+extern crate serde_derive;
 
 mod schema;
 
-use kubos_service::{Config, Service};
+use kubos_service::{Config, ConfigWatcher, Service, SERVICE_CONFIG_KEYS};
 use kubos_telemetry_db::Database;
 use schema::{MutationRoot, QueryRoot};
+use std::process;
+use std::thread;
+
+/// Settings read from the `[telemetry-service]` config section.
+///
+/// The common `kubos_service` settings (ex. `chunk_size`) are skipped when loading
+/// these, so that any other unknown field is reported as an error.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TelemetryConfig {
+    /// Path to the telemetry database file
+    database: String,
+}
 
 fn main() {
-    let config = Config::new("telemetry-service");
+    let config = Config::try_new("telemetry-service").unwrap_or_else(|err| {
+        eprintln!("Failed to load service config: {}", err);
+        process::exit(1);
+    });
 
-    let db_path = config
-        .get("database")
-        .expect("No database path found in config file");
-    let db_path = db_path.as_str().unwrap_or("");
+    let settings: TelemetryConfig = config
+        .deserialize_ignoring(SERVICE_CONFIG_KEYS)
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to load service config: {}", err);
+            process::exit(1);
+        });
 
-    let db = Database::new(&db_path);
+    let db = Database::new(&settings.database);
     db.setup();
 
+    // None of the service's settings can be changed while it's running,
+    // so just let the operator know when a restart is needed
+    let watcher = ConfigWatcher::new("telemetry-service")
+        .restart_required(&["database"])
+        .restart_required(SERVICE_CONFIG_KEYS);
+    let updates = watcher.subscribe();
+    let errors = watcher.errors();
+    watcher.start();
+    thread::spawn(move || {
+        for change in updates {
+            for key in change.restart_required {
+                eprintln!(
+                    "Config value '{}' changed. Restart the service to apply it",
+                    key
+                );
+            }
+        }
+    });
+    thread::spawn(move || {
+        for err in errors {
+            eprintln!("Ignoring changes to the config file: {}", err);
+        }
+    });
+
     Service::new(config, db, QueryRoot, MutationRoot).start();
 }
@@ -0,0 +1,175 @@
+//
+// Copyright (C) 2018 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+#![deny(missing_docs)]
+#![deny(warnings)]
+
+//! Kubos Service for managing upgrades and rollbacks of KubOS itself.
+//!
+//! Upgrade packages (`kpack-<version>.itb` files) are first delivered to the system with the
+//! file transfer service. This service then verifies them, copies them into the upgrade
+//! directory and sets the U-Boot variables which cause the package to be installed on the
+//! next boot. The service never reboots the system itself.
+//!
+//! # Configuration
+//!
+//! The service can be configured in the `/home/system/etc/config.toml` with the following fields:
+//!
+//! ```toml,ignore
+//! [upgrade-service]
+//! # Directory which upgrade packages are installed from (default "/upgrade")
+//! upgrade_dir = "/upgrade"
+//! # Command used to verify package signatures, run as `<verifier> <package> <signature> <key>`
+//! verifier = "/home/system/usr/bin/verify-kpack"
+//! # Key which every upgrade package must be signed with
+//! signing_key = "/home/system/etc/kpack-key.pem"
+//!
+//! [upgrade-service.addr]
+//! ip = "127.0.0.1"
+//! port = 8090
+//! ```
+//!
+//! When a `signing_key` is configured, every package must come with a signature which the
+//! `verifier` accepts. The service refuses to start if the key file is missing, or if no
+//! `verifier` is given to check signatures with.
+//!
+//! ## Unsigned Packages
+//!
+//! If neither a `signing_key` nor a `verifier` is configured, packages are only checked against
+//! their BLAKE2s checksum (the same hash used by the file transfer service) and signatures are
+//! rejected. The checksum catches packages which were corrupted in transit, but anyone able to
+//! send the service a mutation can install a package of their choosing. Systems which can be
+//! commanded over an untrusted link should always be configured with a `signing_key`.
+//!
+//! # GraphQL Schema
+//!
+//! ```graphql
+//! type Query {
+//!     ping: String!
+//!     status: UpgradeStatus!
+//! }
+//!
+//! type UpgradeStatus {
+//!     current: String
+//!     previous: String
+//!     pending: String
+//! }
+//!
+//! type Mutation {
+//!     stage(path: String!, hash: String!, signature: String): String!
+//!     rollback: String!
+//!     cancel: Boolean!
+//! }
+//! ```
+//!
+//! # Example Mutations
+//!
+//! ## Stage an upgrade package to be installed on the next boot
+//! ```graphql
+//! mutation {
+//!     stage(path: "/home/system/file-transfer/kpack-2.0.itb", hash: "e3e2e2d34cc5e1b7c6a2a1d0a1c1e4f5")
+//! }
+//! ```
+//!
+//! ## Reinstall the previous version of KubOS on the next boot
+//! ```graphql
+//! mutation {
+//!     rollback
+//! }
+//! ```
+
+#[macro_use]
+extern crate juniper;
+extern crate kubos_service;
+extern crate kubos_system;
+#[macro_use]
+extern crate serde_derive;
+#[cfg(test)]
+#[macro_use]
+extern crate serde_json;
+#[cfg(test)]
+extern crate tempfile;
+
+mod schema;
+#[cfg(test)]
+mod tests;
+
+use kubos_service::{Config, Service, SERVICE_CONFIG_KEYS};
+use kubos_system::{UBootVars, Upgrader, UPGRADE_DIR};
+use schema::{MutationRoot, QueryRoot};
+use std::path::Path;
+use std::process;
+
+/// Settings read from the `[upgrade-service]` config section.
+///
+/// Unknown fields are rejected, so that a misspelled `signing_key` can't silently
+/// disable signature checks.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UpgradeConfig {
+    #[serde(default = "default_upgrade_dir")]
+    upgrade_dir: String,
+    #[serde(default)]
+    verifier: Option<String>,
+    #[serde(default)]
+    signing_key: Option<String>,
+}
+
+fn default_upgrade_dir() -> String {
+    UPGRADE_DIR.to_owned()
+}
+
+fn main() {
+    let config = Config::try_new("upgrade-service").unwrap_or_else(|err| {
+        eprintln!("Failed to load service config: {}", err);
+        process::exit(1);
+    });
+
+    let settings: UpgradeConfig = config
+        .deserialize_ignoring(SERVICE_CONFIG_KEYS)
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to load service config: {}", err);
+            process::exit(1);
+        });
+
+    let upgrader = new_upgrader(UBootVars::new(), &settings).unwrap_or_else(|err| {
+        eprintln!("Failed to load service config: {}", err);
+        process::exit(1);
+    });
+
+    Service::new(config, upgrader, QueryRoot, MutationRoot).start();
+}
+
+/// Creates the upgrader described by the service's settings, checking that packages can
+/// be verified if a signing key is configured
+fn new_upgrader(vars: UBootVars, settings: &UpgradeConfig) -> Result<Upgrader, String> {
+    let mut upgrader = Upgrader::new_with(vars, &settings.upgrade_dir);
+    if let Some(ref verifier) = settings.verifier {
+        upgrader = upgrader.verifier(verifier);
+    }
+
+    if let Some(ref key) = settings.signing_key {
+        if settings.verifier.is_none() {
+            return Err("signing_key is set, but no verifier is configured".to_owned());
+        }
+        if !Path::new(key).is_file() {
+            return Err(format!("signing_key {} does not exist", key));
+        }
+        upgrader = upgrader.signing_key(key);
+    }
+
+    Ok(upgrader)
+}
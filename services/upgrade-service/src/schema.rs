@@ -0,0 +1,97 @@
+//
+// Copyright (C) 2018 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use juniper::{FieldError, FieldResult, Value};
+use kubos_service;
+use kubos_system::{self, Upgrader};
+
+type Context = kubos_service::Context<Upgrader>;
+
+pub struct UpgradeStatus(pub kubos_system::UpgradeStatus);
+
+graphql_object!(UpgradeStatus: () as "UpgradeStatus" |&self| {
+    description: "Versions of KubOS known to the bootloader"
+
+    field current() -> FieldResult<Option<String>>
+        as "The version of KubOS which is currently running"
+    {
+        Ok(self.0.current.clone())
+    }
+
+    field previous() -> FieldResult<Option<String>>
+        as "The version of KubOS which was running before the current one"
+    {
+        Ok(self.0.previous.clone())
+    }
+
+    field pending() -> FieldResult<Option<String>>
+        as "The version of KubOS which will be installed on the next boot"
+    {
+        Ok(self.0.pending.clone())
+    }
+});
+
+pub struct QueryRoot;
+
+/// Base GraphQL query model
+graphql_object!(QueryRoot: Context as "Query" |&self| {
+    field ping() -> FieldResult<String>
+    {
+        Ok(String::from("pong"))
+    }
+
+    field status(&executor) -> FieldResult<UpgradeStatus>
+        as "Current upgrade status"
+    {
+        Ok(UpgradeStatus(executor.context().subsystem().status()))
+    }
+});
+
+pub struct MutationRoot;
+
+/// Base GraphQL mutation model
+graphql_object!(MutationRoot: Context as "Mutation" |&self| {
+    field stage(&executor, path: String, hash: String, signature: Option<String>) -> FieldResult<String>
+        as "Verify an upgrade package and install it on the next boot"
+    {
+        executor
+            .context()
+            .subsystem()
+            .stage(&path, &hash, signature.as_ref().map(|sig| sig.as_str()))
+            .map_err(|err| FieldError::new(err, Value::null()))
+    }
+
+    field rollback(&executor) -> FieldResult<String>
+        as "Reinstall the previous version of KubOS on the next boot"
+    {
+        executor
+            .context()
+            .subsystem()
+            .rollback()
+            .map_err(|err| FieldError::new(err, Value::null()))
+    }
+
+    field cancel(&executor) -> FieldResult<bool>
+        as "Cancel a pending upgrade or rollback"
+    {
+        executor
+            .context()
+            .subsystem()
+            .cancel()
+            .map(|_| true)
+            .map_err(|err| FieldError::new(err, Value::null()))
+    }
+});
@@ -0,0 +1,187 @@
+//
+// Copyright (C) 2018 Kubos Corporation
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// The fake U-Boot environment shared with the kubos-system tests
+#[path = "../../../apis/system-api/tests/common/mod.rs"]
+mod common;
+
+use super::*;
+use std::fs;
+use tempfile::TempDir;
+
+// BLAKE2s checksum of "upgrade"
+const UPGRADE_HASH: &'static str = "f115cf39c70693ac44166d3fe16ac100";
+
+fn mock_service(dir: &TempDir) -> Service<'static, QueryRoot, MutationRoot, Upgrader> {
+    let upgrade_dir = dir.path().join("upgrade");
+
+    Service::new(
+        Config::new_from_str("upgrade-service", ""),
+        Upgrader::new_with(common::open_vars(dir.path()), upgrade_dir.to_str().unwrap()),
+        QueryRoot,
+        MutationRoot,
+    )
+}
+
+fn settings(verifier: Option<&str>, signing_key: Option<&str>) -> UpgradeConfig {
+    UpgradeConfig {
+        upgrade_dir: UPGRADE_DIR.to_owned(),
+        verifier: verifier.map(|verifier| verifier.to_owned()),
+        signing_key: signing_key.map(|key| key.to_owned()),
+    }
+}
+
+#[test]
+fn status_empty() {
+    let (dir, _) = common::setup_file_vars();
+    let service = mock_service(&dir);
+
+    let expected = json!({
+        "errs": "",
+        "msg": {
+            "status": {
+                "current": null,
+                "previous": null,
+                "pending": null
+            }
+        }
+    });
+
+    assert_eq!(
+        service.process("{ status { current, previous, pending } }".to_owned()),
+        expected.to_string()
+    );
+}
+
+#[test]
+fn stage_and_cancel() {
+    let (dir, _) = common::setup_file_vars();
+    let service = mock_service(&dir);
+
+    let package = dir.path().join("kpack-2.0.itb");
+    fs::write(&package, "upgrade").unwrap();
+
+    let stage = format!(
+        r#"mutation {{ stage(path: "{}", hash: "{}") }}"#,
+        package.display(),
+        UPGRADE_HASH
+    );
+    assert_eq!(
+        service.process(stage),
+        json!({"errs": "", "msg": {"stage": "kpack-2.0.itb"}}).to_string()
+    );
+    assert_eq!(
+        service.process("{ status { pending } }".to_owned()),
+        json!({"errs": "", "msg": {"status": {"pending": "kpack-2.0.itb"}}}).to_string()
+    );
+
+    assert_eq!(
+        service.process("mutation { cancel }".to_owned()),
+        json!({"errs": "", "msg": {"cancel": true}}).to_string()
+    );
+    assert_eq!(
+        service.process("{ status { pending } }".to_owned()),
+        json!({"errs": "", "msg": {"status": {"pending": null}}}).to_string()
+    );
+}
+
+#[test]
+fn stage_bad_checksum() {
+    let (dir, _) = common::setup_file_vars();
+    let service = mock_service(&dir);
+
+    let package = dir.path().join("kpack-2.0.itb");
+    fs::write(&package, "corrupted").unwrap();
+
+    let stage = format!(
+        r#"mutation {{ stage(path: "{}", hash: "{}") }}"#,
+        package.display(),
+        UPGRADE_HASH
+    );
+    let response: serde_json::Value = serde_json::from_str(&service.process(stage)).unwrap();
+
+    assert!(response["errs"]
+        .as_str()
+        .unwrap()
+        .contains("Checksum mismatch for kpack-2.0.itb"));
+    assert_eq!(response["msg"], json!(null));
+}
+
+#[test]
+fn rollback_no_previous() {
+    let (dir, _) = common::setup_file_vars();
+    let service = mock_service(&dir);
+
+    let response: serde_json::Value =
+        serde_json::from_str(&service.process("mutation { rollback }".to_owned())).unwrap();
+
+    assert!(response["errs"]
+        .as_str()
+        .unwrap()
+        .contains("No previous version of KubOS to roll back to"));
+}
+
+#[test]
+fn signing_key_requires_verifier() {
+    let (dir, vars) = common::setup_file_vars();
+    let key = dir.path().join("kpack-key.pem");
+    fs::write(&key, "key").unwrap();
+
+    match new_upgrader(vars, &settings(None, key.to_str())) {
+        Err(err) => assert_eq!(err, "signing_key is set, but no verifier is configured"),
+        Ok(_) => panic!("Upgrader created without a verifier"),
+    }
+}
+
+#[test]
+fn signing_key_missing() {
+    let (_dir, vars) = common::setup_file_vars();
+
+    match new_upgrader(vars, &settings(Some("/fake/verify"), Some("/fake/key.pem"))) {
+        Err(err) => assert_eq!(err, "signing_key /fake/key.pem does not exist"),
+        Ok(_) => panic!("Upgrader created without a signing key"),
+    }
+}
+
+#[test]
+fn signing_key_required() {
+    let (dir, vars) = common::setup_file_vars();
+    let key = dir.path().join("kpack-key.pem");
+    fs::write(&key, "key").unwrap();
+
+    let upgrader = new_upgrader(vars, &settings(Some("/fake/verify"), key.to_str())).unwrap();
+    let service = Service::new(
+        Config::new_from_str("upgrade-service", ""),
+        upgrader,
+        QueryRoot,
+        MutationRoot,
+    );
+
+    let package = dir.path().join("kpack-2.0.itb");
+    fs::write(&package, "upgrade").unwrap();
+
+    let stage = format!(
+        r#"mutation {{ stage(path: "{}", hash: "{}") }}"#,
+        package.display(),
+        UPGRADE_HASH
+    );
+    let response: serde_json::Value = serde_json::from_str(&service.process(stage)).unwrap();
+
+    assert!(response["errs"]
+        .as_str()
+        .unwrap()
+        .contains("A signature is required to stage kpack-2.0.itb"));
+}